@@ -0,0 +1,127 @@
+//! The `cal` command: a month, or a whole year, laid out as a calendar grid.
+
+use crate::shell::{CommandOutput, ShellContext};
+use crate::theme::Theme;
+use chrono::{Datelike, Local, NaiveDate};
+
+/// Width of a month in cells: seven two-digit days with a space between each.
+const MONTH_WIDTH: usize = 20;
+
+/// Space between the months of a year printed side by side.
+const MONTH_GAP: &str = "  ";
+
+const MONTH_NAMES: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November",
+    "December",
+];
+
+/// Returns `text` centered in `width` cells, padded on both sides.
+fn center(text: &str, width: usize) -> String {
+    let left = width.saturating_sub(text.len()) / 2;
+    format!("{:left$}{:<right$}", "", text, right = width - left)
+}
+
+/// Lays out one month as rows exactly `MONTH_WIDTH` cells wide: the title, the weekday
+/// names, and six weeks starting on Sunday, blank where the month has no days. The day
+/// `today` is highlighted, if it falls in this month.
+fn month_rows(year: i32, month: u32, title: &str, today: Option<NaiveDate>, theme: &Theme) -> Vec<String> {
+    let first = NaiveDate::from_ymd_opt(year, month, 1).unwrap_or_default();
+    let offset = first.weekday().num_days_from_sunday() as usize;
+
+    let mut cells = vec!["  ".to_string(); offset];
+    cells.extend(first.iter_days().take_while(|day| day.month() == month).map(|day| {
+        let number = format!("{:>2}", day.day());
+        if Some(day) == today {
+            theme.highlight(number)
+        } else {
+            number
+        }
+    }));
+    cells.resize(42, "  ".to_string());
+
+    let mut rows = vec![center(title, MONTH_WIDTH), "Su Mo Tu We Th Fr Sa".to_string()];
+    rows.extend(cells.chunks(7).map(|week| week.join(" ")));
+    rows
+}
+
+/// Lays out a whole year, three months across, under the year as a title.
+fn year_rows(year: i32, today: Option<NaiveDate>, theme: &Theme) -> Vec<String> {
+    let width = MONTH_WIDTH * 3 + MONTH_GAP.len() * 2;
+    let mut rows = vec![center(&year.to_string(), width)];
+    for quarter in 0..4 {
+        rows.push(String::new());
+        let months: Vec<Vec<String>> = (1..=3)
+            .map(|column| {
+                let month = quarter * 3 + column;
+                month_rows(year, month, MONTH_NAMES[month as usize - 1], today, theme)
+            })
+            .collect();
+        rows.extend((0..months[0].len()).map(|row| {
+            months.iter().map(|month| month[row].as_str()).collect::<Vec<_>>().join(MONTH_GAP)
+        }));
+    }
+    rows
+}
+
+/// Handles `cal`: shows the current month with today highlighted, `cal MM YYYY` for another
+/// month, and `cal -y [YYYY]` or `cal YYYY` for a whole year. Today is the date in the
+/// local time zone.
+pub fn cal_command(args: Vec<&str>, ctx: &ShellContext) -> CommandOutput {
+    let today = Local::now().date_naive();
+
+    let whole_year = args.contains(&"-y");
+    let numbers: Result<Vec<i32>, _> = args.iter().filter(|&&arg| arg != "-y").map(|arg| arg.parse::<i32>()).collect();
+    let (year, month) = match (numbers.as_deref(), whole_year) {
+        (Ok([]), _) => (today.year(), Some(today.month() as i32)),
+        (Ok(&[year]), _) => (year, None),
+        (Ok(&[month, year]), false) => (year, Some(month)),
+        _ => return CommandOutput::error("Error: Usage: cal [[month] year] | cal -y [year]"),
+    };
+    if !(1..=9999).contains(&year) {
+        return CommandOutput::error(format!("Error: cal: year '{}' is not in 1-9999.", year));
+    }
+
+    let theme = &ctx.options.theme;
+    let rows = match month {
+        _ if whole_year => year_rows(year, Some(today), theme),
+        Some(month @ 1..=12) => {
+            let title = format!("{} {}", MONTH_NAMES[month as usize - 1], year);
+            month_rows(year, month as u32, &title, Some(today), theme)
+        }
+        Some(month) => return CommandOutput::error(format!("Error: cal: month '{}' is not in 1-12.", month)),
+        None => year_rows(year, Some(today), theme),
+    };
+    let mut lines: Vec<&str> = rows.iter().map(|row| row.trim_end()).collect();
+    while lines.last() == Some(&"") {
+        lines.pop();
+    }
+    CommandOutput::success(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: Theme = Theme { colored: false, ..Theme::DEFAULT };
+
+    #[test]
+    fn months_start_on_the_right_weekday() {
+        let rows = month_rows(2024, 2, "February 2024", None, &PLAIN);
+        assert_eq!(rows[0], "   February 2024    ");
+        assert_eq!(rows[2], "             1  2  3");
+        assert_eq!(rows[6], "25 26 27 28 29      ");
+        assert_eq!(rows.len(), 8);
+        assert!(rows.iter().all(|row| row.len() == MONTH_WIDTH));
+    }
+
+    #[test]
+    fn today_is_highlighted_and_years_are_three_across() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 15);
+        let rows = month_rows(2026, 10, "October 2026", today, &Theme::DEFAULT);
+        assert!(rows[4].contains(&Theme::DEFAULT.highlight("15")));
+
+        let rows = year_rows(2026, None, &PLAIN);
+        assert_eq!(rows[2], format!("{}  {}  {}", center("January", 20), center("February", 20), center("March", 20)));
+        assert_eq!(rows.len(), 1 + 4 * 9);
+    }
+}
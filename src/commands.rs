@@ -0,0 +1,1752 @@
+//! Built-in commands that work with files, directories, and the session.
+
+use crate::shell::{
+    combine_outputs, expand_path, handle_command, join_output, quote_word, run_pipeline, run_script,
+    split_unquoted, strip_ansi, CommandOutput, ShellContext, ShellOptions, BUILTIN_COMMANDS,
+};
+use arboard::Clipboard;
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeZone};
+use regex::Regex;
+use crate::expand::expand_alias;
+use crate::filesystem::FileInfo;
+use crate::rc::save_alias;
+use crate::render::layout_columns;
+use std::cmp::Ordering;
+use std::env;
+use std::io::{ErrorKind, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How `ls -l` shows times, and one of the forms `touch -d` reads.
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// How deeply `source` commands may nest before a script is assumed to source itself forever.
+const MAX_SOURCE_DEPTH: usize = 64;
+
+/// Size in bytes from which `cp --progress` shows how far a copy has got.
+const PROGRESS_THRESHOLD: u64 = 1024 * 1024;
+
+/// The longest `sleep` the interactive screen waits through, unable to redraw meanwhile.
+const MAX_FOREGROUND_SLEEP: Duration = Duration::from_secs(10);
+
+/// The order `ls` lists entries in.
+#[derive(Clone, Copy, Default, PartialEq)]
+enum LsSort {
+    #[default]
+    Name,
+    /// Newest first (`-t`).
+    Time,
+    /// Largest first (`-S`).
+    Size,
+}
+
+/// Flags accepted by `ls`.
+#[derive(Default)]
+struct LsOptions {
+    /// Show type, size, and modification time before each name (`-l`).
+    long: bool,
+    sort: LsSort,
+    /// Reverse the sort order (`-r`).
+    reverse: bool,
+    /// List directories before files (`--group-directories-first`).
+    group_directories: bool,
+    /// Wrap names with spaces or special characters in double quotes (`-Q`).
+    quote_names: bool,
+    /// Show what symlinks point to rather than the links themselves (`-L`).
+    dereference: bool,
+    /// List one entry per line even on a terminal (`-1`).
+    one_per_line: bool,
+}
+
+/// Lists the contents of a directory, or the current directory if none is given.
+///
+/// Entries are sorted by name, or with `-t` by modification time and with `-S` by size,
+/// and `-r` reverses the order. `-l` shows each entry's type, size, and modification time.
+/// `--group-directories-first` lists directories ahead of files, each group sorted as usual.
+/// `-Q` (`--quote-names`) puts names with spaces or special characters in double quotes.
+/// Names go one per line, or in columns when the output goes to a terminal and neither
+/// `-l` nor `-1` is given.
+///
+/// Symlinks are listed as themselves, shown by `-l` as `name -> target`. With `-L`
+/// (`--dereference`) each one is listed as the file or directory it points to instead,
+/// unless that is missing.
+pub fn list_directory(ctx: &ShellContext, args: Vec<&str>) -> CommandOutput {
+    let mut options = LsOptions::default();
+    let mut dir_name = ".";
+    let mut options_ended = false;
+    for arg in args {
+        match arg.strip_prefix('-') {
+            _ if options_ended => dir_name = arg,
+            Some("-") => options_ended = true,
+            Some("-group-directories-first") => options.group_directories = true,
+            Some("-quote-names") => options.quote_names = true,
+            Some("-dereference") => options.dereference = true,
+            Some(flags) if flags.starts_with('-') => {
+                return CommandOutput::error(format!("Error: ls: unrecognized option '{}'.", arg))
+            }
+            Some(flags) if !flags.is_empty() => {
+                for flag in flags.chars() {
+                    match flag {
+                        // Every entry is listed already, hidden or not
+                        'a' => {}
+                        'l' => options.long = true,
+                        '1' => options.one_per_line = true,
+                        't' => options.sort = LsSort::Time,
+                        'S' => options.sort = LsSort::Size,
+                        'r' => options.reverse = true,
+                        'Q' => options.quote_names = true,
+                        'L' => options.dereference = true,
+                        _ => return CommandOutput::error(format!("Error: ls: invalid option '-{}'.", flag)),
+                    }
+                }
+            }
+            _ => dir_name = arg,
+        }
+    }
+
+    let path = match expand_path(ctx, dir_name) {
+        Ok(path) => path,
+        Err(e) => return CommandOutput::error(e),
+    };
+    let names = match ctx.fs.read_dir(&path) {
+        Ok(names) => names,
+        Err(e) => return CommandOutput::error(format!("Error: {}", e)),
+    };
+
+    let mut entries: Vec<(String, FileInfo)> = names
+        .into_iter()
+        .map(|name| {
+            let entry = path.join(&name);
+            let own = ctx.fs.symlink_metadata(&entry).unwrap_or_default();
+            // A broken link has nothing to show in its place, so it is listed as a link
+            let info = if options.dereference && own.is_symlink { ctx.fs.metadata(&entry).unwrap_or(own) } else { own };
+            (name, info)
+        })
+        .collect();
+    sort_entries(&mut entries, &options);
+
+    let size_width = entries.iter().map(|(_, info)| info.len.to_string().len()).max().unwrap_or(0);
+    let lines: Vec<String> = entries
+        .iter()
+        .map(|(name, info)| {
+            let quoted = if options.quote_names { quote_name(name) } else { name.clone() };
+            let shown = if info.is_dir { ctx.options.theme.directory(quoted) } else { quoted };
+            if !options.long {
+                return shown;
+            }
+            let kind = if info.is_symlink { 'l' } else if info.is_dir { 'd' } else { '-' };
+            let modified = info.modified.map(format_time).unwrap_or_else(|| "-".to_string());
+            let line = format!("{} {:>width$} {:<16} {}", kind, info.len, modified, shown, width = size_width);
+            match info.is_symlink.then(|| ctx.fs.read_link(&path.join(name))) {
+                Some(Ok(target)) => format!("{} -> {}", line, target.display()),
+                _ => line,
+            }
+        })
+        .collect();
+
+    // On a terminal, names alone are laid out in columns across its width
+    match ctx.terminal_width {
+        Some(width) if !options.long && !options.one_per_line => {
+            CommandOutput::success(layout_columns(&lines, width).join("\n"))
+        }
+        _ => CommandOutput::success(lines.join("\n")),
+    }
+}
+
+/// Sorts `ls` entries by the chosen key, breaking ties by name.
+fn sort_entries(entries: &mut [(String, FileInfo)], options: &LsOptions) {
+    entries.sort_by(|(a_name, a), (b_name, b)| {
+        let by_key = match options.sort {
+            LsSort::Name => Ordering::Equal,
+            LsSort::Time => b.modified.cmp(&a.modified),
+            LsSort::Size => b.len.cmp(&a.len),
+        };
+        by_key.then_with(|| a_name.cmp(b_name))
+    });
+    if options.reverse {
+        entries.reverse();
+    }
+    if options.group_directories {
+        // A stable sort keeps each group in the order chosen above
+        entries.sort_by_key(|(_, info)| !info.is_dir);
+    }
+}
+
+/// Puts `name` in double quotes if it contains whitespace, control characters, or characters
+/// a shell treats specially, escaping quotes, backslashes, and control characters inside
+/// the way GNU `ls --quoting-style=c` does. Other names are returned as they are.
+fn quote_name(name: &str) -> String {
+    let special = |c: char| c.is_whitespace() || c.is_control() || "\"'\\$`&;|<>()*?[]{}#~!".contains(c);
+    if !name.chars().any(special) {
+        return name.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in name.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\{:03o}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Formats a time as `YYYY-MM-DD HH:MM` in the local time zone.
+fn format_time(time: SystemTime) -> String {
+    DateTime::<Local>::from(time).format(TIME_FORMAT).to_string()
+}
+
+/// Returns the time that a date and time of day name in the local time zone, or `None` if
+/// they name no time there, as within the hour skipped when clocks go forward.
+fn local_time(date_time: NaiveDateTime) -> Option<SystemTime> {
+    Local.from_local_datetime(&date_time).earliest().map(SystemTime::from)
+}
+
+/// Parses a `touch -t` stamp, `[[CC]YY]MMDDhhmm[.ss]`, in the local time zone. Without a
+/// year the current one is used, and a two-digit year means 1969 to 2068.
+fn parse_stamp(stamp: &str) -> Option<SystemTime> {
+    let (digits, second) = match stamp.split_once('.') {
+        Some((digits, second)) if second.len() == 2 => (digits, second.parse().ok()?),
+        Some(_) => return None,
+        None => (stamp, 0),
+    };
+    if !digits.bytes().all(|b| b.is_ascii_digit()) || ![8, 10, 12].contains(&digits.len()) {
+        return None;
+    }
+    let (year, rest) = digits.split_at(digits.len() - 8);
+    let field = |start: usize| rest[start..start + 2].parse::<u32>().ok();
+    let year = match year.len() {
+        0 => Local::now().year(),
+        2 => {
+            let short: i32 = year.parse().ok()?;
+            if short >= 69 { 1900 + short } else { 2000 + short }
+        }
+        _ => year.parse().ok()?,
+    };
+    let date = NaiveDate::from_ymd_opt(year, field(0)?, field(2)?)?;
+    local_time(date.and_hms_opt(field(4)?, field(6)?, second)?)
+}
+
+/// Parses a `touch -d` date in the local time zone: `YYYY-MM-DD`, optionally followed by a
+/// space or `T` and `HH:MM` or `HH:MM:SS`, or `@<seconds>` since the Unix epoch. `ls -l`
+/// shows times in one of these forms, so they can be copied from its listing.
+fn parse_date(date: &str) -> Option<SystemTime> {
+    if let Some(seconds) = date.strip_prefix('@') {
+        return DateTime::from_timestamp(seconds.parse().ok()?, 0).map(SystemTime::from);
+    }
+    let formats = [TIME_FORMAT, "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S"];
+    let date_time = match formats.iter().find_map(|format| NaiveDateTime::parse_from_str(date, format).ok()) {
+        Some(date_time) => date_time,
+        None => NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0)?,
+    };
+    local_time(date_time)
+}
+
+/// Returns the current working directory.
+///
+/// `-P` prints the physical path with symlinks resolved, and `-L` (the default) prints
+/// the path as the emulator tracks it. The last of the two flags wins.
+pub fn current_directory(ctx: &ShellContext, args: Vec<&str>) -> CommandOutput {
+    let mut physical = false;
+    for arg in args {
+        match arg {
+            "-P" => physical = true,
+            "-L" => physical = false,
+            _ => return CommandOutput::error(format!("Error: pwd: invalid option '{}'.", arg)),
+        }
+    }
+
+    let path = match ctx.fs.current_dir() {
+        Ok(path) => path,
+        Err(e) => return CommandOutput::error(format!("Error: {}", e)),
+    };
+    if !physical {
+        return CommandOutput::success(path.display().to_string());
+    }
+    match ctx.fs.canonicalize(&path) {
+        Ok(resolved) => CommandOutput::success(resolved.display().to_string()),
+        Err(e) => CommandOutput::error(format!("Error resolving '{}': {}", path.display(), e)),
+    }
+}
+
+/// Reads the content of a file.
+pub fn read_file(ctx: &ShellContext, file_name: &str) -> CommandOutput {
+    if file_name.is_empty() {
+        return CommandOutput::error("Error: File name is required.");
+    }
+    let path = match expand_path(ctx, file_name) {
+        Ok(path) => path,
+        Err(e) => return CommandOutput::error(e),
+    };
+    match ctx.fs.read_to_string(&path) {
+        Ok(content) => CommandOutput::success(content),
+        Err(e) => CommandOutput::error(format!("Error reading file '{}': {}", file_name, e)),
+    }
+}
+
+/// The text a command reads: the contents of each file it was given, or the piped input
+/// when it was given none.
+pub struct Inputs {
+    /// Each source that could be read, by name; piped input is named `-`.
+    pub sources: Vec<(String, String)>,
+    /// Messages for the files that could not be read.
+    pub errors: Vec<String>,
+}
+
+impl Inputs {
+    /// Returns 0 if every source was read and 1 otherwise.
+    pub fn exit_code(&self) -> i32 {
+        if self.errors.is_empty() {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+/// Reads each of `files`, or `stdin` when no files are given. A file named `-` also reads
+/// `stdin`. Without files or piped input, reports that a file name is required.
+pub fn read_inputs(files: &[&str], stdin: Option<&str>, ctx: &ShellContext) -> Inputs {
+    let mut inputs = Inputs { sources: Vec::new(), errors: Vec::new() };
+    if files.is_empty() {
+        match stdin {
+            Some(input) => inputs.sources.push(("-".to_string(), input.to_string())),
+            None => inputs.errors.push("Error: File name is required.".to_string()),
+        }
+        return inputs;
+    }
+
+    for file_name in files {
+        match (*file_name, stdin) {
+            ("-", Some(input)) => inputs.sources.push(("-".to_string(), input.to_string())),
+            _ => {
+                let output = read_file(ctx, file_name);
+                if output.exit_code == 0 {
+                    inputs.sources.push((file_name.to_string(), output.stdout));
+                } else {
+                    inputs.errors.push(output.stderr);
+                }
+            }
+        }
+    }
+    inputs
+}
+
+/// Handles `cat`: prints files one after another, or piped input when no file is given.
+///
+/// `-s` squeezes each run of blank lines, including at the start and end, down to one.
+/// `--lf` shows CRLF line endings as plain newlines and `--crlf` keeps them as they are;
+/// without either, the `--normalize-crlf` startup option decides.
+pub fn cat_command(args: Vec<&str>, stdin: Option<&str>, ctx: &ShellContext) -> CommandOutput {
+    let mut squeeze = false;
+    let mut normalize = ctx.options.normalize_crlf;
+    let mut files = Vec::new();
+    let mut options_ended = false;
+    for arg in args {
+        match arg {
+            _ if options_ended => files.push(arg),
+            "--" => options_ended = true,
+            "-s" => squeeze = true,
+            "--lf" => normalize = true,
+            "--crlf" => normalize = false,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return CommandOutput::error(format!("Error: cat: invalid option '{}'.", arg));
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    let inputs = read_inputs(&files, stdin, ctx);
+    let mut content: String = inputs.sources.iter().map(|(_, text)| text.as_str()).collect();
+    if normalize {
+        content = content.replace("\r\n", "\n");
+    }
+    if squeeze {
+        content = squeeze_blank_lines(&content);
+    }
+    CommandOutput { stdout: content, stderr: inputs.errors.join("\n"), exit_code: inputs.exit_code() }
+}
+
+/// Handles `file`: describes what each file holds, such as `notes.txt: ASCII text`.
+///
+/// Text with Windows line endings is noted as `with CRLF line terminators`, a hint that
+/// `cat --lf` will show it more cleanly.
+pub fn file_command(files: Vec<&str>, ctx: &ShellContext) -> CommandOutput {
+    if files.is_empty() {
+        return CommandOutput::error("Error: File name is required.");
+    }
+    let outputs = files
+        .into_iter()
+        .map(|file_name| match describe_file(ctx, file_name) {
+            Ok(description) => CommandOutput::success(format!("{}: {}", file_name, description)),
+            Err(e) => CommandOutput::error(format!("Error: file: '{}': {}", file_name, e)),
+        })
+        .collect();
+    combine_outputs(outputs)
+}
+
+/// Returns the description `file` prints for one file.
+fn describe_file(ctx: &ShellContext, file_name: &str) -> Result<String, String> {
+    let path = expand_path(ctx, file_name)?;
+    let info = ctx.fs.symlink_metadata(&path).map_err(|e| e.to_string())?;
+    if info.is_symlink {
+        let target = ctx.fs.read_link(&path).map_err(|e| e.to_string())?;
+        return Ok(format!("symbolic link to {}", target.display()));
+    }
+    if info.is_dir {
+        return Ok("directory".to_string());
+    }
+
+    let text = match ctx.fs.read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::InvalidData => return Ok("data".to_string()),
+        Err(e) => return Err(e.to_string()),
+    };
+    if text.is_empty() {
+        return Ok("empty".to_string());
+    }
+    let mut description = if text.is_ascii() { "ASCII text" } else { "UTF-8 Unicode text" }.to_string();
+    if text.contains("\r\n") {
+        description.push_str(", with CRLF line terminators");
+    }
+    Ok(description)
+}
+
+/// Handles `less`: shows files, or piped input, in the pager of the interactive screen.
+///
+/// A single file is left open for the pager to read as it scrolls, so huge files open at
+/// once; anything else is read whole.
+pub fn less_command(files: Vec<&str>, stdin: Option<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    if let ([file_name], true, None) = (files.as_slice(), ctx.interactive, &ctx.paged_file) {
+        if *file_name != "-" {
+            let opened = expand_path(ctx, file_name).and_then(|path| {
+                ctx.fs.open(&path).map_err(|e| format!("Error reading file '{}': {}", file_name, e))
+            });
+            return match opened {
+                Ok(file) => {
+                    ctx.paged_file = Some(file);
+                    ctx.page_output = true;
+                    CommandOutput::default()
+                }
+                Err(e) => CommandOutput::error(e),
+            };
+        }
+    }
+
+    let inputs = read_inputs(&files, stdin, ctx);
+    if !inputs.sources.is_empty() {
+        ctx.page_output = true;
+    }
+    let content = inputs.sources.iter().map(|(_, text)| text.as_str()).collect();
+    CommandOutput { stdout: content, stderr: inputs.errors.join("\n"), exit_code: inputs.exit_code() }
+}
+
+/// Drops every blank line that directly follows another blank line.
+fn squeeze_blank_lines(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut previous_blank = false;
+
+    for line in text.split_inclusive('\n') {
+        let blank = line.trim_end_matches(['\r', '\n']).is_empty();
+        if !(blank && previous_blank) {
+            result.push_str(line);
+        }
+        previous_blank = blank;
+    }
+
+    result
+}
+
+/// Creates a new file and optionally writes content to it.
+pub fn create_file(ctx: &ShellContext, file_name: &str, content: &str) -> CommandOutput {
+    if file_name.is_empty() {
+        return CommandOutput::error("Error: File name is required.");
+    }
+    let path = match expand_path(ctx, file_name) {
+        Ok(path) => path,
+        Err(e) => return CommandOutput::error(e),
+    };
+
+    let text = if content.is_empty() { String::new() } else { format!("{}\n", content) };
+
+    match ctx.fs.write(&path, &text) {
+        Ok(_) => CommandOutput::success(ctx.options.theme.success(format!("File '{}' created.", file_name))),
+        Err(e) => CommandOutput::error(format!("Error creating file '{}': {}", file_name, e)),
+    }
+}
+
+/// Creates files, each with the same optional text, for `touch`.
+///
+/// `words` are the arguments as typed, each expanded from any brace pattern in it, so a
+/// pattern in the first one creates every file it names. `-t <stamp>` or `-d <date>` before
+/// the file name also sets the files' modification and access times; a file that already
+/// exists then keeps its contents unless new text is given.
+pub fn touch_command(ctx: &ShellContext, words: &[Vec<String>]) -> CommandOutput {
+    let mut words = words;
+    let mut time = None;
+    // `--` ends the options, so the file name may start with `-`
+    let options_ended = words.first().is_some_and(|word| word == &["--"]);
+    if options_ended {
+        words = &words[1..];
+    }
+    let flag = words.first().and_then(|word| word.first()).filter(|flag| *flag == "-t" || *flag == "-d");
+    if let Some(flag) = flag.filter(|_| !options_ended) {
+        let value = words.get(1).map(|word| word.join(" ")).unwrap_or_default();
+        let parsed = if flag == "-t" { parse_stamp(&value) } else { parse_date(&value) };
+        match parsed {
+            Some(parsed) => time = Some(parsed),
+            None => return CommandOutput::error(format!("Error: touch: invalid date format '{}'.", value)),
+        }
+        words = &words[2.min(words.len())..];
+    }
+
+    let file_names = words.first().map(Vec::as_slice).unwrap_or_default();
+    let content = words.iter().skip(1).flatten().map(String::as_str).collect::<Vec<&str>>().join(" ");
+    if file_names.len() <= 1 {
+        touch_file(ctx, file_names.first().map_or("", String::as_str), &content, time)
+    } else {
+        combine_outputs(file_names.iter().map(|file_name| touch_file(ctx, file_name, &content, time)).collect())
+    }
+}
+
+/// Creates one file for `touch`, then sets its times if `time` is given.
+fn touch_file(ctx: &ShellContext, file_name: &str, content: &str, time: Option<SystemTime>) -> CommandOutput {
+    let Some(time) = time.filter(|_| !file_name.is_empty()) else {
+        return create_file(ctx, file_name, content);
+    };
+    let path = match expand_path(ctx, file_name) {
+        Ok(path) => path,
+        Err(e) => return CommandOutput::error(e),
+    };
+
+    let output = if content.is_empty() && ctx.fs.metadata(&path).is_ok() {
+        CommandOutput::success(ctx.options.theme.success(format!("Timestamp of '{}' updated.", file_name)))
+    } else {
+        create_file(ctx, file_name, content)
+    };
+    if output.exit_code != 0 {
+        return output;
+    }
+    match ctx.fs.set_modified(&path, time) {
+        Ok(()) => output,
+        Err(e) => CommandOutput::error(format!("Error setting the time of '{}': {}", file_name, e)),
+    }
+}
+
+/// Creates a new directory.
+///
+/// With `verbose`, the confirmation reads like that of `mkdir -v`.
+pub fn create_directory(ctx: &ShellContext, dir_name: &str, verbose: bool) -> CommandOutput {
+    if dir_name.is_empty() {
+        return CommandOutput::error("Error: Directory name is required.");
+    }
+    let path = match expand_path(ctx, dir_name) {
+        Ok(path) => path,
+        Err(e) => return CommandOutput::error(e),
+    };
+    match ctx.fs.create_dir(&path) {
+        Ok(_) if verbose => CommandOutput::success(format!("created directory '{}'", dir_name)),
+        Ok(_) => CommandOutput::success(ctx.options.theme.success(format!("Directory '{}' created.", dir_name))),
+        Err(e) => CommandOutput::error(format!("Error creating directory '{}': {}", dir_name, e)),
+    }
+}
+
+/// Flags for `rm`.
+#[derive(Clone, Copy, Default)]
+pub struct RemoveOptions {
+    /// Delete directories along with everything in them (`-r`).
+    pub recursive: bool,
+    /// Treat files that do not exist as already deleted (`-f`).
+    pub force: bool,
+    /// Report each deletion like `rm -v` (`-v`).
+    pub verbose: bool,
+}
+
+/// Deletes a file, or with `-r` a directory and everything in it.
+pub fn delete_file(ctx: &ShellContext, file_name: &str, options: RemoveOptions) -> CommandOutput {
+    let RemoveOptions { recursive, force, verbose } = options;
+    if file_name.is_empty() {
+        return CommandOutput::error("Error: File name is required.");
+    }
+    let path = match expand_path(ctx, file_name) {
+        Ok(path) => path,
+        Err(e) => return CommandOutput::error(e),
+    };
+    let is_dir = ctx.fs.metadata(&path).is_ok_and(|info| info.is_dir);
+    // Like GNU rm, never empty `.`, `..`, or `/`, and never the whole jail
+    let is_jail_root = ctx.options.jail_root.as_ref().is_some_and(|root| ctx.fs.canonicalize(&path).is_ok_and(|p| &p == root));
+    if recursive && is_dir && (Path::new(file_name).file_name().is_none() || is_jail_root) {
+        return CommandOutput::error(format!("Error: rm: refusing to remove '{}'.", file_name));
+    }
+    let result = if recursive && is_dir { ctx.fs.remove_dir_all(&path) } else { ctx.fs.remove_file(&path) };
+    match result {
+        // A forced removal of something already gone succeeds quietly
+        Err(e) if force && e.kind() == ErrorKind::NotFound => CommandOutput::default(),
+        Ok(_) if verbose && is_dir => CommandOutput::success(format!("removed directory '{}'", file_name)),
+        Ok(_) if verbose => CommandOutput::success(format!("removed '{}'", file_name)),
+        Ok(_) => CommandOutput::success(ctx.options.theme.success(format!("File '{}' deleted.", file_name))),
+        Err(e) => CommandOutput::error(format!("Error deleting file '{}': {}", file_name, e)),
+    }
+}
+
+/// Handles `mkdir`: creates each named directory. `-v` reports every directory created;
+/// otherwise several directories are confirmed with one summary line.
+pub fn mkdir_command(args: Vec<&str>, ctx: &ShellContext) -> CommandOutput {
+    let (flags, targets) = match split_flags("mkdir", args, &[('v', "verbose")]) {
+        Ok(parsed) => parsed,
+        Err(e) => return CommandOutput::error(e),
+    };
+    let verbose = flags.contains(&'v');
+    for_each_target(ctx, &targets, verbose, |count| count_of(count, "Created", "directory", "directories"), |target| {
+        create_directory(ctx, target, verbose)
+    })
+}
+
+/// Handles `rm`: deletes each named file, going on past failures. `-r` also deletes
+/// directories with their contents, `-f` ignores files that do not exist, and `-v` reports
+/// every deletion.
+pub fn rm_command(args: Vec<&str>, ctx: &ShellContext) -> CommandOutput {
+    let allowed = [('r', "recursive"), ('R', "recursive"), ('f', "force"), ('v', "verbose")];
+    let (flags, targets) = match split_flags("rm", args, &allowed) {
+        Ok(parsed) => parsed,
+        Err(e) => return CommandOutput::error(e),
+    };
+    let options = RemoveOptions {
+        recursive: flags.contains(&'r') || flags.contains(&'R'),
+        force: flags.contains(&'f'),
+        verbose: flags.contains(&'v'),
+    };
+    if options.force && targets.is_empty() {
+        return CommandOutput::default();
+    }
+    for_each_target(ctx, &targets, options.verbose, |count| count_of(count, "Deleted", "file", "files"), |target| {
+        delete_file(ctx, target, options)
+    })
+}
+
+/// Handles `rmdir`: removes each named empty directory, going on past failures. `-v`
+/// reports every removal.
+pub fn rmdir_command(args: Vec<&str>, ctx: &ShellContext) -> CommandOutput {
+    let (flags, targets) = match split_flags("rmdir", args, &[('v', "verbose")]) {
+        Ok(parsed) => parsed,
+        Err(e) => return CommandOutput::error(e),
+    };
+    let verbose = flags.contains(&'v');
+    for_each_target(ctx, &targets, verbose, |count| count_of(count, "Removed", "directory", "directories"), |target| {
+        remove_directory(ctx, target, verbose)
+    })
+}
+
+/// Flags for `cp`.
+#[derive(Clone, Copy, Default)]
+pub struct CopyOptions {
+    /// Keep each file's modification time, permissions, and where allowed its owner (`-p`).
+    pub preserve: bool,
+    /// Report each copy like `cp -v` (`-v`).
+    pub verbose: bool,
+    /// Show how far the copy of a large file has got (`--progress`).
+    pub progress: bool,
+}
+
+/// Handles `cp`: copies a file to a new name, or each of several files into a directory,
+/// going on past failures. `-p` keeps each file's modification time and permissions, `-v`
+/// reports every copy, and `--progress` shows a percentage while large files are copied.
+pub fn cp_command(mut args: Vec<&str>, ctx: &ShellContext) -> CommandOutput {
+    let progress = args.contains(&"--progress");
+    args.retain(|&arg| arg != "--progress");
+    let (flags, targets) = match split_flags("cp", args, &[('p', "preserve"), ('v', "verbose")]) {
+        Ok(parsed) => parsed,
+        Err(e) => return CommandOutput::error(e),
+    };
+    let Some((&destination, sources)) = targets.split_last().filter(|(_, sources)| !sources.is_empty()) else {
+        return CommandOutput::error("Error: Usage: cp [-p] [-v] [--progress] <source>... <destination>");
+    };
+    let into_dir = expand_path(ctx, destination).is_ok_and(|path| ctx.fs.metadata(&path).is_ok_and(|info| info.is_dir));
+    if sources.len() > 1 && !into_dir {
+        return CommandOutput::error(format!("Error: cp: target '{}' is not a directory.", destination));
+    }
+    let options = CopyOptions { preserve: flags.contains(&'p'), verbose: flags.contains(&'v'), progress };
+    for_each_target(ctx, sources, options.verbose, |count| count_of(count, "Copied", "file", "files"), |source| {
+        let target = match (into_dir, Path::new(source).file_name()) {
+            (true, Some(name)) => Path::new(destination).join(name).to_string_lossy().to_string(),
+            _ => destination.to_string(),
+        };
+        copy_file(ctx, source, &target, options)
+    })
+}
+
+/// Copies the file `source` to `target`. With `preserve`, the copy is given the source's
+/// modification time and permissions, and its owner where the process may set it.
+///
+/// With `progress`, a file of at least [`PROGRESS_THRESHOLD`] bytes has its percentage
+/// shown as it is copied, where the front end can show it.
+fn copy_file(ctx: &ShellContext, source: &str, target: &str, options: CopyOptions) -> CommandOutput {
+    let (from, to) = match (expand_path(ctx, source), expand_path(ctx, target)) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(e), _) | (_, Err(e)) => return CommandOutput::error(e),
+    };
+    let info = match ctx.fs.metadata(&from) {
+        Ok(info) if info.is_dir => return CommandOutput::error(format!("Error: cp: omitting directory '{}'.", source)),
+        Ok(info) => info,
+        Err(e) => return CommandOutput::error(format!("Error: cp: '{}': {}", source, e)),
+    };
+    if ctx.fs.canonicalize(&from).is_ok_and(|from| ctx.fs.canonicalize(&to).is_ok_and(|to| from == to)) {
+        return CommandOutput::error(format!("Error: cp: '{}' and '{}' are the same file.", source, target));
+    }
+
+    let report = ctx.progress.as_ref().filter(|_| options.progress && info.len >= PROGRESS_THRESHOLD);
+    let mut shown = None;
+    let copied = ctx.fs.copy_file(&from, &to, &mut |done| {
+        let percent = (done * 100 / info.len.max(1)).min(100);
+        if let Some(report) = report.filter(|_| shown != Some(percent)) {
+            report(&format!("Copying '{}': {}%", source, percent));
+            shown = Some(percent);
+        }
+    });
+    if let Some(report) = report {
+        report("");
+    }
+    if let Err(e) = copied {
+        return CommandOutput::error(format!("Error: cp: could not copy '{}' to '{}': {}", source, target, e));
+    }
+    if options.preserve {
+        let preserved = ctx
+            .fs
+            .copy_permissions(&from, &to)
+            .and_then(|_| info.modified.map_or(Ok(()), |modified| ctx.fs.set_modified(&to, modified)));
+        if let Err(e) = preserved {
+            return CommandOutput::error(format!("Error: cp: copied '{}' but could not preserve its attributes: {}", source, e));
+        }
+    }
+    if options.verbose {
+        CommandOutput::success(format!("'{}' -> '{}'", source, target))
+    } else {
+        CommandOutput::success(ctx.options.theme.success(format!("File '{}' copied to '{}'.", source, target)))
+    }
+}
+
+/// Separates flags from the targets of a command. `allowed` pairs each flag letter with its
+/// long name, so `-rv`, `-r -v`, and `--recursive --verbose` all give `['r', 'v']`. Every
+/// argument after `--` is a target, even one that starts with `-`.
+fn split_flags<'a>(cmd: &str, args: Vec<&'a str>, allowed: &[(char, &str)]) -> Result<(Vec<char>, Vec<&'a str>), String> {
+    let invalid = |arg: &str| format!("Error: {}: invalid option '{}'.", cmd, arg);
+    let mut flags = Vec::new();
+    let mut targets = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            targets.extend(args);
+            break;
+        } else if let Some(long) = arg.strip_prefix("--") {
+            let (flag, _) = allowed.iter().find(|(_, name)| *name == long).ok_or_else(|| invalid(arg))?;
+            flags.push(*flag);
+        } else if arg.starts_with('-') && arg.len() > 1 {
+            for flag in arg[1..].chars() {
+                if !allowed.iter().any(|(letter, _)| *letter == flag) {
+                    return Err(invalid(&format!("-{}", flag)));
+                }
+                flags.push(flag);
+            }
+        } else {
+            targets.push(arg);
+        }
+    }
+    Ok((flags, targets))
+}
+
+/// Formats a summary such as `Deleted 3 files.`, using the singular noun for one.
+fn count_of(count: usize, verb: &str, singular: &str, plural: &str) -> String {
+    format!("{} {} {}.", verb, count, if count == 1 { singular } else { plural })
+}
+
+/// Runs `action` on each target, going on past failures. A single target, or any number with
+/// `verbose`, is reported one by one; otherwise `summary` describes how many succeeded.
+fn for_each_target(
+    ctx: &ShellContext,
+    targets: &[&str],
+    verbose: bool,
+    summary: impl Fn(usize) -> String,
+    action: impl Fn(&str) -> CommandOutput,
+) -> CommandOutput {
+    if targets.len() <= 1 {
+        return action(targets.first().copied().unwrap_or(""));
+    }
+
+    let mut reports = Vec::new();
+    let mut errors = Vec::new();
+    let mut succeeded = 0;
+    for target in targets {
+        let output = action(target);
+        if output.exit_code != 0 {
+            errors.push(output.stderr);
+        } else if !output.stdout.is_empty() {
+            // Targets skipped without a word, like missing files under `rm -f`, are not counted
+            succeeded += 1;
+            reports.push(output.stdout);
+        }
+    }
+
+    let stdout = match (verbose, succeeded) {
+        (true, _) => reports.join("\n"),
+        (false, 0) => String::new(),
+        (false, count) => ctx.options.theme.success(summary(count)),
+    };
+    let exit_code = if errors.is_empty() { 0 } else { 1 };
+    CommandOutput { stdout, stderr: errors.join("\n"), exit_code }
+}
+
+/// Removes an empty directory.
+///
+/// With `verbose`, the confirmation reads like that of `rmdir -v`.
+pub fn remove_directory(ctx: &ShellContext, dir_name: &str, verbose: bool) -> CommandOutput {
+    if dir_name.is_empty() {
+        return CommandOutput::error("Error: Directory name is required.");
+    }
+    let path = match expand_path(ctx, dir_name) {
+        Ok(path) => path,
+        Err(e) => return CommandOutput::error(e),
+    };
+    match ctx.fs.remove_dir(&path) {
+        Ok(_) if verbose => CommandOutput::success(format!("removed directory '{}'", dir_name)),
+        Ok(_) => CommandOutput::success(ctx.options.theme.success(format!("Directory '{}' removed.", dir_name))),
+        Err(e) => CommandOutput::error(format!("Error removing directory '{}': {}", dir_name, e)),
+    }
+}
+
+/// How `rename` changes each file name.
+enum Renaming {
+    /// Replace the first occurrence of one piece of text with another.
+    Text(String, String),
+    /// Replace the first match of a regex, or every match, as in `s/from/to/g`.
+    Substitute(Regex, String, bool),
+}
+
+impl Renaming {
+    /// Parses an expression such as `s/.txt$/.md/` or `s|a|b|g`. Returns `None` if it is not
+    /// written as a substitution at all.
+    fn substitution(expression: &str) -> Option<Result<Renaming, String>> {
+        let mut chars = expression.chars();
+        let delimiter = chars.next().filter(|&c| c == 's').and(chars.next()).filter(|c| !c.is_alphanumeric())?;
+        let parts: Vec<&str> = chars.as_str().split(delimiter).collect();
+        let [from, to, flags] = parts[..] else {
+            let form = format!("s{0}from{0}to{0}", delimiter);
+            return Some(Err(format!("Error: rename: '{}' is not a complete {} expression.", expression, form)));
+        };
+        let global = match flags {
+            "" => false,
+            "g" => true,
+            _ => return Some(Err(format!("Error: rename: unknown flags '{}' in '{}'.", flags, expression))),
+        };
+        Some(match Regex::new(from) {
+            Ok(regex) => Ok(Renaming::Substitute(regex, to.to_string(), global)),
+            Err(e) => Err(format!("Error: rename: invalid pattern '{}': {}", from, e)),
+        })
+    }
+
+    /// Returns the new name for `name`, or `None` if the renaming does not apply to it.
+    fn apply(&self, name: &str) -> Option<String> {
+        match self {
+            Renaming::Text(from, to) => name.contains(from.as_str()).then(|| name.replacen(from.as_str(), to, 1)),
+            Renaming::Substitute(regex, to, global) if regex.is_match(name) => {
+                Some(if *global { regex.replace_all(name, to.as_str()) } else { regex.replace(name, to.as_str()) }.into_owned())
+            }
+            Renaming::Substitute(..) => None,
+        }
+    }
+}
+
+/// Handles `rename`: renames each file, in its own directory, by replacing text in its
+/// name, as in `rename .txt .md *.txt`, or by a regex substitution, as in
+/// `rename 's/\.txt$/.md/' *.txt`. Files whose names do not match are left alone, and a
+/// file is never renamed onto a name that exists or that another file was just given.
+pub fn rename_command(args: Vec<&str>, ctx: &ShellContext) -> CommandOutput {
+    let usage = "Error: Usage: rename <from> <to> <file>... | rename s/<regex>/<to>/[g] <file>...";
+    let (renaming, files) = match args.split_first() {
+        Some((expression, files)) if !files.is_empty() => match Renaming::substitution(expression) {
+            Some(Ok(renaming)) => (renaming, files),
+            Some(Err(e)) => return CommandOutput::error(e),
+            None if files.len() > 1 => (Renaming::Text(expression.to_string(), files[0].to_string()), &files[1..]),
+            None => return CommandOutput::error(usage),
+        },
+        _ => return CommandOutput::error(usage),
+    };
+
+    let mut reports = Vec::new();
+    let mut errors = Vec::new();
+    let mut taken: Vec<PathBuf> = Vec::new();
+    for &file in files {
+        let path = match expand_path(ctx, file) {
+            Ok(path) => path,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        let Some(name) = path.file_name().map(|name| name.to_string_lossy().to_string()) else {
+            errors.push(format!("Error: rename: cannot rename '{}'.", file));
+            continue;
+        };
+        let new_name = match renaming.apply(&name) {
+            Some(new_name) if new_name == name => continue,
+            Some(new_name) if new_name.is_empty() || new_name.contains('/') || new_name == "." || new_name == ".." => {
+                errors.push(format!("Error: rename: '{}' would be renamed to the invalid name '{}'.", file, new_name));
+                continue;
+            }
+            Some(new_name) => new_name,
+            None => continue,
+        };
+        let shown = match file.trim_end_matches('/').rfind('/') {
+            Some(slash) => format!("{}{}", &file[..=slash], new_name),
+            None => new_name.clone(),
+        };
+
+        let new_path = path.with_file_name(&new_name);
+        if let Err(e) = ctx.fs.symlink_metadata(&path) {
+            errors.push(format!("Error: rename: '{}': {}", file, e));
+        } else if taken.contains(&new_path) || ctx.fs.symlink_metadata(&new_path).is_ok() {
+            errors.push(format!("Error: rename: not renaming '{}', since '{}' already exists.", file, shown));
+        } else {
+            match ctx.fs.rename(&path, &new_path) {
+                Ok(()) => {
+                    reports.push(format!("renamed '{}' -> '{}'", file, shown));
+                    taken.push(new_path);
+                }
+                Err(e) => errors.push(format!("Error: rename: '{}': {}", file, e)),
+            }
+        }
+    }
+
+    let exit_code = if errors.is_empty() { 0 } else { 1 };
+    CommandOutput { stdout: reports.join("\n"), stderr: errors.join("\n"), exit_code }
+}
+
+/// Changes the current directory.
+pub fn change_directory(ctx: &mut ShellContext, dir_name: &str) -> CommandOutput {
+    if dir_name.is_empty() {
+        return CommandOutput::error("Error: Directory name is required.");
+    }
+    match enter_directory(ctx, dir_name) {
+        Ok(()) => CommandOutput::success(ctx.options.theme.success(format!("Changed directory to '{}'.", dir_name))),
+        Err(e) => CommandOutput::error(e),
+    }
+}
+
+/// Makes `dir_name` the current directory, keeping a jailed session inside its root.
+fn enter_directory(ctx: &mut ShellContext, dir_name: &str) -> Result<(), String> {
+    let path = match expand_path(ctx, dir_name) {
+        Ok(path) => path,
+        Err(e) => match ctx.options.jail_root.as_ref().and_then(|root| climb_within(root, dir_name, ctx)) {
+            Some(path) => expand_path(ctx, &path.to_string_lossy())?,
+            None => return Err(e),
+        },
+    };
+    ctx.fs
+        .set_current_dir(&path)
+        .map_err(|e| format!("Error changing directory to '{}': {}", dir_name, e))?;
+    ctx.refresh_current_dir();
+    Ok(())
+}
+
+/// Follows `dir_name` from the current directory, or from the root or `~` it starts with,
+/// treating the jail root like `/`: a `..` there stays there. Returns the directory reached
+/// if it is inside the jail, so that only climbing above the root with `..` lands on it.
+fn climb_within(root: &Path, dir_name: &str, ctx: &ShellContext) -> Option<PathBuf> {
+    let (mut path, rest) = match dir_name.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => (root.to_path_buf(), rest.trim_start_matches('/')),
+        _ => (ctx.fs.current_dir().ok()?, dir_name),
+    };
+    for component in Path::new(rest).components() {
+        match component {
+            Component::ParentDir if path != root => {
+                path.pop();
+            }
+            Component::Normal(name) => path.push(name),
+            Component::RootDir => path = PathBuf::from("/"),
+            _ => {}
+        }
+    }
+    path.starts_with(root).then_some(path)
+}
+
+/// Handles `pushd`: changes to `dir_name` and saves the previous directory on the stack.
+/// Without a directory, swaps the current directory with the top of the stack.
+pub fn pushd_command(ctx: &mut ShellContext, dir_name: Option<&str>) -> CommandOutput {
+    let current = match ctx.fs.current_dir() {
+        Ok(dir) => dir,
+        Err(e) => return CommandOutput::error(format!("Error: {}", e)),
+    };
+    let result = match dir_name {
+        Some(dir_name) => enter_directory(ctx, dir_name),
+        None => match ctx.dir_stack.pop() {
+            Some(top) => {
+                let result = enter_directory(ctx, &top.to_string_lossy());
+                if result.is_err() {
+                    ctx.dir_stack.push(top);
+                }
+                result
+            }
+            None => Err("pushd: no other directory".to_string()),
+        },
+    };
+    match result {
+        Ok(()) => {
+            ctx.dir_stack.push(current);
+            dirs_command(ctx)
+        }
+        Err(e) => CommandOutput::error(e),
+    }
+}
+
+/// Handles `popd`: returns to the directory on top of the stack and removes it.
+pub fn popd_command(ctx: &mut ShellContext) -> CommandOutput {
+    let Some(top) = ctx.dir_stack.pop() else {
+        return CommandOutput::error("popd: directory stack empty");
+    };
+    match enter_directory(ctx, &top.to_string_lossy()) {
+        Ok(()) => dirs_command(ctx),
+        Err(e) => {
+            ctx.dir_stack.push(top);
+            CommandOutput::error(e)
+        }
+    }
+}
+
+/// Handles `dirs`: shows the current directory followed by the saved ones, most recent
+/// first, with the home directory written as `~`.
+pub fn dirs_command(ctx: &ShellContext) -> CommandOutput {
+    let current = match ctx.fs.current_dir() {
+        Ok(dir) => dir,
+        Err(e) => return CommandOutput::error(format!("Error: {}", e)),
+    };
+    let home = ctx.fs.home_dir();
+    let shown: Vec<String> = std::iter::once(&current)
+        .chain(ctx.dir_stack.iter().rev())
+        .map(|dir| match home.as_deref().and_then(|home| dir.strip_prefix(home).ok()) {
+            Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+            Some(rest) => format!("~/{}", rest.display()),
+            None => dir.display().to_string(),
+        })
+        .collect();
+    CommandOutput::success(shown.join(" "))
+}
+
+/// Handles the `echo` command to display user-provided text.
+///
+/// Leading flags are `-n` to omit the trailing newline, `-e` to interpret backslash
+/// escapes, and `-E` to leave them as typed (the default).
+pub fn echo_command(args: Vec<&str>) -> String {
+    let mut newline = true;
+    let mut escapes = false;
+    let mut index = 0;
+
+    // Flags are only recognized before the first word, and only if every letter is valid
+    while let Some(flag) = args.get(index).and_then(|arg| arg.strip_prefix('-')) {
+        if flag.is_empty() || !flag.chars().all(|c| matches!(c, 'n' | 'e' | 'E')) {
+            break;
+        }
+        for c in flag.chars() {
+            match c {
+                'n' => newline = false,
+                'e' => escapes = true,
+                _ => escapes = false,
+            }
+        }
+        index += 1;
+    }
+
+    let mut text = args[index..].join(" "); // Join all arguments with a space
+    if escapes {
+        text = interpret_escapes(&text);
+    }
+    if newline {
+        text.push('\n');
+    }
+    text
+}
+
+/// Replaces the backslash escapes understood by `echo -e`: `\n`, `\t`, `\r`, `\\`, and
+/// `\0nnn` for a character given as up to three octal digits.
+fn interpret_escapes(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('\\') => result.push('\\'),
+            Some('0') => {
+                let mut value = 0;
+                for _ in 0..3 {
+                    match chars.peek().and_then(|d| d.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                result.push(char::from_u32(value).unwrap_or('\0'));
+            }
+            // Unknown escapes are kept as typed
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+/// Handles the `xargs` command: runs a command with arguments read from piped input.
+///
+/// `-n N` passes at most N arguments per invocation, and `-I <placeholder>` runs the
+/// command once per input line with the placeholder replaced by that line. The values may
+/// also be attached, as in `-n1` and `-I{}`. The arguments are passed as they are, without
+/// being expanded again.
+///
+/// Exits with status 123 if any invocation of the command fails, like GNU xargs.
+pub fn xargs_command(args: Vec<&str>, stdin: &str, ctx: &mut ShellContext) -> CommandOutput {
+    let mut batch_size: Option<usize> = None;
+    let mut placeholder: Option<&str> = None;
+    let mut index = 0;
+
+    while let Some(arg) = args.get(index) {
+        let (flag, attached) = match arg.split_at_checked(2) {
+            Some((flag @ ("-n" | "-I"), attached)) => (flag, attached),
+            _ => break,
+        };
+        index += 1;
+        let value = if attached.is_empty() {
+            index += 1;
+            args.get(index - 1).copied()
+        } else {
+            Some(attached)
+        };
+        match (flag, value) {
+            ("-n", value) => match value.unwrap_or("").parse::<usize>() {
+                Ok(n) if n > 0 => batch_size = Some(n),
+                _ => return CommandOutput::error(format!("Error: Invalid number for -n: '{}'.", value.unwrap_or(""))),
+            },
+            (_, Some(value)) => placeholder = Some(value),
+            (_, None) => return CommandOutput::error("Error: -I requires a placeholder."),
+        }
+    }
+
+    let template = match args.get(index..).filter(|words| !words.is_empty()) {
+        Some(words) => words.to_vec(),
+        None => vec!["echo"],
+    };
+    let command_line = |words: Vec<String>| words.iter().map(|word| quote_word(word)).collect::<Vec<_>>().join(" ");
+
+    let command_lines: Vec<String> = match placeholder {
+        Some(placeholder) => stdin
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| command_line(template.iter().map(|word| word.replace(placeholder, line.trim())).collect()))
+            .collect(),
+        None => {
+            let tokens: Vec<&str> = stdin.split_whitespace().collect();
+            let size = batch_size.unwrap_or(tokens.len()).max(1);
+            if tokens.is_empty() {
+                vec![command_line(template.iter().map(|word| word.to_string()).collect())]
+            } else {
+                tokens
+                    .chunks(size)
+                    .map(|batch| command_line(template.iter().chain(batch).map(|word| word.to_string()).collect()))
+                    .collect()
+            }
+        }
+    };
+
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    let mut exit_code = 0;
+
+    for line in &command_lines {
+        let output = handle_command(line, None, ctx);
+        if !output.stdout.is_empty() {
+            results.push(output.stdout);
+        }
+        if !output.stderr.is_empty() {
+            errors.push(output.stderr);
+        }
+        if output.exit_code != 0 {
+            exit_code = 123;
+        }
+    }
+
+    CommandOutput { stdout: join_output(&results), stderr: errors.join("\n"), exit_code }
+}
+
+/// Pauses for the given number of seconds. The interactive screen cannot redraw or take
+/// keys meanwhile, so there a pause is limited to `MAX_FOREGROUND_SLEEP`; a background job
+/// (`sleep 60 &`) may pause for longer.
+pub fn sleep_command(seconds: &str, ctx: &ShellContext) -> CommandOutput {
+    let duration = match seconds.parse::<f64>().map(Duration::try_from_secs_f64) {
+        Ok(Ok(duration)) => duration,
+        _ => return CommandOutput::error(format!("Error: Invalid time interval '{}'.", seconds)),
+    };
+    if ctx.interactive && duration > MAX_FOREGROUND_SLEEP {
+        return CommandOutput::error(format!(
+            "Error: sleep: at most {} seconds on the interactive screen; run longer pauses in the background with '&'.",
+            MAX_FOREGROUND_SLEEP.as_secs()
+        ));
+    }
+    thread::sleep(duration);
+    CommandOutput::default()
+}
+
+/// Handles the `time` prefix: runs `pipeline`, then reports how long all of it took after
+/// its error output, as `real`, `user`, and `sys` lines like other shells. The CPU times
+/// are those of the programs it started, so they are shown only when a stage is a program
+/// run with `--allow-external`, on Unix; for built-in commands only the real time is shown.
+///
+/// The CPU times are those of every program the emulator waited for meanwhile, so a program
+/// in a background job that finishes while the pipeline runs is counted too.
+pub fn time_command(pipeline: &str, heredoc: Option<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    if pipeline.trim().is_empty() {
+        return CommandOutput::error("Error: Usage: time <command>");
+    }
+    let names: Vec<String> = split_unquoted(pipeline, '|')
+        .into_iter()
+        .filter_map(|stage| expand_alias(stage, &ctx.aliases).split_whitespace().next().map(str::to_string))
+        .collect();
+    let started = Instant::now();
+    let cpu_before = child_cpu_times();
+    let mut output = run_pipeline(pipeline, heredoc, ctx);
+    let real = started.elapsed();
+
+    let mut lines = vec![format!("real\t{}", format_elapsed(real))];
+    let external = ctx.options.allow_external && names.iter().any(|name| !BUILTIN_COMMANDS.contains(&name.as_str()));
+    if let (true, Some((user_before, sys_before)), Some((user, sys))) = (external, cpu_before, child_cpu_times()) {
+        lines.push(format!("user\t{}", format_elapsed(user.saturating_sub(user_before))));
+        lines.push(format!("sys\t{}", format_elapsed(sys.saturating_sub(sys_before))));
+    }
+    if !output.stderr.is_empty() {
+        output.stderr.push('\n');
+    }
+    output.stderr.push_str(&lines.join("\n"));
+    output
+}
+
+/// Formats a duration as minutes and seconds to the millisecond, as in `0m1.250s`.
+fn format_elapsed(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    format!("{}m{}.{:03}s", millis / 60_000, millis / 1000 % 60, millis % 1000)
+}
+
+/// Returns the user and system CPU time used so far by the programs the emulator has
+/// started and waited for.
+#[cfg(unix)]
+fn child_cpu_times() -> Option<(Duration, Duration)> {
+    // SAFETY: `rusage` is plain data, so all zeros is a valid value for getrusage to fill in
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage) } != 0 {
+        return None;
+    }
+    let time = |tv: libc::timeval| Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64);
+    Some((time(usage.ru_utime), time(usage.ru_stime)))
+}
+
+/// CPU times of other processes are only available on Unix.
+#[cfg(not(unix))]
+fn child_cpu_times() -> Option<(Duration, Duration)> {
+    None
+}
+
+/// Handles `uptime`: shows the current time in the local time zone and how long the
+/// emulator session has been running, as in ` 14:03:12 up 1:23:45`, with whole days
+/// counted separately.
+pub fn uptime_command(ctx: &ShellContext) -> CommandOutput {
+    CommandOutput::success(format!(" {} up {}", Local::now().format("%H:%M:%S"), format_session_time(ctx)))
+}
+
+/// Returns how long the session has been running as `H:MM:SS`, preceded by the number of
+/// whole days, if any.
+fn format_session_time(ctx: &ShellContext) -> String {
+    let up = ctx.started.elapsed().as_secs();
+    let days = match up / 86_400 {
+        0 => String::new(),
+        1 => "1 day, ".to_string(),
+        days => format!("{} days, ", days),
+    };
+    format!("{}{}:{:02}:{:02}", days, up % 86_400 / 3600, up % 3600 / 60, up % 60)
+}
+
+/// Returns the session's statistics: how many commands have run, for how long, and how
+/// many times each command ran, most used first.
+pub fn session_stats(ctx: &ShellContext) -> String {
+    let mut counts: Vec<(&String, &u32)> = ctx.command_counts.iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    let width = counts.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+
+    let total: u32 = ctx.command_counts.values().sum();
+    let mut lines = vec![format!("Commands run: {}", total), format!("Session time: {}", format_session_time(ctx))];
+    lines.extend(counts.iter().map(|(name, count)| format!("  {:<width$}  {}", name, count)));
+    lines.join("\n")
+}
+
+/// Handles `stats`: shows the session's statistics.
+pub fn stats_command(ctx: &ShellContext) -> CommandOutput {
+    CommandOutput::success(session_stats(ctx))
+}
+
+/// Handles the `copy` command: puts piped input, a file's contents, or the previous
+/// command's output on the system clipboard.
+pub fn copy_command(file_name: &str, stdin: Option<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    let text = match stdin {
+        Some(input) if file_name.is_empty() => input.to_string(),
+        _ if file_name.is_empty() => ctx.last_output.clone(),
+        _ => match expand_path(ctx, file_name).and_then(|path| {
+            ctx.fs.read_to_string(&path).map_err(|e| format!("Error reading file '{}': {}", file_name, e))
+        }) {
+            Ok(content) => content,
+            Err(e) => return CommandOutput::error(e),
+        },
+    };
+    let text = strip_ansi(&text);
+
+    if ctx.clipboard.is_none() {
+        match Clipboard::new() {
+            Ok(clipboard) => ctx.clipboard = Some(clipboard),
+            Err(e) => return CommandOutput::error(format!("Error accessing clipboard: {}", e)),
+        }
+    }
+
+    let clipboard = ctx.clipboard.as_mut().expect("clipboard was just initialized");
+    match clipboard.set_text(text.as_str()) {
+        Ok(_) => CommandOutput::success(ctx.options.theme.success(format!("Copied {} bytes to the clipboard.", text.len()))),
+        Err(e) => CommandOutput::error(format!("Error copying to clipboard: {}", e)),
+    }
+}
+
+/// Runs a program found on the real `PATH`, for commands that are not built in.
+///
+/// The program runs in the emulator's current directory with its environment, and its
+/// output is captured rather than shown live, so interactive programs are not supported.
+pub fn external_command(cmd: &str, args: Vec<&str>, stdin: Option<&str>, ctx: &ShellContext) -> CommandOutput {
+    let mut command = Command::new(cmd);
+    command
+        .args(&args)
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Ok(dir) = ctx.fs.current_dir() {
+        command.current_dir(dir);
+    }
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return CommandOutput { exit_code: 127, ..CommandOutput::error(format!("Unknown command: {}", cmd)) };
+        }
+        Err(e) => return CommandOutput::error(format!("Error running '{}': {}", cmd, e)),
+    };
+
+    // Feed piped input from another thread so a full output pipe cannot stall the write
+    if let (Some(input), Some(mut child_stdin)) = (stdin, child.stdin.take()) {
+        let input = input.to_string();
+        thread::spawn(move || {
+            let _ = child_stdin.write_all(input.as_bytes());
+        });
+    }
+
+    match child.wait_with_output() {
+        Ok(output) => CommandOutput {
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim_end().to_string(),
+            exit_code: output.status.code().unwrap_or(1),
+        },
+        Err(e) => CommandOutput::error(format!("Error running '{}': {}", cmd, e)),
+    }
+}
+
+/// Handles `source` and `.`: runs a script's lines in the current session, so changes it
+/// makes to the session last after it finishes.
+///
+/// Unlike `--script`, every line runs even if an earlier one fails. Error output is
+/// prefixed with the script name and line number.
+pub fn source_command(file_name: &str, ctx: &mut ShellContext) -> CommandOutput {
+    if file_name.is_empty() {
+        return CommandOutput::error("Error: File name is required.");
+    }
+    if ctx.source_depth >= MAX_SOURCE_DEPTH {
+        return CommandOutput::error(format!("Error: '{}' is sourced too many times recursively.", file_name));
+    }
+    let script = match expand_path(ctx, file_name).and_then(|path| {
+        ctx.fs.read_to_string(&path).map_err(|e| format!("Error reading file '{}': {}", file_name, e))
+    }) {
+        Ok(script) => script,
+        Err(e) => return CommandOutput::error(e),
+    };
+    source_script(&script, file_name, ctx)
+}
+
+/// Runs each line of `script` in the current session, as `source` does. Errors are
+/// labeled with `name` and their line number.
+pub fn source_script(script: &str, name: &str, ctx: &mut ShellContext) -> CommandOutput {
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+
+    ctx.source_depth += 1;
+    let exit_code = run_script(script, true, ctx, |number, _, output| {
+        if !output.stdout.is_empty() {
+            results.push(output.stdout.clone());
+        }
+        errors.extend(output.stderr.lines().map(|line| format!("{}: line {}: {}", name, number, line)));
+    });
+    ctx.source_depth -= 1;
+
+    CommandOutput { stdout: join_output(&results), stderr: errors.join("\n"), exit_code }
+}
+
+/// Handles `alias`: with no arguments lists every alias, `name=value` defines one, and a
+/// bare name prints that alias. Several definitions or names may be given at once.
+///
+/// With `--save`, each alias defined or named, or every alias if none is, is also saved in
+/// the startup file, so later sessions have it too.
+pub fn alias_command(mut words: Vec<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    let save = words.contains(&"--save");
+    words.retain(|&word| word != "--save");
+    let rc_path = match &ctx.rc_path {
+        _ if !save => None,
+        _ if ctx.options.read_only || ctx.options.jail_root.is_some() || ctx.options.virtual_root.is_some() => {
+            return CommandOutput::error("Error: alias: --save is disabled with --read-only, --jail, and --virtual.")
+        }
+        Some(path) => Some(path.clone()),
+        None => return CommandOutput::error("Error: alias: there is no home directory to save aliases in."),
+    };
+
+    if let (true, Some(path)) = (words.is_empty(), &rc_path) {
+        let errors: Vec<String> = ctx
+            .aliases
+            .iter()
+            .filter_map(|(name, value)| save_alias(path, name, value).err())
+            .map(|e| format!("alias: could not save to '{}': {}", path.display(), e))
+            .collect();
+        let exit_code = if errors.is_empty() { 0 } else { 1 };
+        return CommandOutput { stdout: String::new(), stderr: errors.join("\n"), exit_code };
+    }
+    if words.is_empty() {
+        let lines: Vec<String> =
+            ctx.aliases.iter().map(|(name, value)| format!("alias {}='{}'", name, value)).collect();
+        return CommandOutput::success(lines.join("\n"));
+    }
+
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    for word in words {
+        let name = match word.split_once('=') {
+            Some(("", _)) => {
+                errors.push(format!("alias: '{}': invalid alias name", word));
+                continue;
+            }
+            Some((name, value)) => {
+                ctx.aliases.insert(name.to_string(), value.to_string());
+                name
+            }
+            None => match ctx.aliases.get(word) {
+                // Naming an alias to save it is not a request to print it
+                Some(_) if save => word,
+                Some(value) => {
+                    results.push(format!("alias {}='{}'", word, value));
+                    continue;
+                }
+                None => {
+                    errors.push(format!("alias: {}: not found", word));
+                    continue;
+                }
+            },
+        };
+        if let Some(path) = &rc_path {
+            if let Err(e) = save_alias(path, name, &ctx.aliases[name]) {
+                errors.push(format!("alias: {}: could not save to '{}': {}", name, path.display(), e));
+            }
+        }
+    }
+
+    let exit_code = if errors.is_empty() { 0 } else { 1 };
+    CommandOutput { stdout: results.join("\n"), stderr: errors.join("\n"), exit_code }
+}
+
+/// Handles `export`: `NAME=value` sets an environment variable, which commands and
+/// programs run later see, and a bare name leaves it as it is. With no arguments lists every
+/// variable as an `export` command.
+pub fn export_command(words: Vec<&str>) -> CommandOutput {
+    if words.is_empty() {
+        let lines: Vec<String> =
+            sorted_environment().into_iter().map(|(name, value)| format!("export {}='{}'", name, value)).collect();
+        return CommandOutput::success(lines.join("\n"));
+    }
+
+    let mut errors = Vec::new();
+    for word in words {
+        let (name, value) = match word.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (word, None),
+        };
+        if !is_variable_name(name) {
+            errors.push(format!("export: '{}': not a valid identifier", word));
+        } else if let Some(value) = value {
+            env::set_var(name, value);
+        }
+    }
+    let exit_code = if errors.is_empty() { 0 } else { 1 };
+    CommandOutput { stdout: String::new(), stderr: errors.join("\n"), exit_code }
+}
+
+/// Handles `history`: lists the command lines typed on the interactive screen, numbered for
+/// `!N`, or only the last `count` of them. `-c` clears the history, in the history file too.
+pub fn history_command(args: Vec<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    let count = match args.as_slice() {
+        ["-c"] => {
+            ctx.history.clear();
+            if let Some(path) = ctx.history_path.as_ref().filter(|path| path.exists()) {
+                if let Err(e) = std::fs::write(path, "") {
+                    return CommandOutput::error(format!("Error: history: cannot clear '{}': {}", path.display(), e));
+                }
+            }
+            return CommandOutput::success("");
+        }
+        [] => ctx.history.len(),
+        [count] => match count.parse::<usize>() {
+            Ok(count) => count,
+            Err(_) => return CommandOutput::error(format!("Error: history: '{}' is not a number.", count)),
+        },
+        _ => return CommandOutput::error("Error: Usage: history [count] | history -c"),
+    };
+    let start = ctx.history.len().saturating_sub(count);
+    let lines: Vec<String> =
+        ctx.history.iter().enumerate().skip(start).map(|(index, line)| format!("{:>5}  {}", index + 1, line)).collect();
+    CommandOutput::success(lines.join("\n"))
+}
+
+/// Handles `unset`: removes each named environment variable. As in POSIX shells, a name
+/// that is not set is not an error.
+pub fn unset_command(names: Vec<&str>) -> CommandOutput {
+    let mut errors = Vec::new();
+    for name in names {
+        if is_variable_name(name) {
+            env::remove_var(name);
+        } else {
+            errors.push(format!("unset: '{}': not a valid identifier", name));
+        }
+    }
+    let exit_code = if errors.is_empty() { 0 } else { 1 };
+    CommandOutput { stdout: String::new(), stderr: errors.join("\n"), exit_code }
+}
+
+/// Handles `set`: `-o <name>` turns a shell option on and `+o <name>` turns it off, and
+/// each option's letter does the same, as in `set -e` or `set +Cx`. With no arguments, or
+/// `-o` alone, lists every option as the `set` command that restores it.
+pub fn set_command(args: Vec<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    let list = |ctx: &ShellContext| {
+        let lines: Vec<String> = ShellOptions::NAMES
+            .iter()
+            .map(|(_, name)| {
+                let sign = if ctx.shell_options.get(name) == Some(true) { '-' } else { '+' };
+                format!("set {}o {}", sign, name)
+            })
+            .collect();
+        CommandOutput::success(lines.join("\n"))
+    };
+    if args.is_empty() {
+        return list(ctx);
+    }
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (on, letters) = match (arg.strip_prefix('-'), arg.strip_prefix('+')) {
+            (Some(letters), _) | (_, Some(letters)) if !letters.is_empty() => (arg.starts_with('-'), letters),
+            _ => return CommandOutput::error(format!("Error: set: invalid option '{}'.", arg)),
+        };
+        for letter in letters.chars() {
+            let name = match (letter, ShellOptions::NAMES.iter().find(|(short, _)| *short == letter)) {
+                ('o', _) => match args.next() {
+                    Some(name) => name,
+                    None => return list(ctx),
+                },
+                (_, Some((_, name))) => name,
+                (_, None) => return CommandOutput::error(format!("Error: set: invalid option '-{}'.", letter)),
+            };
+            if !ctx.shell_options.set(name, on) {
+                return CommandOutput::error(format!("Error: set: unknown option '{}'.", name));
+            }
+        }
+    }
+    CommandOutput::success("")
+}
+
+/// Handles `env`: lists every environment variable as `NAME=value`, sorted by name.
+pub fn env_command(args: Vec<&str>) -> CommandOutput {
+    if !args.is_empty() {
+        return CommandOutput::error("Error: Usage: env");
+    }
+    let lines: Vec<String> = sorted_environment().into_iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+    CommandOutput::success(lines.join("\n"))
+}
+
+/// Returns the environment variables with readable names and values, sorted by name.
+fn sorted_environment() -> Vec<(String, String)> {
+    let mut variables: Vec<(String, String)> = env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .collect();
+    variables.sort();
+    variables
+}
+
+/// Returns true if `name` can name a variable: letters, digits, and underscores, not
+/// starting with a digit.
+fn is_variable_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Handles `unalias`: removes the named aliases, or all of them with `-a`.
+pub fn unalias_command(names: Vec<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    if names.is_empty() {
+        return CommandOutput::error("Error: Alias name is required.");
+    }
+    if names.contains(&"-a") {
+        ctx.aliases.clear();
+        return CommandOutput::default();
+    }
+
+    let errors: Vec<String> = names
+        .into_iter()
+        .filter(|name| ctx.aliases.remove(*name).is_none())
+        .map(|name| format!("unalias: {}: not found", name))
+        .collect();
+    let exit_code = if errors.is_empty() { 0 } else { 1 };
+    CommandOutput { stdout: String::new(), stderr: errors.join("\n"), exit_code }
+}
+
+/// Handles `which`: reports whether each name runs an alias, a built-in command, or (with
+/// `--allow-external`) a program on the `PATH`, in the order the dispatcher checks them.
+pub fn which_command(names: Vec<&str>, ctx: &ShellContext) -> CommandOutput {
+    if names.is_empty() {
+        return CommandOutput::error("Error: Command name is required.");
+    }
+
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    for name in names {
+        if let Some(value) = ctx.aliases.get(name) {
+            results.push(format!("{}: aliased to '{}'", name, value));
+        } else if BUILTIN_COMMANDS.contains(&name) {
+            results.push(format!("{}: shell builtin", name));
+        } else if let Some(path) = ctx.options.allow_external.then(|| find_in_path(name)).flatten() {
+            results.push(path.display().to_string());
+        } else {
+            errors.push(format!("which: {} not found", name));
+        }
+    }
+
+    let exit_code = if errors.is_empty() { 0 } else { 1 };
+    CommandOutput { stdout: results.join("\n"), stderr: errors.join("\n"), exit_code }
+}
+
+/// Handles `type`: describes what each name runs, following aliases through to the
+/// command they name. With `-a`, lists every alias, built-in command, and program that
+/// matches each name instead.
+pub fn type_command(args: Vec<&str>, ctx: &ShellContext) -> CommandOutput {
+    let all = args.contains(&"-a");
+    let names: Vec<&str> = args.into_iter().filter(|arg| *arg != "-a").collect();
+    if names.is_empty() {
+        return CommandOutput::error("Error: Command name is required.");
+    }
+
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    for name in names {
+        let lines = if all { list_command_matches(name, ctx) } else { describe_command(name, ctx, &mut Vec::new()) };
+        if lines.is_empty() {
+            errors.push(format!("type: {}: not found", name));
+        }
+        results.extend(lines);
+    }
+
+    let exit_code = if errors.is_empty() { 0 } else { 1 };
+    CommandOutput { stdout: results.join("\n"), stderr: errors.join("\n"), exit_code }
+}
+
+/// Describes the command that running `name` would start, one line per alias on the way.
+/// Aliases already in `expanded` are not followed again, matching `expand_alias`.
+fn describe_command(name: &str, ctx: &ShellContext, expanded: &mut Vec<String>) -> Vec<String> {
+    if let Some(value) = ctx.aliases.get(name).filter(|_| !expanded.iter().any(|used| used == name)) {
+        expanded.push(name.to_string());
+        let mut lines = vec![format!("{} is aliased to '{}'", name, value)];
+        if let Some(target) = value.split_whitespace().next() {
+            lines.extend(describe_command(target, ctx, expanded));
+        }
+        return lines;
+    }
+    if BUILTIN_COMMANDS.contains(&name) {
+        return vec![format!("{} is a shell builtin", name)];
+    }
+    match ctx.options.allow_external.then(|| find_in_path(name)).flatten() {
+        Some(path) => vec![format!("{} is {}", name, path.display())],
+        None => Vec::new(),
+    }
+}
+
+/// Lists every alias, built-in command, and runnable program named `name`.
+fn list_command_matches(name: &str, ctx: &ShellContext) -> Vec<String> {
+    let mut lines = Vec::new();
+    if let Some(value) = ctx.aliases.get(name) {
+        lines.push(format!("{} is aliased to '{}'", name, value));
+    }
+    if BUILTIN_COMMANDS.contains(&name) {
+        lines.push(format!("{} is a shell builtin", name));
+    }
+    if ctx.options.allow_external {
+        lines.extend(path_matches(name).iter().map(|path| format!("{} is {}", name, path.display())));
+    }
+    lines
+}
+
+/// Returns the program that running `name` would start, searching the `PATH` unless the
+/// name already contains a path separator.
+pub fn find_in_path(name: &str) -> Option<PathBuf> {
+    path_matches(name).into_iter().next()
+}
+
+/// Returns every program named `name` on the `PATH`, in search order.
+fn path_matches(name: &str) -> Vec<PathBuf> {
+    if name.contains(std::path::MAIN_SEPARATOR) || name.contains('/') {
+        let path = PathBuf::from(name);
+        return if is_executable(&path) { vec![path] } else { Vec::new() };
+    }
+    let Some(paths) = env::var_os("PATH") else {
+        return Vec::new();
+    };
+    env::split_paths(&paths).map(|dir| dir.join(name)).filter(|path| is_executable(path)).collect()
+}
+
+/// Returns whether `path` is a file the current user could run.
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata().is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
@@ -0,0 +1,139 @@
+//! Tab completion on the interactive screen: command names, the flags each command
+//! declares in [`COMMAND_FLAGS`], and file names.
+
+use crate::shell::{expand_path, ShellContext, BUILTIN_COMMANDS, COMMAND_FLAGS};
+
+/// The input line after Tab is pressed.
+pub struct Completion {
+    /// The line with its last word completed as far as every match agrees. A single match
+    /// is followed by a space, except a directory, which ends with `/` instead.
+    pub line: String,
+    /// Every match, when there is more than one, to be listed for the user.
+    pub choices: Vec<String>,
+}
+
+/// Completes the last word of `line`: a command name at the start of a command, one of the
+/// command's flags for a word starting with `-`, and otherwise a file or directory name.
+pub fn complete(line: &str, ctx: &ShellContext) -> Completion {
+    let start = line.rfind(char::is_whitespace).map_or(0, |index| index + 1);
+    let (before, word) = line.split_at(start);
+    let command_start = before.rfind(['|', ';', '&', '(']).map_or(0, |index| index + 1);
+
+    // Matches are whole words; `shown` is how much of each to leave out when listing them
+    let (matches, shown) = match before[command_start..].split_whitespace().next() {
+        None => (command_names(word, ctx), 0),
+        Some(cmd) if word.starts_with('-') => (flags(cmd, word, ctx), 0),
+        Some(_) => file_names(word, ctx),
+    };
+
+    let completed = match matches.as_slice() {
+        [] => word.to_string(),
+        [only] if only.ends_with('/') => only.clone(),
+        [only] => format!("{} ", only),
+        [first, rest @ ..] => rest.iter().fold(first.clone(), |common, name| common_prefix(&common, name).to_string()),
+    };
+    let choices =
+        if matches.len() > 1 { matches.iter().map(|name| name[shown..].to_string()).collect() } else { Vec::new() };
+    Completion { line: format!("{}{}", before, completed), choices }
+}
+
+/// Returns the built-in commands and aliases whose names start with `prefix`, sorted.
+fn command_names(prefix: &str, ctx: &ShellContext) -> Vec<String> {
+    let mut names: Vec<String> = BUILTIN_COMMANDS
+        .iter()
+        .map(|name| name.to_string())
+        .chain(ctx.aliases.keys().cloned())
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Returns the flags of `cmd` that start with `prefix`, in the order the command declares
+/// them. An alias offers the flags of the command it runs.
+fn flags(cmd: &str, prefix: &str, ctx: &ShellContext) -> Vec<String> {
+    let cmd = ctx.aliases.get(cmd).and_then(|value| value.split_whitespace().next()).unwrap_or(cmd);
+    COMMAND_FLAGS
+        .iter()
+        .find(|(name, _)| *name == cmd)
+        .map(|(_, flags)| flags.iter().filter(|flag| flag.starts_with(prefix)).map(|flag| flag.to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// Returns the paths that complete `word`, sorted, with directories ending in `/`, along
+/// with the length of the directory part of `word`. As with patterns, names starting with
+/// `.` are only offered once the word's last part starts with `.`.
+fn file_names(word: &str, ctx: &ShellContext) -> (Vec<String>, usize) {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(index) => word.split_at(index + 1),
+        None => ("", word),
+    };
+    let Ok(path) = expand_path(ctx, if dir.is_empty() { "." } else { dir }) else {
+        return (Vec::new(), dir.len());
+    };
+    let Ok(names) = ctx.fs.read_dir(&path) else {
+        return (Vec::new(), dir.len());
+    };
+
+    let mut matches: Vec<String> = names
+        .into_iter()
+        .filter(|name| name.starts_with(prefix) && (!name.starts_with('.') || prefix.starts_with('.')))
+        .map(|name| {
+            let is_dir = ctx.fs.metadata(&path.join(&name)).is_ok_and(|info| info.is_dir);
+            format!("{}{}{}", dir, name, if is_dir { "/" } else { "" })
+        })
+        .collect();
+    matches.sort();
+    (matches, dir.len())
+}
+
+/// Returns the longest start that `a` and `b` share, ending on a character boundary.
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let len = a
+        .char_indices()
+        .zip(b.chars())
+        .take_while(|((_, x), y)| x == y)
+        .last()
+        .map_or(0, |((index, x), _)| index + x.len_utf8());
+    &a[..len]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{FileSystem, MemoryFileSystem};
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[test]
+    fn flags_complete_after_a_dash() {
+        let ctx = ShellContext::default();
+        let completion = complete("ls -", &ctx);
+        assert_eq!(completion.line, "ls -");
+        assert!(completion.choices.contains(&"-l".to_string()) && completion.choices.contains(&"-t".to_string()));
+        assert_eq!(complete("cat notes | ls --gr", &ctx).line, "cat notes | ls --group-directories-first ");
+        assert_eq!(complete("rm --re", &ctx).line, "rm --recursive ");
+        assert_eq!(complete("pwd -x", &ctx).choices, Vec::<String>::new());
+    }
+
+    #[test]
+    fn commands_and_files_complete_to_what_the_matches_share() {
+        let fs = MemoryFileSystem::new();
+        fs.create_dir(Path::new("/notes")).unwrap();
+        for file in ["/notes/todo.txt", "/notes/today.md", "/.hidden"] {
+            fs.write(Path::new(file), "").unwrap();
+        }
+        let ctx = ShellContext { fs: Arc::new(fs), ..Default::default() };
+
+        assert_eq!(complete("unal", &ctx).line, "unalias ");
+        assert_eq!(complete("un", &ctx).choices, ["unalias", "unexpand", "uniq", "unset"]);
+        assert_eq!(complete("cat n", &ctx).line, "cat notes/");
+        let completion = complete("cat notes/to", &ctx);
+        assert_eq!(completion.line, "cat notes/tod");
+        assert_eq!(completion.choices, ["today.md", "todo.txt"]);
+        assert_eq!(complete("cat .h", &ctx).line, "cat .hidden ");
+        assert_eq!(complete("cat ", &ctx).choices, Vec::<String>::new());
+        assert_eq!(complete("cat ", &ctx).line, "cat notes/");
+    }
+}
@@ -0,0 +1,118 @@
+//! Configuration files in `~/.config/rust_emulator/`: `config.toml`, read at every start,
+//! and the named profiles chosen with `--profile <name>`, whose settings take precedence.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Settings read from a configuration file. Settings the file leaves out keep their
+/// defaults.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The prompt shown before the input line, where `{cwd}` stands for the current
+    /// directory.
+    pub prompt: Option<String>,
+    /// Name of the color theme, as `--theme` takes it.
+    pub theme: Option<String>,
+    /// How many lines of output the scrollback keeps.
+    pub max_output_lines: Option<usize>,
+    /// Whether text is shown in color, unless `--no-color` or `NO_COLOR` turn it off.
+    pub color: Option<bool>,
+    /// The welcome line at the top of the interactive screen.
+    pub banner: Option<String>,
+    /// Whether commands that modify the filesystem are disabled, as with `--read-only`.
+    pub read_only: Option<bool>,
+    /// Distance between tab stops, as `--tab-size` takes it.
+    pub tab_size: Option<usize>,
+    /// How many lines of output a command may show, as `--output-limit` takes it.
+    pub output_limit: Option<usize>,
+    /// Which lines the history leaves out, as the `HISTCONTROL` variable takes it.
+    pub history_control: Option<String>,
+    /// Aliases defined before the startup file runs, by name.
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// Returns these settings with those `over` sets put in their place. Aliases from both
+    /// are kept, with `over`'s winning where both define the same name.
+    pub fn merge(mut self, over: Config) -> Config {
+        self.aliases.extend(over.aliases);
+        Config {
+            prompt: over.prompt.or(self.prompt),
+            theme: over.theme.or(self.theme),
+            max_output_lines: over.max_output_lines.or(self.max_output_lines),
+            color: over.color.or(self.color),
+            banner: over.banner.or(self.banner),
+            read_only: over.read_only.or(self.read_only),
+            tab_size: over.tab_size.or(self.tab_size),
+            output_limit: over.output_limit.or(self.output_limit),
+            history_control: over.history_control.or(self.history_control),
+            aliases: self.aliases,
+        }
+    }
+}
+
+/// Returns the path of the configuration file read at every start,
+/// `~/.config/rust_emulator/config.toml`.
+pub fn config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("rust_emulator").join("config.toml"))
+}
+
+/// Reads `config.toml`, or returns the built-in defaults when there is no such file.
+pub fn load_config() -> Result<Config, String> {
+    match config_path() {
+        Some(path) if path.exists() => read_config(&path),
+        _ => Ok(Config::default()),
+    }
+}
+
+/// Returns the path of the profile `name`, `~/.config/rust_emulator/<name>.toml`.
+pub fn profile_path(name: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("rust_emulator").join(format!("{}.toml", name)))
+}
+
+/// Reads the configuration file at `path`.
+pub fn read_config(path: &Path) -> Result<Config, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Cannot read '{}': {}", path.display(), e))?;
+    parse_config(&contents).map_err(|e| format!("Invalid configuration '{}': {}", path.display(), e))
+}
+
+/// Parses the TOML text of a configuration file.
+fn parse_config(contents: &str) -> Result<Config, String> {
+    toml::from_str(contents).map_err(|e| e.message().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_set_only_what_they_name() {
+        let config = parse_config(
+            "prompt = \"[{cwd}]$ \"\nmax_output_lines = 200\n\n[aliases]\nll = \"ls -l\"\ngs = \"grep -n\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.prompt.as_deref(), Some("[{cwd}]$ "));
+        assert_eq!(config.theme, None);
+        assert_eq!(config.max_output_lines, Some(200));
+        assert_eq!(config.aliases.get("gs").map(String::as_str), Some("grep -n"));
+
+        assert_eq!(parse_config("").unwrap(), Config::default());
+        assert!(parse_config("colour = \"red\"").is_err());
+        assert!(parse_config("max_output_lines = \"many\"").is_err());
+    }
+
+    #[test]
+    fn profiles_take_precedence_over_the_config_file() {
+        let defaults = parse_config("read_only = true\nprompt = \"$ \"\n[aliases]\nll = \"ls -l\"\nla = \"ls -a\"\n");
+        let profile = parse_config("read_only = false\ncolor = false\n[aliases]\nll = \"ls -lt\"\n");
+        let config = defaults.unwrap().merge(profile.unwrap());
+        assert_eq!(config.read_only, Some(false));
+        assert_eq!(config.color, Some(false));
+        assert_eq!(config.prompt.as_deref(), Some("$ "));
+        assert_eq!(config.aliases.get("ll").map(String::as_str), Some("ls -lt"));
+        assert_eq!(config.aliases.get("la").map(String::as_str), Some("ls -a"));
+    }
+}
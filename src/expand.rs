@@ -0,0 +1,379 @@
+//! Expansions applied to the words of a command before it runs.
+
+use crate::redirect::read_variable;
+use crate::shell::{execute, strip_ansi, ShellContext};
+use std::collections::BTreeMap;
+
+/// Replaces an alias at the start of a command with its value, repeating while the new
+/// first word is another alias. Each alias is used at most once, so one that refers to a
+/// command of the same name, like `ls='ls -a'`, stops there.
+pub fn expand_alias(command: &str, aliases: &BTreeMap<String, String>) -> String {
+    let mut command = command.trim_start().to_string();
+    let mut used: Vec<String> = Vec::new();
+
+    loop {
+        let name = command.split_whitespace().next().unwrap_or("");
+        match aliases.get(name) {
+            Some(value) if !used.iter().any(|used| used == name) => {
+                used.push(name.to_string());
+                command = format!("{}{}", value, &command[name.len()..]);
+            }
+            _ => return command,
+        }
+    }
+}
+
+/// Applies history expansion to a command line, given the lines run before it, oldest
+/// first. Returns the new line, or `None` if the line has nothing to expand.
+///
+/// A line `^old^new^` is the previous line with the first `old` replaced by `new`.
+/// Elsewhere, outside single quotes, `!!` is the previous line, `!N` line N as numbered by
+/// `history`, `!-N` the line N back, and `!text` the latest line starting with `text`.
+/// A `!` before a space, `=`, `(`, or the end of the line is left as typed.
+pub fn expand_history(line: &str, history: &[String]) -> Result<Option<String>, String> {
+    if let Some(substitution) = line.strip_prefix('^') {
+        let mut parts = substitution.splitn(3, '^');
+        let (old, new) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+        let failed = || format!("Error: ^{}^{}: substitution failed", old, new);
+        let previous = history.last().ok_or_else(failed)?;
+        if old.is_empty() || !previous.contains(old) {
+            return Err(failed());
+        }
+        return Ok(Some(format!("{}{}", previous.replacen(old, new, 1), parts.next().unwrap_or(""))));
+    }
+
+    let mut expanded = String::with_capacity(line.len());
+    let mut changed = false;
+    let mut in_single_quotes = false;
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        let after = &rest[c.len_utf8()..];
+        let event_len = after.find(|c: char| c.is_whitespace() || ";|&<>()'\"".contains(c)).unwrap_or(after.len());
+        let event = &after[..event_len];
+        if c != '!' || in_single_quotes || event.is_empty() || event.starts_with('=') {
+            in_single_quotes ^= c == '\'';
+            expanded.push(c);
+            rest = after;
+            continue;
+        }
+
+        // `!!` may be followed straight away by more text, as in `!!:` or `!!.bak`
+        let (found, used) = if let Some(tail) = event.strip_prefix('!') {
+            (history.last(), event.len() - tail.len())
+        } else if let Ok(back) = event.strip_prefix('-').unwrap_or("x").parse::<usize>() {
+            (history.len().checked_sub(back).and_then(|index| history.get(index)), event.len())
+        } else if let Ok(number) = event.parse::<usize>() {
+            (number.checked_sub(1).and_then(|index| history.get(index)), event.len())
+        } else {
+            (history.iter().rev().find(|previous| previous.starts_with(event)), event.len())
+        };
+        let Some(found) = found else {
+            return Err(format!("Error: !{}: event not found", &event[..used]));
+        };
+        expanded.push_str(found);
+        changed = true;
+        rest = &after[used..];
+    }
+    Ok(changed.then_some(expanded))
+}
+
+/// Replaces each `$NAME` and `${NAME}` in a command with the value of that environment
+/// variable, and `$?` with `last_exit_code`, except inside single quotes or after a
+/// backslash. Unset variables expand to nothing. Backslashes are left in place for quote
+/// removal to take out.
+pub fn expand_variables(command: &str, last_exit_code: i32) -> String {
+    let mut result = String::with_capacity(command.len());
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                result.push(c);
+                if let Some(escaped) = chars.next() {
+                    result.push(escaped);
+                }
+                continue;
+            }
+            (_, '$') if chars.next_if_eq(&'?').is_some() => {
+                result.push_str(&last_exit_code.to_string());
+                continue;
+            }
+            (_, '$') => {
+                result.push_str(&read_variable(&mut chars));
+                continue;
+            }
+            _ => {}
+        }
+        result.push(c);
+    }
+
+    result
+}
+
+/// Replaces each `$(command)` and `` `command` `` in a command with the output of running
+/// it, minus trailing newlines. Substitutions inside single quotes are left as typed, and
+/// nested substitutions run innermost first.
+///
+/// Returns the expanded command along with any error output from the substituted commands.
+pub fn substitute_commands(command: &str, ctx: &mut ShellContext) -> Result<(String, Vec<String>), String> {
+    let mut result = String::with_capacity(command.len());
+    let mut errors = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut index = 0;
+
+    while let Some(c) = command[index..].chars().next() {
+        let rest = &command[index..];
+        let inner = match (quote, c) {
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                None
+            }
+            (Some(open), _) if c == open => {
+                quote = None;
+                None
+            }
+            (Some('\''), _) => None,
+            (_, '`') => {
+                let end = rest[1..].find('`').ok_or("Error: Unmatched '`'.")?;
+                Some((&rest[1..=end], end + 2))
+            }
+            (_, '$') if rest.starts_with("$(") => {
+                let end = closing_paren(&rest[2..]).ok_or("Error: Unmatched '$('.")?;
+                Some((&rest[2..end + 2], end + 3))
+            }
+            _ => None,
+        };
+
+        match inner {
+            Some((inner, length)) => {
+                // `exit` inside a substitution only ends the substituted command, and its
+                // output is text for the command rather than for the terminal, so it is not
+                // traced by `set -x` either
+                let (exit_requested, terminal_width) = (ctx.exit_requested, ctx.terminal_width.take());
+                let xtrace = std::mem::take(&mut ctx.shell_options.xtrace);
+                let output = execute(inner, ctx);
+                (ctx.exit_requested, ctx.terminal_width) = (exit_requested, terminal_width);
+                ctx.shell_options.xtrace = xtrace;
+
+                if !output.stderr.is_empty() {
+                    errors.push(output.stderr);
+                }
+                result.push_str(strip_ansi(&output.stdout).trim_end_matches('\n'));
+                index += length;
+            }
+            None => {
+                result.push(c);
+                index += c.len_utf8();
+            }
+        }
+    }
+
+    Ok((result, errors))
+}
+
+/// Returns the position of the `)` that closes a substitution whose text starts `text`.
+fn closing_paren(text: &str) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    let mut depth = 0;
+
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '(') => depth += 1,
+            (None, ')') if depth == 0 => return Some(index),
+            (None, ')') => depth -= 1,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// The most words one word's braces may expand to, so that a slip such as
+/// `{1..10000000000}` is an error rather than an attempt to fill memory.
+const MAX_BRACE_WORDS: usize = 100_000;
+
+/// Expands the first unquoted `{...}` group in a word, then any groups after it.
+///
+/// A group is either a comma list like `{a,b,c}` or a range like `{1..5}`, `{a..e}`, or
+/// `{0..10..2}` with a step. Words without a valid group, including nested or unmatched
+/// braces, are returned unchanged. Fails if the word would give more than
+/// `MAX_BRACE_WORDS` words.
+pub fn expand_braces(word: &str) -> Result<Vec<String>, String> {
+    let Some((open, close)) = find_brace_group(word) else {
+        return Ok(vec![word.to_string()]);
+    };
+
+    let (prefix, body, suffix) = (&word[..open], &word[open + 1..close], &word[close + 1..]);
+    let too_many = || format!("Error: {}: brace expansion gives more than {} words.", word, MAX_BRACE_WORDS);
+    let rests = expand_braces(suffix).map_err(|_| too_many())?;
+    let items = match expand_range(body) {
+        Some(Err(())) => return Err(too_many()),
+        Some(Ok(items)) => items,
+        None => match expand_list(body) {
+            Some(items) => items,
+            // Leave this group literal but still expand any group after it
+            None => return Ok(rests.iter().map(|rest| format!("{}{{{}}}{}", prefix, body, rest)).collect()),
+        },
+    };
+    if items.len().saturating_mul(rests.len()) > MAX_BRACE_WORDS {
+        return Err(too_many());
+    }
+
+    Ok(items.iter().flat_map(|item| rests.iter().map(move |rest| format!("{}{}{}", prefix, item, rest))).collect())
+}
+
+/// Returns the byte positions of the first unquoted `{` and the `}` that closes it.
+fn find_brace_group(word: &str) -> Option<(usize, usize)> {
+    let mut quote: Option<char> = None;
+    let mut open = None;
+
+    for (index, c) in word.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '{') if open.is_none() => open = Some(index),
+            (None, '}') => {
+                if let Some(start) = open {
+                    return Some((start, index));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Splits a `a,b,c` group body, which needs at least one comma and no nested braces.
+fn expand_list(body: &str) -> Option<Vec<String>> {
+    if !body.contains(',') || body.contains('{') {
+        return None;
+    }
+    Some(body.split(',').map(str::to_string).collect())
+}
+
+/// Expands a `start..end` or `start..end..step` group body of numbers or single letters.
+/// Returns `None` if the body is not a range, and an error if it has more than
+/// `MAX_BRACE_WORDS` values.
+fn expand_range(body: &str) -> Option<Result<Vec<String>, ()>> {
+    let parts: Vec<&str> = body.split("..").collect();
+    let step = match parts.len() {
+        2 => 1,
+        3 => parts[2].parse::<i64>().ok()?.unsigned_abs().max(1),
+        _ => return None,
+    };
+
+    let (start, end, letters) = match (parts[0].parse::<i64>(), parts[1].parse::<i64>()) {
+        (Ok(start), Ok(end)) => (start, end, false),
+        _ => (single_letter(parts[0])? as i64, single_letter(parts[1])? as i64, true),
+    };
+    if start.abs_diff(end) / step >= MAX_BRACE_WORDS as u64 {
+        return Some(Err(()));
+    }
+    let values = range_values(start, end, step);
+    if letters {
+        return Some(Ok(values.map(|n| (n as u8 as char).to_string()).collect()));
+    }
+    Some(Ok(values.map(|n| n.to_string()).collect()))
+}
+
+/// Counts from `start` to `end` inclusive, in either direction, by `step`, stopping
+/// before a value that would overflow.
+fn range_values(start: i64, end: i64, step: u64) -> impl Iterator<Item = i64> {
+    let count = start.abs_diff(end) / step + 1;
+    let next = move |n: &i64| if end < start { n.checked_sub_unsigned(step) } else { n.checked_add_unsigned(step) };
+    std::iter::successors(Some(start), next).take(count as usize)
+}
+
+/// Returns the character of a one-letter ASCII string.
+fn single_letter(text: &str) -> Option<u8> {
+    match text.as_bytes() {
+        [c] if c.is_ascii_alphabetic() => Some(*c),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases_expand_in_chains_without_looping() {
+        let aliases: BTreeMap<String, String> = [("ll", "ls -l"), ("l", "ll"), ("ls", "ls -a")]
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        assert_eq!(expand_alias("l docs", &aliases), "ls -a -l docs");
+        assert_eq!(expand_alias("echo ll", &aliases), "echo ll");
+    }
+
+    #[test]
+    fn variables_expand_outside_single_quotes() {
+        std::env::set_var("EXPAND_TEST_NAME", "world");
+        assert_eq!(
+            expand_variables(
+                "echo $EXPAND_TEST_NAME \"${EXPAND_TEST_NAME}!\" '$EXPAND_TEST_NAME' $EXPAND_TEST_UNSET. $",
+                0
+            ),
+            "echo world \"world!\" '$EXPAND_TEST_NAME' . $"
+        );
+        assert_eq!(expand_variables("echo $? \"$?\" '$?'", 127), "echo 127 \"127\" '$?'");
+        assert_eq!(
+            expand_variables(r#"echo \$EXPAND_TEST_NAME "\$? \"$?" \\$?"#, 1),
+            r#"echo \$EXPAND_TEST_NAME "\$? \"1" \\1"#
+        );
+    }
+
+    #[test]
+    fn history_events_and_substitutions_expand() {
+        let history: Vec<String> = ["ls -l", "cat notes.txt", "echo hi"].iter().map(|line| line.to_string()).collect();
+        let expand = |line| expand_history(line, &history);
+
+        assert_eq!(expand("!!"), Ok(Some("echo hi".to_string())));
+        assert_eq!(expand("sudo !! | wc"), Ok(Some("sudo echo hi | wc".to_string())));
+        assert_eq!(expand("!1; !-2"), Ok(Some("ls -l; cat notes.txt".to_string())));
+        assert_eq!(expand("!ca"), Ok(Some("cat notes.txt".to_string())));
+        assert_eq!(expand("^hi^there^ again"), Ok(Some("echo there again".to_string())));
+        assert_eq!(expand("echo hi! 'a!b' x != y"), Ok(None));
+        assert_eq!(expand("!9"), Err("Error: !9: event not found".to_string()));
+        assert_eq!(expand("^nope^x"), Err("Error: ^nope^x: substitution failed".to_string()));
+    }
+
+    #[test]
+    fn lists_and_ranges_expand() {
+        assert_eq!(expand_braces("file{1,2,3}.txt").unwrap(), vec!["file1.txt", "file2.txt", "file3.txt"]);
+        assert_eq!(expand_braces("{1..5}").unwrap(), vec!["1", "2", "3", "4", "5"]);
+        assert_eq!(expand_braces("{0..10..4}").unwrap(), vec!["0", "4", "8"]);
+        assert_eq!(expand_braces("{3..1}").unwrap(), vec!["3", "2", "1"]);
+        assert_eq!(expand_braces("{c..a}").unwrap(), vec!["c", "b", "a"]);
+        assert_eq!(expand_braces("{a,b}{1..2}").unwrap(), vec!["a1", "a2", "b1", "b2"]);
+    }
+
+    #[test]
+    fn huge_ranges_are_errors_instead_of_overflowing() {
+        let extremes = expand_braces("{-9223372036854775808..9223372036854775807..9223372036854775807}").unwrap();
+        assert_eq!(extremes, vec!["-9223372036854775808", "-1", "9223372036854775806"]);
+        assert_eq!(expand_braces("{9223372036854775807..9223372036854775806}").unwrap().len(), 2);
+
+        let error = "Error: {1..10000000000}: brace expansion gives more than 100000 words.";
+        assert_eq!(expand_braces("{1..10000000000}"), Err(error.to_string()));
+        assert!(expand_braces("{1..1000}{1..1000}").is_err());
+        assert_eq!(expand_braces("{1..100000}").map(|words| words.len()), Ok(100_000));
+    }
+
+    #[test]
+    fn invalid_groups_stay_literal() {
+        assert_eq!(expand_braces("{abc").unwrap(), vec!["{abc"]);
+        assert_eq!(expand_braces("{single}").unwrap(), vec!["{single}"]);
+        assert_eq!(expand_braces("'{a,b}'").unwrap(), vec!["'{a,b}'"]);
+        assert_eq!(expand_braces("{1..b}").unwrap(), vec!["{1..b}"]);
+        assert_eq!(expand_braces("{x}{1,2}").unwrap(), vec!["{x}1", "{x}2"]);
+    }
+}
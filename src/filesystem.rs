@@ -0,0 +1,711 @@
+//! Filesystem backends that commands run against: the real OS filesystem, or an
+//! in-memory tree for safe demos and deterministic tests.
+
+use filetime::FileTime;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Cursor, ErrorKind, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// The filesystem operations used by the built-in commands.
+///
+/// Relative paths are resolved against the backend's own current directory.
+pub trait FileSystem: Send + Sync {
+    /// Returns the names of the entries in a directory, in no particular order.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<String>>;
+    /// Returns the type, size, and modification time of a file or directory, following
+    /// symlinks.
+    fn metadata(&self, path: &Path) -> io::Result<FileInfo>;
+    /// Returns the same details as [`FileSystem::metadata`], but for a symlink itself rather
+    /// than what it points to.
+    fn symlink_metadata(&self, path: &Path) -> io::Result<FileInfo>;
+    /// Returns the path a symlink points to, as it was written.
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+    /// Reads a whole file as text.
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+    /// Opens a file to be read a little at a time, for files that may be too large to read
+    /// whole.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn BufRead + Send>>;
+    /// Creates or truncates a file and writes `contents` to it.
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()>;
+    /// Adds `contents` to the end of a file, creating it if needed.
+    fn append(&self, path: &Path, contents: &str) -> io::Result<()>;
+    /// Creates a new, empty directory.
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+    /// Removes a file.
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    /// Removes an empty directory.
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+    /// Removes a directory along with everything inside it.
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// Moves a file or directory to `to`, replacing a file already there.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    /// Copies the contents and permissions of the file `from` to `to`, replacing a file
+    /// already there, and returns the number of bytes copied. `progress` is called with the
+    /// bytes copied so far each time another chunk is written.
+    fn copy_file(&self, from: &Path, to: &Path, progress: &mut dyn FnMut(u64)) -> io::Result<u64>;
+    /// Gives `to` the permissions of `from`, and also its owner and group where the backend
+    /// has them and the process is allowed to change them.
+    fn copy_permissions(&self, from: &Path, to: &Path) -> io::Result<()>;
+    /// Sets the modification time of a file, and its access time where the backend has one.
+    fn set_modified(&self, path: &Path, time: SystemTime) -> io::Result<()>;
+    /// Returns the absolute form of `path` with `.`, `..`, and symlinks resolved.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+    /// Returns the current working directory.
+    fn current_dir(&self) -> io::Result<PathBuf>;
+    /// Changes the current working directory.
+    fn set_current_dir(&self, path: &Path) -> io::Result<()>;
+    /// Returns the directory that `~` expands to.
+    fn home_dir(&self) -> Option<PathBuf>;
+}
+
+/// The details `ls` shows about an entry.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FileInfo {
+    pub is_dir: bool,
+    /// Whether the entry is a symlink, which only `symlink_metadata` reports.
+    pub is_symlink: bool,
+    /// Size in bytes.
+    pub len: u64,
+    /// Last modification time, if the backend records one.
+    pub modified: Option<SystemTime>,
+}
+
+/// Number of bytes the real filesystem copies at a time, between progress reports.
+const COPY_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The real filesystem of the machine the emulator runs on.
+pub struct OsFileSystem;
+
+/// Working directory as reached through `cd`, before symlinks are resolved. Shared by the
+/// whole process, like the real working directory.
+static LOGICAL_CWD: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Joins `path` onto `base` and removes `.` and `..` components without touching the disk.
+pub fn lexical_join(base: &Path, path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in base.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            other => result.push(other.as_os_str()),
+        }
+    }
+    result
+}
+
+impl FileSystem for OsFileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<String>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.file_name().to_string_lossy().to_string()))
+            .collect()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileInfo> {
+        Ok(file_info(&fs::metadata(path)?))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<FileInfo> {
+        Ok(file_info(&fs::symlink_metadata(path)?))
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn BufRead + Send>> {
+        // Opening a directory succeeds on some systems, leaving reads to fail later
+        if fs::metadata(path)?.is_dir() {
+            return Err(is_a_directory());
+        }
+        Ok(Box::new(BufReader::new(fs::File::open(path)?)))
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn append(&self, path: &Path, contents: &str) -> io::Result<()> {
+        fs::OpenOptions::new().create(true).append(true).open(path)?.write_all(contents.as_bytes())
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        match fs::rename(from, to) {
+            // Another filesystem cannot take the entry as it is, so copy it there instead
+            Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+                copy_entry(from, to)?;
+                if fs::symlink_metadata(from)?.is_dir() {
+                    fs::remove_dir_all(from)
+                } else {
+                    fs::remove_file(from)
+                }
+            }
+            result => result,
+        }
+    }
+
+    fn copy_file(&self, from: &Path, to: &Path, progress: &mut dyn FnMut(u64)) -> io::Result<u64> {
+        // Copied a chunk at a time rather than with `fs::copy`, so progress can be reported
+        let mut reader = fs::File::open(from)?;
+        let permissions = reader.metadata()?.permissions();
+        let mut writer = fs::File::create(to)?;
+        let mut buffer = vec![0; COPY_CHUNK_SIZE];
+        let mut copied = 0;
+        loop {
+            let read = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            writer.write_all(&buffer[..read])?;
+            copied += read as u64;
+            progress(copied);
+        }
+        fs::set_permissions(to, permissions)?;
+        Ok(copied)
+    }
+
+    fn copy_permissions(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let meta = fs::metadata(from)?;
+        fs::set_permissions(to, meta.permissions())?;
+        // Changing the owner takes privileges most users lack, so it is only attempted
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let _ = std::os::unix::fs::chown(to, Some(meta.uid()), Some(meta.gid()));
+        }
+        Ok(())
+    }
+
+    fn set_modified(&self, path: &Path, time: SystemTime) -> io::Result<()> {
+        let time = FileTime::from_system_time(time);
+        filetime::set_file_times(path, time, time)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        let physical = env::current_dir()?;
+        // Prefer the path `cd` took, as long as it still leads to the same directory
+        let logical = LOGICAL_CWD.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        match logical {
+            Some(logical) if fs::canonicalize(&logical).is_ok_and(|resolved| resolved == physical) => Ok(logical),
+            _ => Ok(physical),
+        }
+    }
+
+    fn set_current_dir(&self, path: &Path) -> io::Result<()> {
+        // Like `cd -L`, `..` leaves a symlinked directory the way it was entered
+        let logical = lexical_join(&self.current_dir()?, path);
+        let logical = match env::set_current_dir(&logical) {
+            Ok(()) => logical,
+            Err(_) => {
+                env::set_current_dir(path)?;
+                env::current_dir()?
+            }
+        };
+        *LOGICAL_CWD.lock().unwrap_or_else(|e| e.into_inner()) = Some(logical);
+        Ok(())
+    }
+
+    fn home_dir(&self) -> Option<PathBuf> {
+        dirs::home_dir()
+    }
+}
+
+/// Copies a file, symlink, or directory with everything inside it from `from` to `to`.
+fn copy_entry(from: &Path, to: &Path) -> io::Result<()> {
+    let file_type = fs::symlink_metadata(from)?.file_type();
+    #[cfg(unix)]
+    if file_type.is_symlink() {
+        return std::os::unix::fs::symlink(fs::read_link(from)?, to);
+    }
+    if file_type.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_entry(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        return Ok(());
+    }
+    fs::copy(from, to).map(|_| ())
+}
+
+/// Converts the real filesystem's metadata to the details commands use.
+fn file_info(meta: &fs::Metadata) -> FileInfo {
+    FileInfo {
+        is_dir: meta.is_dir(),
+        is_symlink: meta.file_type().is_symlink(),
+        len: meta.len(),
+        modified: meta.modified().ok(),
+    }
+}
+
+/// A node in the in-memory tree. Files keep their contents and modification time.
+enum Node {
+    File(String, SystemTime),
+    Directory,
+}
+
+/// Mutable state of a `MemoryFileSystem`, kept behind one lock.
+struct MemoryState {
+    /// Every file and directory, keyed by normalized absolute path.
+    nodes: BTreeMap<PathBuf, Node>,
+    cwd: PathBuf,
+}
+
+/// A filesystem held entirely in memory and rooted at `/`. Nothing is written to disk.
+pub struct MemoryFileSystem {
+    state: Mutex<MemoryState>,
+}
+
+impl MemoryFileSystem {
+    /// Creates a filesystem containing only an empty root directory.
+    pub fn new() -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(PathBuf::from("/"), Node::Directory);
+        MemoryFileSystem { state: Mutex::new(MemoryState { nodes, cwd: PathBuf::from("/") }) }
+    }
+
+    /// Creates a filesystem whose root holds a copy of the real directory `source`.
+    ///
+    /// Symlinks are skipped, and files that are not valid UTF-8 are copied lossily.
+    pub fn from_directory(source: &Path) -> io::Result<Self> {
+        let memory = MemoryFileSystem::new();
+        {
+            let mut state = memory.lock();
+            copy_tree(source, Path::new("/"), &mut state.nodes)?;
+        }
+        Ok(memory)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MemoryState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for MemoryFileSystem {
+    fn default() -> Self {
+        MemoryFileSystem::new()
+    }
+}
+
+/// Recursively copies the real directory `source` into `nodes` at `target`.
+fn copy_tree(source: &Path, target: &Path, nodes: &mut BTreeMap<PathBuf, Node>) -> io::Result<()> {
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = target.join(entry.file_name());
+
+        if file_type.is_dir() {
+            nodes.insert(path.clone(), Node::Directory);
+            copy_tree(&entry.path(), &path, nodes)?;
+        } else if file_type.is_file() {
+            let bytes = fs::read(entry.path())?;
+            let modified = entry.metadata()?.modified().unwrap_or_else(|_| SystemTime::now());
+            nodes.insert(path, Node::File(String::from_utf8_lossy(&bytes).into_owned(), modified));
+        }
+    }
+    Ok(())
+}
+
+/// Joins `path` onto `cwd` and resolves `.` and `..` lexically.
+fn normalize(cwd: &Path, path: &Path) -> PathBuf {
+    let mut result = PathBuf::from("/");
+    for component in cwd.join(path).components() {
+        match component {
+            Component::RootDir | Component::Prefix(_) => result = PathBuf::from("/"),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            Component::Normal(name) => result.push(name),
+        }
+    }
+    result
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(ErrorKind::NotFound, "No such file or directory")
+}
+
+fn already_exists() -> io::Error {
+    io::Error::new(ErrorKind::AlreadyExists, "File exists")
+}
+
+fn is_a_directory() -> io::Error {
+    io::Error::new(ErrorKind::IsADirectory, "Is a directory")
+}
+
+fn not_a_directory() -> io::Error {
+    io::Error::new(ErrorKind::NotADirectory, "Not a directory")
+}
+
+impl MemoryState {
+    fn resolve(&self, path: &Path) -> PathBuf {
+        normalize(&self.cwd, path)
+    }
+
+    /// Checks that the parent of `path` exists and is a directory.
+    fn require_parent_dir(&self, path: &Path) -> io::Result<()> {
+        match path.parent().map(|parent| self.nodes.get(parent)) {
+            Some(Some(Node::Directory)) => Ok(()),
+            Some(Some(Node::File(..))) => Err(not_a_directory()),
+            _ => Err(not_found()),
+        }
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn metadata(&self, path: &Path) -> io::Result<FileInfo> {
+        let state = self.lock();
+        match state.nodes.get(&state.resolve(path)) {
+            Some(Node::File(contents, modified)) => {
+                Ok(FileInfo { len: contents.len() as u64, modified: Some(*modified), ..FileInfo::default() })
+            }
+            Some(Node::Directory) => Ok(FileInfo { is_dir: true, ..FileInfo::default() }),
+            None => Err(not_found()),
+        }
+    }
+
+    // The in-memory tree has no symlinks
+    fn symlink_metadata(&self, path: &Path) -> io::Result<FileInfo> {
+        self.metadata(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.metadata(path)?;
+        Err(io::Error::new(ErrorKind::InvalidInput, "Invalid argument"))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<String>> {
+        let state = self.lock();
+        let dir = state.resolve(path);
+        match state.nodes.get(&dir) {
+            Some(Node::Directory) => {}
+            Some(Node::File(..)) => return Err(not_a_directory()),
+            None => return Err(not_found()),
+        }
+
+        Ok(state
+            .nodes
+            .keys()
+            .filter(|key| key.parent() == Some(dir.as_path()))
+            .filter_map(|key| key.file_name().map(|name| name.to_string_lossy().to_string()))
+            .collect())
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        let state = self.lock();
+        match state.nodes.get(&state.resolve(path)) {
+            Some(Node::File(contents, _)) => Ok(contents.clone()),
+            Some(Node::Directory) => Err(is_a_directory()),
+            None => Err(not_found()),
+        }
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn BufRead + Send>> {
+        Ok(Box::new(Cursor::new(self.read_to_string(path)?.into_bytes())))
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        let mut state = self.lock();
+        let file = state.resolve(path);
+        if let Some(Node::Directory) = state.nodes.get(&file) {
+            return Err(is_a_directory());
+        }
+        state.require_parent_dir(&file)?;
+        state.nodes.insert(file, Node::File(contents.to_string(), SystemTime::now()));
+        Ok(())
+    }
+
+    fn append(&self, path: &Path, contents: &str) -> io::Result<()> {
+        let mut state = self.lock();
+        let file = state.resolve(path);
+        match state.nodes.get_mut(&file) {
+            Some(Node::File(existing, modified)) => {
+                existing.push_str(contents);
+                *modified = SystemTime::now();
+                Ok(())
+            }
+            Some(Node::Directory) => Err(is_a_directory()),
+            None => {
+                state.require_parent_dir(&file)?;
+                state.nodes.insert(file, Node::File(contents.to_string(), SystemTime::now()));
+                Ok(())
+            }
+        }
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.lock();
+        let dir = state.resolve(path);
+        if state.nodes.contains_key(&dir) {
+            return Err(already_exists());
+        }
+        state.require_parent_dir(&dir)?;
+        state.nodes.insert(dir, Node::Directory);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.lock();
+        let file = state.resolve(path);
+        match state.nodes.get(&file) {
+            Some(Node::File(..)) => {
+                state.nodes.remove(&file);
+                Ok(())
+            }
+            Some(Node::Directory) => Err(is_a_directory()),
+            None => Err(not_found()),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.lock();
+        let dir = state.resolve(path);
+        match state.nodes.get(&dir) {
+            Some(Node::Directory) if dir.parent().is_none() => {
+                Err(io::Error::new(ErrorKind::PermissionDenied, "Cannot remove the root directory"))
+            }
+            Some(Node::Directory) => {
+                if state.nodes.keys().any(|key| key.parent() == Some(dir.as_path())) {
+                    return Err(io::Error::new(ErrorKind::DirectoryNotEmpty, "Directory not empty"));
+                }
+                state.nodes.remove(&dir);
+                Ok(())
+            }
+            Some(Node::File(..)) => Err(not_a_directory()),
+            None => Err(not_found()),
+        }
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.lock();
+        let dir = state.resolve(path);
+        match state.nodes.get(&dir) {
+            Some(Node::Directory) if dir.parent().is_none() => {
+                Err(io::Error::new(ErrorKind::PermissionDenied, "Cannot remove the root directory"))
+            }
+            Some(Node::Directory) => {
+                state.nodes.retain(|key, _| !key.starts_with(&dir));
+                Ok(())
+            }
+            Some(Node::File(..)) => Err(not_a_directory()),
+            None => Err(not_found()),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut state = self.lock();
+        let (source, target) = (state.resolve(from), state.resolve(to));
+        let is_dir = match state.nodes.get(&source) {
+            Some(Node::Directory) if source.parent().is_none() || target.starts_with(&source) => {
+                return Err(io::Error::new(ErrorKind::InvalidInput, "Invalid argument"))
+            }
+            Some(node) => matches!(node, Node::Directory),
+            None => return Err(not_found()),
+        };
+        match state.nodes.get(&target) {
+            Some(Node::Directory) => return Err(already_exists()),
+            Some(Node::File(..)) if is_dir => return Err(not_a_directory()),
+            _ => state.require_parent_dir(&target)?,
+        }
+
+        let moved: Vec<PathBuf> = state.nodes.keys().filter(|key| key.starts_with(&source)).cloned().collect();
+        for key in moved {
+            if let Some(node) = state.nodes.remove(&key) {
+                let inside = key.strip_prefix(&source).unwrap_or(Path::new(""));
+                state.nodes.insert(target.join(inside), node);
+            }
+        }
+        Ok(())
+    }
+
+    fn copy_file(&self, from: &Path, to: &Path, progress: &mut dyn FnMut(u64)) -> io::Result<u64> {
+        let mut state = self.lock();
+        let (source, target) = (state.resolve(from), state.resolve(to));
+        let contents = match state.nodes.get(&source) {
+            Some(Node::File(contents, _)) => contents.clone(),
+            Some(Node::Directory) => return Err(is_a_directory()),
+            None => return Err(not_found()),
+        };
+        if let Some(Node::Directory) = state.nodes.get(&target) {
+            return Err(is_a_directory());
+        }
+        state.require_parent_dir(&target)?;
+        let len = contents.len() as u64;
+        state.nodes.insert(target, Node::File(contents, SystemTime::now()));
+        progress(len);
+        Ok(len)
+    }
+
+    // Files in memory have no permissions or owner to copy
+    fn copy_permissions(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.metadata(from)?;
+        self.metadata(to).map(|_| ())
+    }
+
+    fn set_modified(&self, path: &Path, time: SystemTime) -> io::Result<()> {
+        let mut state = self.lock();
+        let file = state.resolve(path);
+        match state.nodes.get_mut(&file) {
+            Some(Node::File(_, modified)) => {
+                *modified = time;
+                Ok(())
+            }
+            // Directories in memory have no modification time to set
+            Some(Node::Directory) => Ok(()),
+            None => Err(not_found()),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let state = self.lock();
+        let resolved = state.resolve(path);
+        if state.nodes.contains_key(&resolved) {
+            Ok(resolved)
+        } else {
+            Err(not_found())
+        }
+    }
+
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        Ok(self.lock().cwd.clone())
+    }
+
+    fn set_current_dir(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.lock();
+        let dir = state.resolve(path);
+        match state.nodes.get(&dir) {
+            Some(Node::Directory) => {
+                state.cwd = dir;
+                Ok(())
+            }
+            Some(Node::File(..)) => Err(not_a_directory()),
+            None => Err(not_found()),
+        }
+    }
+
+    fn home_dir(&self) -> Option<PathBuf> {
+        Some(PathBuf::from("/"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lexical_join_keeps_the_path_as_typed() {
+        assert_eq!(lexical_join(Path::new("/srv/link"), Path::new("../other/./x")), PathBuf::from("/srv/other/x"));
+        assert_eq!(lexical_join(Path::new("/srv"), Path::new("/..")), PathBuf::from("/"));
+    }
+
+    #[test]
+    fn files_round_trip_through_memory() {
+        let memory = MemoryFileSystem::new();
+        memory.create_dir(Path::new("docs")).unwrap();
+        memory.write(Path::new("docs/a.txt"), "hello").unwrap();
+
+        assert_eq!(memory.read_to_string(Path::new("/docs/a.txt")).unwrap(), "hello");
+        assert_eq!(memory.read_dir(Path::new("/docs")).unwrap(), vec!["a.txt"]);
+
+        memory.remove_file(Path::new("docs/a.txt")).unwrap();
+        memory.remove_dir(Path::new("docs")).unwrap();
+        assert!(memory.read_dir(Path::new("/")).unwrap().is_empty());
+
+        memory.create_dir(Path::new("tree")).unwrap();
+        memory.create_dir(Path::new("tree/sub")).unwrap();
+        memory.write(Path::new("tree/sub/b.txt"), "deep").unwrap();
+        memory.write(Path::new("treehouse"), "kept").unwrap();
+        memory.remove_dir_all(Path::new("tree")).unwrap();
+        assert_eq!(memory.read_dir(Path::new("/")).unwrap(), vec!["treehouse"]);
+    }
+
+    #[test]
+    fn relative_paths_follow_current_directory() {
+        let memory = MemoryFileSystem::new();
+        memory.create_dir(Path::new("/a")).unwrap();
+        memory.create_dir(Path::new("/a/b")).unwrap();
+        memory.set_current_dir(Path::new("/a/b")).unwrap();
+        memory.write(Path::new("../note"), "x").unwrap();
+
+        assert_eq!(memory.current_dir().unwrap(), PathBuf::from("/a/b"));
+        assert_eq!(memory.canonicalize(Path::new("../note")).unwrap(), PathBuf::from("/a/note"));
+        assert_eq!(memory.canonicalize(Path::new("../../..")).unwrap(), PathBuf::from("/"));
+    }
+
+    #[test]
+    fn errors_match_the_real_filesystem() {
+        let memory = MemoryFileSystem::new();
+        memory.create_dir(Path::new("/dir")).unwrap();
+        memory.write(Path::new("/dir/file"), "").unwrap();
+
+        let kind = |result: io::Result<()>| result.unwrap_err().kind();
+        assert_eq!(kind(memory.create_dir(Path::new("/dir"))), ErrorKind::AlreadyExists);
+        assert_eq!(kind(memory.remove_dir(Path::new("/dir"))), ErrorKind::DirectoryNotEmpty);
+        assert_eq!(kind(memory.remove_file(Path::new("/dir"))), ErrorKind::IsADirectory);
+        assert_eq!(kind(memory.write(Path::new("/missing/file"), "")), ErrorKind::NotFound);
+        assert_eq!(kind(memory.set_current_dir(Path::new("/dir/file"))), ErrorKind::NotADirectory);
+        assert_eq!(kind(memory.rename(Path::new("/dir"), Path::new("/dir/inside"))), ErrorKind::InvalidInput);
+        let copied = memory.copy_file(Path::new("/dir/file"), Path::new("/dir"), &mut |_| {});
+        assert_eq!(kind(copied.map(|_| ())), ErrorKind::IsADirectory);
+    }
+
+    #[test]
+    fn renaming_a_directory_moves_everything_inside_it() {
+        let memory = MemoryFileSystem::new();
+        memory.create_dir(Path::new("/dir")).unwrap();
+        memory.write(Path::new("/dir/file"), "text").unwrap();
+        memory.write(Path::new("/other"), "").unwrap();
+
+        memory.rename(Path::new("/dir"), Path::new("/moved")).unwrap();
+        assert_eq!(memory.read_to_string(Path::new("/moved/file")).unwrap(), "text");
+        assert!(memory.metadata(Path::new("/dir")).is_err());
+        memory.rename(Path::new("/moved/file"), Path::new("/other")).unwrap();
+        assert_eq!(memory.read_to_string(Path::new("/other")).unwrap(), "text");
+    }
+
+    #[test]
+    fn seeding_copies_a_real_directory() {
+        let source = tempfile::TempDir::new().unwrap();
+        fs::create_dir(source.path().join("sub")).unwrap();
+        fs::write(source.path().join("sub/data.txt"), "seeded").unwrap();
+
+        let memory = MemoryFileSystem::from_directory(source.path()).unwrap();
+        assert_eq!(memory.read_to_string(Path::new("/sub/data.txt")).unwrap(), "seeded");
+
+        // Changes stay in memory
+        memory.remove_file(Path::new("/sub/data.txt")).unwrap();
+        assert!(source.path().join("sub/data.txt").exists());
+    }
+}
@@ -0,0 +1,353 @@
+//! The `find` command: walking a directory tree for entries that pass every test given, and
+//! listing them or running a command on them.
+
+use crate::filesystem::FileInfo;
+use crate::shell::{expand_path, handle_command, join_output, quote_word, CommandOutput, ShellContext};
+use crate::walk::Walker;
+use regex::Regex;
+use std::time::SystemTime;
+
+/// How a number given to a test compares with an entry's: `+N` is more than N, `-N` less
+/// than N, and plain `N` exactly N.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Bound {
+    MoreThan(u64),
+    LessThan(u64),
+    Exactly(u64),
+}
+
+impl Bound {
+    fn parse(text: &str) -> Option<(Bound, &str)> {
+        let (make, rest): (fn(u64) -> Bound, &str) = match text.as_bytes().first() {
+            Some(b'+') => (Bound::MoreThan, &text[1..]),
+            Some(b'-') => (Bound::LessThan, &text[1..]),
+            _ => (Bound::Exactly, text),
+        };
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let value = rest[..digits].parse().ok()?;
+        Some((make(value), &rest[digits..]))
+    }
+
+    fn matches(self, value: u64) -> bool {
+        match self {
+            Bound::MoreThan(bound) => value > bound,
+            Bound::LessThan(bound) => value < bound,
+            Bound::Exactly(bound) => value == bound,
+        }
+    }
+}
+
+/// One test from the `find` expression. An entry is listed only if it passes them all.
+#[derive(Debug)]
+enum Predicate {
+    /// `-name <pattern>`: the entry's own name matches a shell pattern.
+    Name(Regex),
+    /// `-type d` or `-type f`: the entry is (or is not) a directory.
+    Type { directory: bool },
+    /// `-mtime N`: whole days since the entry was last modified.
+    ModifiedDays(Bound),
+    /// `-size N[ckMG]`: size in units of `unit` bytes, rounded up.
+    Size { bound: Bound, unit: u64 },
+}
+
+impl Predicate {
+    /// Returns true if the entry called `name` with details `info` passes the test.
+    fn matches(&self, name: &str, info: &FileInfo, now: SystemTime) -> bool {
+        match self {
+            Predicate::Name(pattern) => pattern.is_match(name),
+            Predicate::Type { directory } => info.is_dir == *directory,
+            Predicate::ModifiedDays(bound) => {
+                let Some(modified) = info.modified else {
+                    return false;
+                };
+                let age = now.duration_since(modified).map_or(0, |age| age.as_secs());
+                bound.matches(age / 86_400)
+            }
+            Predicate::Size { bound, unit } => bound.matches(info.len.div_ceil(*unit)),
+        }
+    }
+}
+
+/// The command `-exec` runs on the entries found.
+#[derive(Debug)]
+struct Exec {
+    /// The words of the command, where `{}` stands for an entry's path.
+    template: Vec<String>,
+    /// Whether every path goes to a single run (`+`) rather than one run per path (`;`).
+    batch: bool,
+}
+
+impl Exec {
+    /// Builds the command lines to run for `paths`.
+    fn command_lines(&self, paths: &[String]) -> Vec<String> {
+        if self.batch {
+            // `{}` is the last word, and stands for all the paths at once
+            let words = self.template[..self.template.len() - 1].iter().chain(paths);
+            return vec![words.map(|word| quote_word(word)).collect::<Vec<_>>().join(" ")];
+        }
+        paths
+            .iter()
+            .map(|path| {
+                let words = self.template.iter().map(|word| quote_word(&word.replace("{}", path)));
+                words.collect::<Vec<_>>().join(" ")
+            })
+            .collect()
+    }
+}
+
+/// The expression after the starting points: the tests, and the command to run if any.
+#[derive(Debug)]
+struct Expression {
+    predicates: Vec<Predicate>,
+    exec: Option<Exec>,
+}
+
+/// Reads the `-exec` command from `args`, up to its `;` or `+`. A `;` typed bare ends the
+/// whole command line before `find` sees it, so the end of the arguments also ends it.
+fn parse_exec<'a>(args: &mut impl Iterator<Item = &'a &'a str>) -> Result<Exec, String> {
+    let mut template = Vec::new();
+    let mut batch = false;
+    for &arg in args.by_ref() {
+        match arg {
+            ";" | "\\;" | "';'" | "\";\"" => break,
+            "+" if template.last().is_some_and(|last| last == "{}") => {
+                batch = true;
+                break;
+            }
+            _ => template.push(arg.to_string()),
+        }
+    }
+    if template.is_empty() {
+        return Err("Error: find: missing argument to '-exec'.".to_string());
+    }
+    Ok(Exec { template, batch })
+}
+
+/// Reads the expression that follows the starting points, such as
+/// `-name '*.txt' -type f -exec rm {} \;`.
+fn parse_expression<'a>(args: &'a [&'a str]) -> Result<Expression, String> {
+    let mut predicates = Vec::new();
+    let mut exec = None;
+    let mut args = args.iter();
+    while let Some(&test) = args.next() {
+        if test == "-exec" {
+            if exec.is_some() {
+                return Err("Error: find: only one '-exec' is supported.".to_string());
+            }
+            exec = Some(parse_exec(&mut args)?);
+            continue;
+        }
+        let value = match test {
+            "-name" | "-type" | "-mtime" | "-size" => args
+                .next()
+                .copied()
+                .ok_or_else(|| format!("Error: find: missing argument to '{}'.", test))?,
+            _ if test.starts_with('-') => return Err(format!("Error: find: unknown predicate '{}'.", test)),
+            _ => return Err(format!("Error: find: paths must come before the expression: '{}'.", test)),
+        };
+        let invalid = || format!("Error: find: invalid argument '{}' to '{}'.", value, test);
+        let predicate = match test {
+            "-name" => Predicate::Name(pattern_regex(value)),
+            "-type" => match value {
+                "d" => Predicate::Type { directory: true },
+                "f" => Predicate::Type { directory: false },
+                _ => return Err(invalid()),
+            },
+            "-mtime" => match Bound::parse(value) {
+                Some((bound, "")) => Predicate::ModifiedDays(bound),
+                _ => return Err(invalid()),
+            },
+            _ => {
+                let (bound, suffix) = Bound::parse(value).ok_or_else(invalid)?;
+                // Like GNU find, a bare number counts 512-byte blocks
+                let unit = match suffix {
+                    "" | "b" => 512,
+                    "c" => 1,
+                    "k" => 1024,
+                    "M" => 1024 * 1024,
+                    "G" => 1024 * 1024 * 1024,
+                    _ => return Err(invalid()),
+                };
+                Predicate::Size { bound, unit }
+            }
+        };
+        predicates.push(predicate);
+    }
+    Ok(Expression { predicates, exec })
+}
+
+/// Translates a shell pattern (`*`, `?`, and `[...]` classes) into an anchored regex.
+pub fn pattern_regex(pattern: &str) -> Regex {
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                let rest: String = chars.clone().collect();
+                match rest.find(']').filter(|&end| end > 0) {
+                    Some(end) => {
+                        let class = &rest[..end];
+                        let (negated, class) = match class.strip_prefix('!') {
+                            Some(class) => (true, class),
+                            None => (false, class),
+                        };
+                        regex.push('[');
+                        if negated {
+                            regex.push('^');
+                        }
+                        regex.push_str(&class.replace('\\', "\\\\").replace('[', "\\["));
+                        regex.push(']');
+                        for _ in 0..rest[..=end].chars().count() {
+                            chars.next();
+                        }
+                    }
+                    // An unclosed bracket is an ordinary character
+                    None => regex.push_str("\\["),
+                }
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    // Only a reversed range such as `[z-a]` can fail to compile, and it then matches nothing
+    Regex::new(&regex).unwrap_or_else(|_| Regex::new("$^").expect("valid regex"))
+}
+
+/// Handles the `find` command: lists every entry under the starting points (the current
+/// directory if none are given) that passes all the tests, one path per line, or with
+/// `-exec` runs a command on them instead.
+///
+/// Entries are visited depth-first, each directory's entries in name order. With
+/// `--gitignore`, entries ignored by `.gitignore` files and `.git` directories are skipped.
+/// Errors reading a directory, and commands that fail, are reported and the rest go on,
+/// ending with status 1.
+pub fn find_command(args: Vec<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    let gitignore = args.contains(&"--gitignore");
+    let args: Vec<&str> = args.into_iter().filter(|&arg| arg != "--gitignore").collect();
+    let split = args.iter().position(|arg| arg.starts_with('-')).unwrap_or(args.len());
+    let (roots, tests) = args.split_at(split);
+    let Expression { predicates, exec } = match parse_expression(tests) {
+        Ok(expression) => expression,
+        Err(e) => return CommandOutput::error(e),
+    };
+    let roots = if roots.is_empty() { &["."] } else { roots };
+
+    let now = SystemTime::now();
+    let mut found = Vec::new();
+    let mut errors = Vec::new();
+    // Past the lines the screen shows, the rest of the walk is not worth doing
+    let limit = ctx.line_limit().filter(|_| exec.is_none());
+    let mut walker = Walker::new(ctx, "find", gitignore);
+    for root in roots {
+        let path = match expand_path(ctx, root) {
+            Ok(path) => path,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        match ctx.fs.metadata(&path) {
+            Ok(info) => walker.walk(&path, root, &info, &mut |entry| {
+                if limit.is_some_and(|limit| found.len() > limit) {
+                    return false;
+                }
+                if predicates.iter().all(|predicate| predicate.matches(entry.name, entry.info, now)) {
+                    found.push(entry.shown.to_string());
+                }
+                true
+            }),
+            Err(e) => errors.push(format!("Error: find: '{}': {}", root, e)),
+        }
+        errors.append(&mut walker.errors);
+    }
+
+    let mut stdout = found.join("\n");
+    if let Some(exec) = exec {
+        stdout = run_exec(&exec, &found, ctx, &mut errors);
+    }
+    let exit_code = if errors.is_empty() { 0 } else { 1 };
+    CommandOutput { stdout, stderr: errors.join("\n"), exit_code }
+}
+
+/// Runs the `-exec` command on the entries found, returning what the runs printed. Errors,
+/// and the status of each run that fails, are added to `errors`.
+fn run_exec(exec: &Exec, paths: &[String], ctx: &mut ShellContext, errors: &mut Vec<String>) -> String {
+    let mut results = Vec::new();
+    // A batch with nothing found is not run at all
+    let lines = if paths.is_empty() { Vec::new() } else { exec.command_lines(paths) };
+    for line in lines {
+        let output = handle_command(&line, None, ctx);
+        if !output.stdout.is_empty() {
+            results.push(output.stdout);
+        }
+        if !output.stderr.is_empty() {
+            errors.push(output.stderr);
+        }
+        if output.exit_code != 0 {
+            errors.push(format!("Error: find: '{}' exited with status {}.", line, output.exit_code));
+        }
+    }
+    join_output(&results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn shell_patterns_match_whole_names() {
+        assert!(pattern_regex("*.txt").is_match("notes.txt"));
+        assert!(!pattern_regex("*.txt").is_match("notes.txt.bak"));
+        assert!(pattern_regex("file?.[ch]").is_match("file1.c"));
+        assert!(!pattern_regex("file[!0-9].c").is_match("file1.c"));
+        assert!(pattern_regex("a.b[").is_match("a.b["));
+    }
+
+    #[test]
+    fn sizes_round_up_to_their_unit() {
+        let tests = parse_expression(&["-size", "+1k", "-size", "-3k"]).unwrap().predicates;
+        let file = |len| FileInfo { len, ..FileInfo::default() };
+        let now = SystemTime::now();
+        let passes = |len| tests.iter().all(|test| test.matches("f", &file(len), now));
+        assert!(!passes(1024));
+        assert!(passes(1025));
+        assert!(passes(2048));
+        assert!(!passes(2049));
+    }
+
+    #[test]
+    fn mtime_counts_whole_days() {
+        let now = SystemTime::now();
+        let aged = |days: u64| FileInfo { modified: Some(now - Duration::from_secs(days * 86_400 + 60)), ..FileInfo::default() };
+        let older = &parse_expression(&["-mtime", "+2"]).unwrap().predicates[0];
+        let exactly = &parse_expression(&["-mtime", "2"]).unwrap().predicates[0];
+        assert!(!older.matches("f", &aged(2), now) && older.matches("f", &aged(3), now));
+        assert!(exactly.matches("f", &aged(2), now) && !exactly.matches("f", &aged(1), now));
+    }
+
+    #[test]
+    fn bad_expressions_are_reported() {
+        assert_eq!(parse_expression(&["-size", "1X"]).unwrap_err(), "Error: find: invalid argument '1X' to '-size'.");
+        assert_eq!(parse_expression(&["-mtime"]).unwrap_err(), "Error: find: missing argument to '-mtime'.");
+        assert_eq!(parse_expression(&["-perm", "644"]).unwrap_err(), "Error: find: unknown predicate '-perm'.");
+        assert_eq!(parse_expression(&["-exec", ";"]).unwrap_err(), "Error: find: missing argument to '-exec'.");
+    }
+
+    #[test]
+    fn exec_runs_once_per_path_or_once_for_all() {
+        let paths = ["./a".to_string(), "./b".to_string()];
+        let each = parse_expression(&["-exec", "mv", "{}", "{}.bak", "\\;"]).unwrap().exec.unwrap();
+        assert_eq!(each.command_lines(&paths), ["mv ./a ./a.bak", "mv ./b ./b.bak"]);
+
+        let batch = parse_expression(&["-type", "f", "-exec", "rm", "{}", "+"]).unwrap();
+        assert_eq!(batch.predicates.len(), 1);
+        assert_eq!(batch.exec.unwrap().command_lines(&paths), ["rm ./a ./b"]);
+
+        // A `+` that does not follow `{}` is part of the command
+        let plus = parse_expression(&["-exec", "echo", "+", "{}"]).unwrap().exec.unwrap();
+        assert_eq!(plus.command_lines(&paths[..1]), ["echo + ./a"]);
+    }
+}
@@ -0,0 +1,75 @@
+//! Following a file as it grows, for `tail -f`.
+
+use crate::filesystem::FileSystem;
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+/// A file `tail -f` keeps showing, read from where the last look at it ended.
+pub struct Follow {
+    /// The file's name as typed, for messages.
+    pub name: String,
+    path: PathBuf,
+    /// Number of bytes of the file already shown.
+    offset: u64,
+    /// The end of the file after its last newline, held back until the line is complete.
+    partial: String,
+}
+
+impl Follow {
+    /// Starts following the file at `path`, of which the first `offset` bytes were shown.
+    pub fn new(name: &str, path: PathBuf, offset: u64) -> Self {
+        Follow { name: name.to_string(), path, offset, partial: String::new() }
+    }
+
+    /// Returns the lines added to the file since the last call. A file that has become
+    /// shorter was truncated, so it is read again from the start after a line saying so.
+    pub fn poll(&mut self, fs: &dyn FileSystem) -> io::Result<Vec<String>> {
+        let len = fs.metadata(&self.path)?.len;
+        let mut lines = Vec::new();
+        if len < self.offset {
+            lines.push(format!("tail: {}: file truncated", self.name));
+            self.offset = 0;
+            self.partial.clear();
+        }
+        if len == self.offset {
+            return Ok(lines);
+        }
+
+        let mut reader = fs.open(&self.path)?;
+        io::copy(&mut reader.by_ref().take(self.offset), &mut io::sink())?;
+        let mut added = Vec::new();
+        reader.read_to_end(&mut added)?;
+        self.offset += added.len() as u64;
+        self.partial.push_str(&String::from_utf8_lossy(&added));
+
+        if let Some(end) = self.partial.rfind('\n') {
+            let complete: String = self.partial.drain(..=end).collect();
+            lines.extend(complete.lines().map(str::to_string));
+        }
+        Ok(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::MemoryFileSystem;
+    use std::path::Path;
+
+    #[test]
+    fn new_lines_are_returned_once_complete() {
+        let fs = MemoryFileSystem::new();
+        fs.write(Path::new("/app.log"), "old\n").unwrap();
+        let mut follow = Follow::new("app.log", PathBuf::from("/app.log"), 4);
+
+        assert!(follow.poll(&fs).unwrap().is_empty());
+        fs.append(Path::new("/app.log"), "one\ntw").unwrap();
+        assert_eq!(follow.poll(&fs).unwrap(), ["one"]);
+        fs.append(Path::new("/app.log"), "o\n").unwrap();
+        assert_eq!(follow.poll(&fs).unwrap(), ["two"]);
+        fs.write(Path::new("/app.log"), "new\n").unwrap();
+        assert_eq!(follow.poll(&fs).unwrap(), ["tail: app.log: file truncated", "new"]);
+        fs.remove_file(Path::new("/app.log")).unwrap();
+        assert!(follow.poll(&fs).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+//! Filename expansion: words with `*`, `?`, or `[...]` replaced by the paths they match.
+
+use crate::find::pattern_regex;
+use crate::shell::{expand_path, ShellContext};
+
+/// Returns true if `word` is a pattern to expand: it has `*`, `?`, or `[`, and no quotes
+/// or backslashes, which keep those characters as typed.
+fn is_pattern(word: &str) -> bool {
+    word.contains(['*', '?', '[']) && !word.contains(['"', '\'', '\\'])
+}
+
+/// Expands a word into the paths its pattern matches, sorted, written the way the word
+/// was (relative, absolute, or starting with `~`). Each `/`-separated part of the word may
+/// be a pattern. A word that is not a pattern, or matches nothing, is returned unchanged.
+///
+/// As in other shells, a name starting with `.` is only matched by a pattern that starts
+/// with `.` too.
+pub fn expand_glob(word: &str, ctx: &ShellContext) -> Vec<String> {
+    if !is_pattern(word) {
+        return vec![word.to_string()];
+    }
+    let mut matches = matching_paths("", word, ctx);
+    if matches.is_empty() {
+        return vec![word.to_string()];
+    }
+    matches.sort();
+    matches
+}
+
+/// Returns the paths matched by `rest`, the part of a pattern after `prefix`, where
+/// `prefix` has no patterns left in it and is either empty or ends with `/`.
+fn matching_paths(prefix: &str, rest: &str, ctx: &ShellContext) -> Vec<String> {
+    // Copy the parts without a pattern onto the prefix as they are
+    let mut prefix = prefix.to_string();
+    let mut rest = rest;
+    while let Some((part, after)) = rest.split_once('/') {
+        if is_pattern(part) {
+            break;
+        }
+        prefix.push_str(part);
+        prefix.push('/');
+        rest = after;
+    }
+    if !is_pattern(rest) {
+        let path = format!("{}{}", prefix, rest);
+        let exists = expand_path(ctx, &path).is_ok_and(|resolved| ctx.fs.metadata(&resolved).is_ok());
+        return if exists { vec![path] } else { Vec::new() };
+    }
+
+    let (part, after) = match rest.split_once('/') {
+        Some((part, after)) => (part, Some(after)),
+        None => (rest, None),
+    };
+    let dir = if prefix.is_empty() { "." } else { prefix.as_str() };
+    let Ok(names) = expand_path(ctx, dir).and_then(|path| ctx.fs.read_dir(&path).map_err(|e| e.to_string())) else {
+        return Vec::new();
+    };
+    let regex = pattern_regex(part);
+    names
+        .into_iter()
+        .filter(|name| regex.is_match(name) && (!name.starts_with('.') || part.starts_with('.')))
+        .flat_map(|name| match after {
+            Some(after) => matching_paths(&format!("{}{}/", prefix, name), after, ctx),
+            None => vec![format!("{}{}", prefix, name)],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{FileSystem, MemoryFileSystem};
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[test]
+    fn patterns_match_each_part_of_a_path() {
+        let fs = MemoryFileSystem::new();
+        for dir in ["/src", "/src/bin", "/docs"] {
+            fs.create_dir(Path::new(dir)).unwrap();
+        }
+        for file in ["/.hidden.rs", "/main.rs", "/notes.txt", "/src/lib.rs", "/src/bin/cli.rs", "/docs/guide.md"] {
+            fs.write(Path::new(file), "").unwrap();
+        }
+        let ctx = ShellContext { fs: Arc::new(fs), ..Default::default() };
+
+        assert_eq!(expand_glob("*.rs", &ctx), ["main.rs"]);
+        assert_eq!(expand_glob(".*.rs", &ctx), [".hidden.rs"]);
+        assert_eq!(expand_glob("*/*.rs", &ctx), ["src/lib.rs"]);
+        assert_eq!(expand_glob("/src/*/c?i.rs", &ctx), ["/src/bin/cli.rs"]);
+        assert_eq!(expand_glob("[dm]*", &ctx), ["docs", "main.rs"]);
+        assert_eq!(expand_glob("*.py", &ctx), ["*.py"]);
+        assert_eq!(expand_glob("'*.rs'", &ctx), ["'*.rs'"]);
+    }
+}
@@ -0,0 +1,324 @@
+//! The `grep` command: line selection by substring or regular expression, in files,
+//! piped input, or whole directory trees.
+
+use crate::commands::{read_inputs, Inputs};
+use crate::find::pattern_regex;
+use crate::shell::{expand_path, CommandOutput, ShellContext};
+use crate::theme::Theme;
+use crate::walk::Walker;
+use regex::{Regex, RegexBuilder};
+use std::io;
+use std::path::Path;
+
+/// Options that control which lines `grep` selects and how they are reported.
+#[derive(Default)]
+struct GrepOptions {
+    ignore_case: bool,
+    invert: bool,
+    count: bool,
+    extended: bool,
+    recursive: bool,
+    /// Skip what `.gitignore` files ignore, and `.git` directories, in a recursive search.
+    gitignore: bool,
+}
+
+/// Shell patterns limiting the files `grep -r` searches, from `--include` and `--exclude`.
+///
+/// A pattern is tested against both an entry's name and its path below the directory
+/// given, so `--include='*.rs'` picks files by extension while `--exclude='target/*'`
+/// skips a whole subtree.
+#[derive(Default)]
+struct FileFilters {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+impl FileFilters {
+    /// Returns true if the file should be searched: it matches no `--exclude` pattern and,
+    /// when `--include` patterns are given, at least one of them.
+    fn searches_file(&self, name: &str, path: &str) -> bool {
+        let matches = |glob: &Regex| glob.is_match(name) || glob.is_match(path);
+        !self.exclude.iter().any(matches) && (self.include.is_empty() || self.include.iter().any(matches))
+    }
+
+    /// Returns true if the walk should go into the directory, which it skips when an
+    /// `--exclude` pattern matches its name or everything inside it.
+    fn enters_dir(&self, name: &str, path: &str) -> bool {
+        let inside = format!("{}/", path);
+        !self.exclude.iter().any(|glob| glob.is_match(name) || glob.is_match(&inside))
+    }
+}
+
+/// A compiled `grep` pattern: a plain substring by default, or a regular expression with `-E`.
+enum GrepMatcher {
+    Literal { needle: String, ignore_case: bool },
+    Regex(Regex),
+}
+
+impl GrepMatcher {
+    /// Builds the matcher for `pattern`, compiling it as a regex when `-E` is given.
+    fn new(pattern: &str, options: &GrepOptions) -> Result<Self, regex::Error> {
+        if options.extended {
+            let regex = RegexBuilder::new(pattern).case_insensitive(options.ignore_case).build()?;
+            return Ok(GrepMatcher::Regex(regex));
+        }
+
+        let needle = if options.ignore_case { pattern.to_lowercase() } else { pattern.to_string() };
+        Ok(GrepMatcher::Literal { needle, ignore_case: options.ignore_case })
+    }
+
+    /// Returns true if `line` contains the pattern.
+    fn is_match(&self, line: &str) -> bool {
+        match self {
+            GrepMatcher::Literal { needle, ignore_case: true } => line.to_lowercase().contains(needle),
+            GrepMatcher::Literal { needle, ignore_case: false } => line.contains(needle),
+            GrepMatcher::Regex(regex) => regex.is_match(line),
+        }
+    }
+
+    /// Returns `line` with each regex match highlighted; literal matches are left as-is.
+    fn highlight(&self, line: &str, theme: &Theme) -> String {
+        let regex = match self {
+            GrepMatcher::Regex(regex) => regex,
+            GrepMatcher::Literal { .. } => return line.to_string(),
+        };
+
+        let mut highlighted = String::new();
+        let mut last = 0;
+        for found in regex.find_iter(line).filter(|m| !m.is_empty()) {
+            highlighted.push_str(&line[last..found.start()]);
+            highlighted.push_str(&theme.highlight(found.as_str()));
+            last = found.end();
+        }
+        highlighted.push_str(&line[last..]);
+        highlighted
+    }
+}
+
+/// Handles the `grep` command: searches files, or piped input, for lines containing a pattern.
+///
+/// With `-r`, directories are searched through, the current one if none is given, and
+/// `--include`/`--exclude` (each may be repeated) choose which of the files inside are read.
+/// `--gitignore` also skips the files a repository's `.gitignore` files ignore.
+///
+/// Exits with status 0 if any line was selected, 1 if none were, and 2 on errors.
+pub fn grep_command(args: Vec<&str>, stdin: Option<&str>, ctx: &ShellContext) -> CommandOutput {
+    let mut options = GrepOptions::default();
+    let mut filters = FileFilters::default();
+    let mut operands = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // File filters may come anywhere, as in `grep -r TODO --include='*.rs'`
+        let (filter, value) = match arg.split_once('=') {
+            Some((flag @ ("--include" | "--exclude"), value)) => (flag, Some(value)),
+            _ if arg == "--include" || arg == "--exclude" => (arg, args.next()),
+            _ => ("", None),
+        };
+        if arg == "--" {
+            // Everything after `--` is the pattern or a file, even if it starts with `-`
+            operands.extend(args);
+            break;
+        } else if arg == "--gitignore" {
+            options.gitignore = true;
+        } else if !filter.is_empty() {
+            let Some(value) = value else {
+                return CommandOutput::error(format!("Error: grep: {} requires a pattern.", filter));
+            };
+            let glob = pattern_regex(value);
+            if filter == "--include" {
+                filters.include.push(glob);
+            } else {
+                filters.exclude.push(glob);
+            }
+        } else if operands.is_empty() && arg.len() > 1 && arg.starts_with('-') {
+            for flag in arg[1..].chars() {
+                match flag {
+                    'i' => options.ignore_case = true,
+                    'v' => options.invert = true,
+                    'c' => options.count = true,
+                    'E' => options.extended = true,
+                    'r' | 'R' => options.recursive = true,
+                    _ => return CommandOutput::error(format!("Error: Unknown grep option '-{}'.", flag)),
+                }
+            }
+        } else {
+            operands.push(arg);
+        }
+    }
+
+    if operands.is_empty() || (operands.len() < 2 && stdin.is_none() && !options.recursive) {
+        return CommandOutput::error(
+            "Error: Usage: grep [-i] [-v] [-c] [-E] [-r] [--include=<glob>] [--exclude=<glob>] [--gitignore] <pattern> <file>...",
+        );
+    }
+
+    let pattern = operands[0];
+    let matcher = match GrepMatcher::new(pattern, &options) {
+        Ok(matcher) => matcher,
+        Err(e) => return CommandOutput::error(format!("Error: Invalid regular expression '{}': {}", pattern, e)),
+    };
+
+    let files = &operands[1..];
+    let show_names = files.len() > 1 || options.recursive;
+    let inputs = if options.recursive {
+        read_tree(if files.is_empty() { &["."] } else { files }, &filters, options.gitignore, ctx)
+    } else {
+        read_inputs(files, stdin, ctx)
+    };
+    let mut results = Vec::new();
+    let mut selected_any = false;
+
+    for (file_name, content) in &inputs.sources {
+        let lines = find_matching_lines(content, &matcher, &options);
+        let name = if show_names { Some(file_name.as_str()) } else { None };
+        selected_any |= !lines.is_empty();
+        results.extend(format_grep_output(name, &lines, &matcher, &options, &ctx.options.theme));
+    }
+
+    let exit_code = if !inputs.errors.is_empty() {
+        2
+    } else if selected_any {
+        0
+    } else {
+        1
+    };
+
+    CommandOutput { stdout: results.join("\n"), stderr: inputs.errors.join("\n"), exit_code }
+}
+
+/// Reads every file under the directories in `roots` that the filters let through, each
+/// directory's entries in name order. Files named directly are read whatever their name.
+///
+/// Files that are not text, such as images and compiled programs, are skipped without an
+/// error, as GNU grep does not print their lines either.
+fn read_tree(roots: &[&str], filters: &FileFilters, gitignore: bool, ctx: &ShellContext) -> Inputs {
+    let mut inputs = Inputs { sources: Vec::new(), errors: Vec::new() };
+    let mut walker = Walker::new(ctx, "grep", gitignore);
+    for &root in roots {
+        let path = match expand_path(ctx, root) {
+            Ok(path) => path,
+            Err(e) => {
+                inputs.errors.push(e);
+                continue;
+            }
+        };
+        match ctx.fs.metadata(&path) {
+            Ok(info) if info.is_dir => {
+                // Entries under the current directory are shown without a leading `./`
+                let shown = if root == "." || root == "./" { "" } else { root };
+                walker.walk(&path, shown, &info, &mut |entry| {
+                    if entry.depth == 0 {
+                        true
+                    } else if entry.info.is_dir {
+                        filters.enters_dir(entry.name, entry.shown)
+                    } else {
+                        if filters.searches_file(entry.name, entry.shown) {
+                            read_text(entry.path, entry.shown, ctx, &mut inputs);
+                        }
+                        false
+                    }
+                });
+                inputs.errors.append(&mut walker.errors);
+            }
+            Ok(_) => read_text(&path, root, ctx, &mut inputs),
+            Err(e) => inputs.errors.push(format!("Error: grep: '{}': {}", root, e)),
+        }
+    }
+    inputs
+}
+
+/// Adds the file at `path` to `inputs` under the name `shown`, unless it is not text.
+fn read_text(path: &Path, shown: &str, ctx: &ShellContext, inputs: &mut Inputs) {
+    match ctx.fs.read_to_string(path) {
+        Ok(content) => inputs.sources.push((shown.to_string(), content)),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {}
+        Err(e) => inputs.errors.push(format!("Error reading file '{}': {}", shown, e)),
+    }
+}
+
+/// Returns the lines of `content` selected by the matcher, honoring `-v`.
+fn find_matching_lines<'a>(content: &'a str, matcher: &GrepMatcher, options: &GrepOptions) -> Vec<&'a str> {
+    content
+        .lines()
+        .filter(|line| matcher.is_match(line) != options.invert)
+        .collect()
+}
+
+/// Formats selected lines for display, or just their count when `-c` is given.
+fn format_grep_output(
+    file_name: Option<&str>,
+    lines: &[&str],
+    matcher: &GrepMatcher,
+    options: &GrepOptions,
+    theme: &Theme,
+) -> Vec<String> {
+    let prefix = file_name.map(|name| format!("{}:", name)).unwrap_or_default();
+
+    if options.count {
+        return vec![format!("{}{}", prefix, lines.len())];
+    }
+
+    lines
+        .iter()
+        .map(|line| {
+            let body = if options.invert { line.to_string() } else { matcher.highlight(line, theme) };
+            format!("{}{}", prefix, body)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "error1 disk\nwarning\nERROR22 net\nok";
+
+    fn select(pattern: &str, options: &GrepOptions) -> Vec<&'static str> {
+        let matcher = GrepMatcher::new(pattern, options).unwrap();
+        find_matching_lines(CONTENT, &matcher, options)
+    }
+
+    #[test]
+    fn literal_match_respects_case_flag() {
+        assert_eq!(select("error", &GrepOptions::default()), vec!["error1 disk"]);
+
+        let options = GrepOptions { ignore_case: true, ..Default::default() };
+        assert_eq!(select("error", &options), vec!["error1 disk", "ERROR22 net"]);
+    }
+
+    #[test]
+    fn invert_selects_non_matching_lines() {
+        let options = GrepOptions { invert: true, ignore_case: true, ..Default::default() };
+        assert_eq!(select("error", &options), vec!["warning", "ok"]);
+    }
+
+    #[test]
+    fn extended_uses_regular_expressions() {
+        let options = GrepOptions { extended: true, ignore_case: true, ..Default::default() };
+        assert_eq!(select("^error[0-9]+", &options), vec!["error1 disk", "ERROR22 net"]);
+    }
+
+    #[test]
+    fn filters_match_names_and_paths_below_the_start() {
+        let filters = FileFilters { include: vec![pattern_regex("*.rs")], exclude: vec![pattern_regex("target/*")] };
+        assert!(filters.searches_file("main.rs", "src/main.rs"));
+        assert!(!filters.searches_file("README.md", "README.md"));
+        assert!(!filters.searches_file("build.rs", "target/build.rs"));
+        assert!(!filters.enters_dir("target", "target"));
+        assert!(filters.enters_dir("src", "src"));
+
+        let filters = FileFilters { exclude: vec![pattern_regex(".git")], ..Default::default() };
+        assert!(!filters.enters_dir(".git", "sub/.git"));
+        assert!(filters.searches_file(".gitignore", ".gitignore"));
+    }
+
+    #[test]
+    fn count_reports_number_of_selected_lines() {
+        let options = GrepOptions { count: true, invert: true, ..Default::default() };
+        let matcher = GrepMatcher::new("o", &options).unwrap();
+        let lines = find_matching_lines(CONTENT, &matcher, &options);
+
+        assert_eq!(format_grep_output(None, &lines, &matcher, &options, &Theme::DEFAULT), vec!["2"]);
+    }
+}
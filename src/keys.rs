@@ -0,0 +1,190 @@
+//! Key bindings for the interactive screen, with defaults that can be changed in a file.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Something the interactive screen can do in response to a key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    /// Run the line being typed.
+    Run,
+    /// Abandon the line being typed, along with any line it continues.
+    Cancel,
+    /// Delete the last character typed.
+    DeleteBack,
+    /// Scroll the output up one screen.
+    PageUp,
+    /// Scroll the output down one screen.
+    PageDown,
+    /// Leave the emulator, if nothing has been typed.
+    Quit,
+    /// Complete the command, flag, or file name being typed.
+    Complete,
+}
+
+impl Action {
+    /// Names used for actions in the key bindings file.
+    const NAMES: &'static [(&'static str, Action)] = &[
+        ("run", Action::Run),
+        ("cancel", Action::Cancel),
+        ("delete-back", Action::DeleteBack),
+        ("page-up", Action::PageUp),
+        ("page-down", Action::PageDown),
+        ("quit", Action::Quit),
+        ("complete", Action::Complete),
+    ];
+
+    fn named(name: &str) -> Option<Action> {
+        Action::NAMES.iter().find(|(action_name, _)| *action_name == name).map(|(_, action)| *action)
+    }
+}
+
+/// The action bound to each key. Keys without a binding that produce a character type it.
+pub struct KeyBindings {
+    bindings: HashMap<(KeyCode, KeyModifiers), Action>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let bindings = [
+            ((KeyCode::Enter, KeyModifiers::NONE), Action::Run),
+            ((KeyCode::Char('c'), KeyModifiers::CONTROL), Action::Cancel),
+            ((KeyCode::Backspace, KeyModifiers::NONE), Action::DeleteBack),
+            ((KeyCode::PageUp, KeyModifiers::NONE), Action::PageUp),
+            ((KeyCode::PageDown, KeyModifiers::NONE), Action::PageDown),
+            ((KeyCode::Esc, KeyModifiers::NONE), Action::Cancel),
+            ((KeyCode::Char('d'), KeyModifiers::CONTROL), Action::Quit),
+            ((KeyCode::Tab, KeyModifiers::NONE), Action::Complete),
+        ];
+        KeyBindings { bindings: bindings.into_iter().collect() }
+    }
+}
+
+impl KeyBindings {
+    /// Returns the path of the key bindings file, `~/.config/rust_emulator/keybindings`.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rust_emulator").join("keybindings"))
+    }
+
+    /// Returns the default bindings changed by the file at `path`, if it exists, along with
+    /// a message for each line that could not be understood.
+    ///
+    /// Each line reads `<key> = <action>`, such as `ctrl+q = quit`, and the action `none`
+    /// removes a binding. Blank lines and `#` comments are skipped.
+    pub fn load(path: &Path) -> io::Result<(KeyBindings, Vec<String>)> {
+        let mut bindings = KeyBindings::default();
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((bindings, Vec::new())),
+            Err(e) => return Err(e),
+        };
+
+        let mut problems = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Err(e) = bindings.apply(line) {
+                problems.push(format!("{}:{}: {}", path.display(), number + 1, e));
+            }
+        }
+        Ok((bindings, problems))
+    }
+
+    /// Applies one `<key> = <action>` line.
+    fn apply(&mut self, line: &str) -> Result<(), String> {
+        let (key, action) = line.split_once('=').ok_or("expected '<key> = <action>'")?;
+        let key = parse_key(key.trim()).ok_or_else(|| format!("unknown key '{}'", key.trim()))?;
+        match action.trim() {
+            "none" => {
+                self.bindings.remove(&key);
+            }
+            name => {
+                let action = Action::named(name).ok_or_else(|| format!("unknown action '{}'", name))?;
+                self.bindings.insert(key, action);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the action bound to a key press, if any.
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        // Shift is part of the character itself, so `A` matches a binding for `a` with Shift
+        let modifiers = match key.code {
+            KeyCode::Char(_) => key.modifiers - KeyModifiers::SHIFT,
+            _ => key.modifiers,
+        };
+        self.bindings.get(&(key.code, modifiers)).copied()
+    }
+}
+
+/// Parses a key such as `esc`, `enter`, `f5`, or `ctrl+d`.
+fn parse_key(text: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let text = text.to_lowercase();
+    let mut parts: Vec<&str> = text.split('+').collect();
+    let name = parts.pop()?;
+    let mut modifiers = KeyModifiers::NONE;
+    for part in parts {
+        modifiers |= match part {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+    }
+
+    let code = match name {
+        "enter" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        "tab" => KeyCode::Tab,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "delete" => KeyCode::Delete,
+        "space" => KeyCode::Char(' '),
+        _ if name.len() > 1 && name.starts_with('f') => KeyCode::F(name[1..].parse().ok()?),
+        _ if name.chars().count() == 1 => KeyCode::Char(name.chars().next()?),
+        _ => return None,
+    };
+    Some((code, modifiers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn defaults_match_the_built_in_keys() {
+        let bindings = KeyBindings::default();
+        assert_eq!(bindings.action(&key(KeyCode::Esc, KeyModifiers::NONE)), Some(Action::Cancel));
+        assert_eq!(bindings.action(&key(KeyCode::Char('d'), KeyModifiers::CONTROL)), Some(Action::Quit));
+        assert_eq!(bindings.action(&key(KeyCode::Char('c'), KeyModifiers::CONTROL)), Some(Action::Cancel));
+        assert_eq!(bindings.action(&key(KeyCode::Char('c'), KeyModifiers::NONE)), None);
+    }
+
+    #[test]
+    fn lines_rebind_and_unbind_keys() {
+        let mut bindings = KeyBindings::default();
+        bindings.apply("esc = none").unwrap();
+        bindings.apply("Ctrl+Q = quit").unwrap();
+        assert_eq!(bindings.action(&key(KeyCode::Esc, KeyModifiers::NONE)), None);
+        assert_eq!(bindings.action(&key(KeyCode::Char('q'), KeyModifiers::CONTROL)), Some(Action::Quit));
+
+        assert_eq!(bindings.apply("hyper+x = quit"), Err("unknown key 'hyper+x'".to_string()));
+        assert_eq!(bindings.apply("f2 = explode"), Err("unknown action 'explode'".to_string()));
+    }
+}
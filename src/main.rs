@@ -1,277 +1,1001 @@
+mod calendar;
+mod commands;
+mod config;
+mod complete;
+mod expand;
+mod filesystem;
+mod find;
+mod follow;
+mod glob;
+mod grep;
+mod keys;
+mod math;
+mod options;
+mod pager;
+mod rc;
+mod redirect;
+mod render;
+mod replay;
+mod search;
+mod session;
+mod shell;
+mod text;
+mod theme;
+mod trash;
+mod tree;
+mod walk;
+mod watch;
+
+use commands::{session_stats, source_script};
 use crossterm::{
     cursor::MoveTo,
-    event::{self, Event, KeyCode},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event, KeyCode,
+        KeyEvent, KeyModifiers, MouseEventKind,
+    },
     execute, queue,
-    style::{Color, Print, Stylize},
-    terminal::{self, Clear, ClearType},
+    terminal,
+};
+use filesystem::MemoryFileSystem;
+use follow::Follow;
+use keys::{Action, KeyBindings};
+use options::{parse_args, Options};
+use pager::Pager;
+use render::{
+    display_width, draw_progress, highlight_matches, sanitize, status_bar, tabs_to_spaces, truncate_line, wrap_line,
+    Screen,
 };
+use search::{ScrollbackSearch, SearchOutcome};
+use session::{history_path, load_history, load_session, save_history, save_session, session_path, SessionLog};
+use redirect::{read_heredoc, Heredoc};
+use replay::{transcript_commands, Replay, ReplayStep};
+use serde_json::json;
+use shell::{strip_ansi, CommandOutput, ShellContext, DEFAULT_ALIASES};
+use std::any::Any;
 use std::collections::VecDeque;
 use std::env;
 use std::fs;
-use std::fs::File;
-use std::io::{stdout, Write};
+use std::io::{stderr, stdout, BufRead, IsTerminal, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use watch::DirWatch;
 
-fn main() -> crossterm::Result<()> {
-    // Set emulator's working directory to the home directory
-    if let Err(e) = set_to_home_directory() {
-        eprintln!("Failed to set home directory: {}", e);
-        return Ok(());
-    }
+/// The welcome line at the top of the screen, unless the configuration says otherwise.
+const BANNER: &str = "Welcome to the Unix Emulator";
 
-    let mut stdout = stdout();
+/// Rows reserved for the welcome banner above the output area.
+const HEADER_ROWS: usize = 2;
 
-    // Enter raw mode
-    terminal::enable_raw_mode()?;
-    execute!(stdout, terminal::EnterAlternateScreen)?;
+/// Number of lines moved per mouse-wheel notch.
+const SCROLL_STEP: usize = 3;
 
-    let mut command_buffer = String::new();
-    let mut output_lines: VecDeque<String> = VecDeque::new();
-    const MAX_OUTPUT_LINES: usize = 20;
+/// How long the main loop waits for input before checking for background output.
+const TICK_RATE: Duration = Duration::from_millis(50);
 
-    loop {
-        // Clear the screen
-        queue!(stdout, Clear(ClearType::All))?;
-
-        // Render the welcome message
-        queue!(
-            stdout,
-            MoveTo(0, 0),
-            Print("Welcome to the Unix Emulator".with(Color::Green)),
-            MoveTo(0, 1),
-            Print("------------------------------")
-        )?;
-
-        // Render Command Outputs
-        for (index, line) in output_lines.iter().enumerate() {
-            queue!(stdout, MoveTo(0, (index + 2) as u16), Print(line))?;
-        }
+/// How often `tail -f` looks for new lines when run with `-c`.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(250);
 
-        // Get the current working directory
-        let current_dir = env::current_dir()
-            .map(|path| path.display().to_string())
-            .unwrap_or_else(|_| "Unknown Directory".to_string());
+/// Prompt shown while the body of a here-document is being typed.
+const HEREDOC_PROMPT: &str = "heredoc> ";
 
-        // Position Input Prompt Below Last Output
-        let input_position = output_lines.len() as u16 + 2;
-        queue!(
-            stdout,
-            MoveTo(0, input_position),
-            Print(format!("> {} {}", current_dir, command_buffer).with(Color::Cyan))
-        )?;
+/// Prompt shown while a line ending in `\` or an open quote is being continued.
+const CONTINUATION_PROMPT: &str = "> ";
 
-        stdout.flush()?;
+/// Prompt shown while `bc` takes the lines typed.
+const CALCULATOR_PROMPT: &str = "bc> ";
 
-        // Handle input
-        if let Event::Key(key_event) = event::read()? {
-            match key_event.code {
-                KeyCode::Char(c) => {
-                    command_buffer.push(c);
-                }
-                KeyCode::Backspace => {
-                    command_buffer.pop();
-                }
-                KeyCode::Enter => {
-                    if !command_buffer.trim().is_empty() {
-                        let response = handle_command(&command_buffer, &mut output_lines);
-                        if output_lines.len() >= MAX_OUTPUT_LINES {
-                            output_lines.pop_front();
-                        }
-                        output_lines.push_back(format!("> {} {}", current_dir, command_buffer));
-                        output_lines.push_back(response);
-                        command_buffer.clear();
-                    }
-                }
-                KeyCode::Esc => {
-                    quit_terminal(&mut stdout)?;
-                    break;
-                }
-                _ => {}
+fn main() -> crossterm::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return Ok(());
+        }
+    };
+
+    let mut ctx = match &options.virtual_root {
+        // A virtual session works on an in-memory copy and never touches the real directory
+        Some(dir) => match MemoryFileSystem::from_directory(dir) {
+            Ok(fs) => ShellContext { options, fs: Arc::new(fs), ..Default::default() },
+            Err(e) => {
+                eprintln!("Failed to load virtual filesystem from '{}': {}", dir.display(), e);
+                return Ok(());
+            }
+        },
+        None => {
+            // Set emulator's working directory to the jail root, or the home directory
+            let start_dir = match &options.jail_root {
+                Some(root) => env::set_current_dir(root),
+                None => set_to_home_directory(),
+            };
+            if let Err(e) = start_dir {
+                eprintln!("Failed to set starting directory: {}", e);
+                return Ok(());
+            }
+            ShellContext { options, ..Default::default() }
+        }
+    };
+
+    ctx.rc_path = rc::rc_path();
+    ctx.aliases.extend(DEFAULT_ALIASES.iter().map(|(name, value)| (name.to_string(), value.to_string())));
+    ctx.aliases.extend(ctx.options.aliases.clone());
+    // Outside the interactive screen, progress rewrites one line of the terminal
+    if stderr().is_terminal() {
+        ctx.progress = Some(Box::new(|text| eprint!("\r{}\x1b[K", text)));
+    }
+    if let Some(path) = ctx.options.log.clone() {
+        match SessionLog::open(&path) {
+            Ok(log) => ctx.log = Some(log),
+            Err(e) => {
+                eprintln!("Failed to open log '{}': {}", path.display(), e);
+                return Ok(());
             }
         }
     }
 
-    Ok(())
-}
+    // `ls` fills the terminal's width with columns when the output is shown on one
+    if stdout().is_terminal() && !ctx.options.json {
+        ctx.terminal_width = terminal::size().ok().map(|(columns, _)| columns as usize);
+    }
 
-/// Handles the execution of commands entered by the user.
-fn handle_command(command: &str, output_lines: &mut VecDeque<String>) -> String {
-    let mut parts = command.split_whitespace();
-    let cmd = parts.next().unwrap_or("");
-
-    match cmd {
-        "ls" => list_directory(),
-        "pwd" => current_directory(),
-        "cat" => {
-            let file_name = parts.next().unwrap_or("");
-            read_file(file_name)
+    // `-c` runs a single command line and exits with its status
+    if let Some(line) = ctx.options.command.clone() {
+        let output = shell::execute(&line, &mut ctx);
+        print_output(&output, ctx.terminal_width.map(|_| ctx.tab_size()));
+        if let Some(follow) = ctx.follow.take() {
+            follow_on_stdout(follow, &ctx);
         }
-        "echo" => {
-            let args: Vec<&str> = parts.collect();
-            echo_command(args)
+        finish_session(&mut ctx);
+        std::process::exit(output.exit_code);
+    }
+
+    // Script mode runs the commands in a file and exits without entering the TUI
+    if let Some(path) = ctx.options.script.clone() {
+        match run_script(&path, &mut ctx) {
+            Ok(exit_code) => {
+                finish_session(&mut ctx);
+                std::process::exit(exit_code)
+            }
+            Err(e) => {
+                eprintln!("Error reading script '{}': {}", path.display(), e);
+                std::process::exit(1);
+            }
         }
-        "touch" => {
-            let file_name = parts.next().unwrap_or("");
-            let content = parts.collect::<Vec<&str>>().join(" ");
-            create_file(file_name, &content)
+    }
+
+    // JSON mode reads commands from standard input instead of running the TUI
+    if ctx.options.json {
+        if let Err(e) = run_json_mode(&mut ctx) {
+            eprintln!("Error: {}", e);
         }
-        "clear" => {
-            if let Err(e) = clear_screen(&mut stdout(), output_lines) {
-                format!("Error clearing screen: {}", e).with(Color::Red).to_string()
-            } else {
-                String::new() 
+        finish_session(&mut ctx);
+        return Ok(());
+    }
+
+    let replay = match ctx.options.replay.clone() {
+        Some(path) => match fs::read_to_string(&path) {
+            Ok(transcript) => Some(Replay::new(transcript_commands(&transcript), Instant::now())),
+            Err(e) => {
+                eprintln!("Failed to read replay transcript '{}': {}", path.display(), e);
+                return Ok(());
             }
+        },
+        None => None,
+    };
+
+    // Put the terminal back before a panic message is printed, so it can be read
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if thread::current().name() == Some("main") {
+            restore_terminal();
         }
-        "mkdir" => {
-            let dir_name = parts.next().unwrap_or("");
-            create_directory(dir_name)
-        }
-        "rm" => {
-            let file_name = parts.next().unwrap_or("");
-            delete_file(file_name)
+        default_hook(info);
+    }));
+
+    if ctx.options.restore {
+        restore_session(&mut ctx);
+    }
+    if !ctx.options.no_history {
+        load_history_file(&mut ctx);
+    }
+    run_rc_file(&mut ctx);
+    let key_bindings = load_key_bindings(&mut ctx);
+    let guard = TerminalGuard::enter()?;
+    let result = run_interactive(&mut ctx, &key_bindings, replay);
+    drop(guard);
+    // Save every session, so the next one can be started with `--restore`
+    if let Some(path) = session_path() {
+        if let Err(e) = save_session(&path, &ctx.output_lines) {
+            eprintln!("Failed to save session to '{}': {}", path.display(), e);
         }
-        "rmdir" => {
-            let dir_name = parts.next().unwrap_or("");
-            remove_directory(dir_name)
+    }
+    if let Some(path) = &ctx.history_path {
+        if let Err(e) = save_history(path, &ctx.history) {
+            eprintln!("Failed to save history to '{}': {}", path.display(), e);
         }
-        "cd" => {
-            let dir_name = parts.next().unwrap_or("");
-            change_directory(dir_name)
+    }
+    println!("Exiting Unix Emulator. Goodbye!");
+    finish_session(&mut ctx);
+    result
+}
+
+/// Prints the lines added to the file `tail -f` follows as they arrive, until the file
+/// can no longer be read or the user interrupts the emulator.
+fn follow_on_stdout(mut follow: Follow, ctx: &ShellContext) {
+    loop {
+        match follow.poll(ctx.fs.as_ref()) {
+            Ok(lines) => lines.iter().for_each(|line| println!("{}", line)),
+            Err(e) => {
+                eprintln!("Error: tail: {}: {}", follow.name, e);
+                std::process::exit(1);
+            }
         }
-        "exit" => {
-            quit_terminal(&mut stdout()).unwrap();
-            std::process::exit(0);
+        thread::sleep(FOLLOW_INTERVAL);
+    }
+}
+
+/// Writes out the rest of the `--log` transcript and, when the emulator was started with
+/// `--stats`, prints the session's command statistics to stderr.
+fn finish_session(ctx: &mut ShellContext) {
+    if let Some(log) = ctx.log.as_mut() {
+        if let Err(e) = log.flush() {
+            eprintln!("Failed to write log: {}", e);
         }
-        _ => format!("Unknown command: {}", cmd).with(Color::Red).to_string(),
+    }
+    if ctx.options.stats {
+        eprintln!("{}", session_stats(ctx));
     }
 }
 
-/// Clears the screen and resets the output buffer.
-fn clear_screen(stdout: &mut std::io::Stdout, output_lines: &mut VecDeque<String>) -> crossterm::Result<()> {
-    output_lines.clear();
-    execute!(
-        stdout,
-        Clear(ClearType::All), 
-        MoveTo(0, 0),             
-        Print("Screen Cleared".with(Color::Yellow)), 
-        Print("\n")               
-    )?;
-    stdout.flush()?;
-    Ok(())
+/// Keeps the terminal in raw mode on the alternate screen, restoring it when dropped,
+/// including while unwinding from a panic.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> crossterm::Result<TerminalGuard> {
+        enter_terminal()?;
+        Ok(TerminalGuard)
+    }
 }
 
-/// Lists the contents of the current directory.
-fn list_directory() -> String {
-    match fs::read_dir(".") {
-        Ok(entries) => {
-            let mut results: Vec<String> = entries
-                .filter_map(|entry| {
-                    entry.ok().map(|e| e.file_name().to_string_lossy().to_string())
-                })
-                .collect();
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
 
-            results.sort();
+/// Switches to raw mode and the alternate screen, with mouse capture for scrolling and
+/// bracketed paste so pasted text arrives as one event.
+fn enter_terminal() -> crossterm::Result<()> {
+    terminal::enable_raw_mode()?;
+    execute!(stdout(), terminal::EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)
+}
+
+/// Returns the terminal to its normal state. Errors are ignored, since this also runs
+/// while handling other failures.
+fn restore_terminal() {
+    let _ = execute!(stdout(), DisableBracketedPaste, DisableMouseCapture, terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+}
 
-            results.join("\n")
+/// Fills the scrollback with the output saved when the previous session ended.
+fn restore_session(ctx: &mut ShellContext) {
+    let Some(path) = session_path() else {
+        return;
+    };
+    match load_session(&path, ctx.max_output_lines()) {
+        Ok(lines) => ctx.output_lines = lines,
+        Err(e) => {
+            let message = format!("Error restoring session from '{}': {}", path.display(), e);
+            ctx.push_output(ctx.options.theme.error(message));
         }
-        Err(e) => format!("Error: {}", e).with(Color::Red).to_string(),
     }
 }
 
-/// Returns the current working directory.
-fn current_directory() -> String {
-    match env::current_dir() {
-        Ok(path) => path.display().to_string(),
-        Err(e) => format!("Error: {}", e).with(Color::Red).to_string(),
+/// Reads the history saved by earlier sessions, and notes the file so this session's history
+/// is saved there when the screen closes.
+fn load_history_file(ctx: &mut ShellContext) {
+    let Some(path) = history_path() else {
+        return;
+    };
+    match load_history(&path) {
+        Ok(history) => {
+            ctx.history = history;
+            ctx.history_path = Some(path);
+        }
+        // The file is left alone rather than replaced by this session's history
+        Err(e) => {
+            let message = format!("Error reading history from '{}': {}", path.display(), e);
+            ctx.push_output(ctx.options.theme.error(message));
+        }
     }
 }
 
-/// Reads the content of a file.
-fn read_file(file_name: &str) -> String {
-    if file_name.is_empty() {
-        return "Error: File name is required.".with(Color::Red).to_string();
-    }
-    match fs::read_to_string(file_name) {
-        Ok(content) => content,
-        Err(e) => format!("Error reading file '{}': {}", file_name, e).with(Color::Red).to_string(),
+/// Runs the commands in the user's startup file, if there is one, showing their output and
+/// errors in the scrollback. They are left out of the `--log` transcript.
+fn run_rc_file(ctx: &mut ShellContext) {
+    let Some(path) = ctx.rc_path.clone() else {
+        return;
+    };
+    let script = match fs::read_to_string(&path) {
+        Ok(script) => script,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+        Err(e) => {
+            let message = format!("Error reading startup file '{}': {}", path.display(), e);
+            ctx.push_output(ctx.options.theme.error(message));
+            return;
+        }
+    };
+    let log = ctx.log.take();
+    let output = source_script(&script, ".rustemurc", ctx);
+    ctx.log = log;
+    if !output.stdout.is_empty() || !output.stderr.is_empty() {
+        ctx.push_output(screen_text(&output, &ctx.options));
     }
 }
 
-/// Creates a new file and optionally writes content to it.
-fn create_file(file_name: &str, content: &str) -> String {
-    if file_name.is_empty() {
-        return "Error: File name is required.".with(Color::Red).to_string();
+/// Loads the user's key bindings, reporting any problems with the file in the scrollback.
+fn load_key_bindings(ctx: &mut ShellContext) -> KeyBindings {
+    let Some(path) = KeyBindings::default_path() else {
+        return KeyBindings::default();
+    };
+    match KeyBindings::load(&path) {
+        Ok((bindings, problems)) => {
+            for problem in problems {
+                ctx.push_output(ctx.options.theme.error(format!("Key bindings: {}", problem)));
+            }
+            bindings
+        }
+        Err(e) => {
+            let message = format!("Error reading key bindings '{}': {}", path.display(), e);
+            ctx.push_output(ctx.options.theme.error(message));
+            KeyBindings::default()
+        }
     }
+}
+
+/// Runs the interactive screen until the user quits with `exit` or the quit key (Ctrl+D
+/// unless rebound) on an empty line.
+///
+/// A `replay` types and runs its lines by itself while the input line is free, until it
+/// finishes or the cancel key (Ctrl+C unless rebound) stops it.
+fn run_interactive(
+    ctx: &mut ShellContext,
+    key_bindings: &KeyBindings,
+    mut replay: Option<Replay>,
+) -> crossterm::Result<()> {
+    let mut stdout = stdout();
+    let mut command_buffer = String::new();
+    // A command line waiting for the body of its here-document
+    let mut pending_heredoc: Option<(String, Heredoc)> = None;
+    // The start of a command line that continues onto the next line
+    let mut continuation: Option<String> = None;
+    // Pasted lines after the one in the input line, each loaded once the previous one runs
+    let mut pasted_lines: VecDeque<String> = VecDeque::new();
+    let (output_tx, output_rx) = mpsc::channel::<String>();
+    let mut needs_redraw = true;
+    // Number of lines the view is scrolled up from the newest output
+    let mut scroll_offset: usize = 0;
+    let mut visible_rows: usize = 1;
+    // The pager opened by `less`, which covers the screen until it is closed
+    let mut pager: Option<Pager> = None;
+    // What is on the terminal now, so each redraw only writes the rows that changed
+    let mut screen = Screen::default();
+    // A search of the scrollback opened with `/`, and the line it last moved to
+    let mut search: Option<ScrollbackSearch> = None;
+    let mut jump_to_line: Option<usize> = None;
+    // With `--watch`, the current directory is watched for changes to suggest another `ls`
+    let mut dir_watch: Option<DirWatch> = None;
+    let mut watching = ctx.options.watch;
+    let mut dir_changed = false;
+    ctx.refresh_current_dir();
+    ctx.interactive = true;
+    // Progress is drawn over the status bar, so the screen repaints once the command is done
+    let progress_drawn = Arc::new(AtomicBool::new(false));
+    let drawn = Arc::clone(&progress_drawn);
+    ctx.progress = Some(Box::new(move |text| {
+        let _ = draw_progress(&mut std::io::stdout(), text);
+        drawn.store(true, Ordering::Relaxed);
+    }));
+
+    loop {
+        // Collect output sent by background jobs
+        while let Ok(response) = output_rx.try_recv() {
+            ctx.push_output(response);
+            // A `cd` in a background job moves the whole process
+            ctx.refresh_current_dir();
+            needs_redraw = true;
+        }
+
+        // Show what has been added to the file `tail -f` follows
+        if let Some(follow) = ctx.follow.as_mut() {
+            match follow.poll(ctx.fs.as_ref()) {
+                Ok(lines) if lines.is_empty() => {}
+                Ok(lines) => {
+                    ctx.push_output(lines.join("\n"));
+                    needs_redraw = true;
+                }
+                Err(e) => {
+                    let message = ctx.options.theme.error(format!("Error: tail: {}: {}", follow.name, e));
+                    ctx.push_output(message);
+                    ctx.follow = None;
+                    needs_redraw = true;
+                }
+            }
+        }
+
+        // Move the watch along after a `cd`, and show when the watched directory has changed
+        if watching {
+            let dir = env::current_dir().ok();
+            if dir.is_some() && dir.as_deref() != dir_watch.as_ref().map(DirWatch::dir) {
+                dir_watch = None;
+                match dir.as_deref().map(DirWatch::new) {
+                    Some(Ok(watch)) => dir_watch = Some(watch),
+                    Some(Err(e)) => {
+                        let message = format!("Error: --watch: cannot watch '{}': {}", ctx.current_dir, e);
+                        ctx.push_output(ctx.options.theme.error(message));
+                        watching = false;
+                    }
+                    None => {}
+                }
+            }
+            let changed = dir_watch.as_ref().is_some_and(DirWatch::changed);
+            needs_redraw |= changed != dir_changed;
+            dir_changed = changed;
+        }
 
-    let sanitized_content = content.trim_matches('"');
+        if let (true, Some(pager)) = (needs_redraw, pager.as_mut()) {
+            // The last row stays reserved for the status bar
+            let (columns, rows) = terminal::size()?;
+            let (columns, rows) = (columns as usize, rows as usize);
+            let mut frame = pager.render((columns, rows.saturating_sub(1)), &ctx.options.theme);
+            let footer_row = frame.len() - 1;
+            let footer_width = display_width(&frame[footer_row]);
+            let mode = if pager.is_searching() { "SEARCH" } else { "PAGER" };
+            frame.push(status_bar(mode, ctx.last_exit_code, &ctx.options.theme, columns));
+            screen.draw(&mut stdout, &frame, (columns, rows))?;
+            queue!(stdout, MoveTo(footer_width as u16, footer_row as u16))?;
+            stdout.flush()?;
+            needs_redraw = false;
+        }
+
+        if needs_redraw {
+            // Lay out the whole screen, then write only the rows that changed
+            let (columns, rows) = terminal::size()?;
+            let banner = ctx.options.banner.as_deref().unwrap_or(BANNER);
+            let mut frame = vec![
+                truncate_line(&ctx.options.theme.header(banner), columns as usize),
+                truncate_line("------------------------------", columns as usize),
+            ];
+
+            // Lay out the prompt and the output as screen rows, wrapping by display width
+            let typed = match (&pending_heredoc, &continuation) {
+                _ if search.as_ref().is_some_and(|search| search.input().is_some()) => {
+                    format!("/{}", search.as_ref().and_then(ScrollbackSearch::input).unwrap_or_default())
+                }
+                _ if ctx.confirmation.is_some() => {
+                    format!("{}{}", ctx.confirmation.as_ref().map_or("", |asked| asked.question.as_str()), command_buffer)
+                }
+                _ if ctx.follow.is_some() => {
+                    let name = ctx.follow.as_ref().map_or("", |follow| follow.name.as_str());
+                    format!("[following {}: press q or Ctrl+C to stop]", name)
+                }
+                _ if ctx.calculator.is_some() => format!("{}{}", CALCULATOR_PROMPT, command_buffer),
+                (Some(_), _) => format!("{}{}", HEREDOC_PROMPT, command_buffer),
+                (None, Some(_)) => format!("{}{}", CONTINUATION_PROMPT, command_buffer),
+                (None, None) => format!("{}{}", ctx.prompt(), command_buffer),
+            };
+            let mut prompt = ctx.options.theme.prompt(&typed);
+            if !pasted_lines.is_empty() {
+                let waiting = format!("  [{} more pasted line(s)]", pasted_lines.len());
+                prompt.push_str(&ctx.options.theme.notice(waiting));
+            }
+            if dir_changed {
+                prompt.push_str(&ctx.options.theme.notice("  [directory changed: run ls to see it]"));
+            }
+            if let Some(replay) = &replay {
+                let waiting = format!("  [replay: {} more line(s)]", replay.remaining());
+                prompt.push_str(&ctx.options.theme.notice(waiting));
+            }
+            let prompt_rows = wrap_line(&prompt, columns as usize);
+            // Lines with a match for the search are shown plain, with the matches highlighted
+            let query = search.as_ref().map_or("", ScrollbackSearch::query);
+            let screen_rows: Vec<String> = ctx
+                .output_lines
+                .iter()
+                .flat_map(|line| {
+                    let plain = if query.is_empty() { None } else { Some(strip_ansi(line)) };
+                    match plain {
+                        Some(plain) if plain.contains(query) => wrap_line(&highlight_matches(&plain, query), columns as usize),
+                        _ => wrap_line(line, columns as usize),
+                    }
+                })
+                .collect();
+
+            // Render the visible window of the scrollback buffer
+            visible_rows = (rows as usize).saturating_sub(HEADER_ROWS + prompt_rows.len() + 1).max(1);
+            if let Some(line) = jump_to_line.take() {
+                // Scroll so the line with the match sits in the middle of the output area
+                let rows_below: usize =
+                    ctx.output_lines.iter().skip(line + 1).map(|line| wrap_line(line, columns as usize).len()).sum();
+                scroll_offset = rows_below.saturating_sub(visible_rows / 2);
+            }
+            scroll_offset = scroll_offset.min(screen_rows.len().saturating_sub(visible_rows));
+            let end = screen_rows.len() - scroll_offset;
+            let start = end.saturating_sub(visible_rows);
+            frame.extend_from_slice(&screen_rows[start..end]);
+
+            // Position Input Prompt Below Last Output, with the cursor after the typed text
+            let input_position = frame.len();
+            frame.extend(prompt_rows);
+            frame.resize((rows as usize).saturating_sub(1), String::new());
+            let mode = search.as_ref().map_or("INSERT".to_string(), |search| search.status(&ctx.output_lines));
+            frame.push(status_bar(&mode, ctx.last_exit_code, &ctx.options.theme, columns as usize));
+            screen.draw(&mut stdout, &frame, (columns as usize, rows as usize))?;
+
+            let typed_rows = wrap_line(&typed, columns as usize);
+            let cursor_row = input_position + typed_rows.len() - 1;
+            let cursor_column = display_width(typed_rows.last().map_or("", String::as_str));
+            queue!(stdout, MoveTo(cursor_column as u16, cursor_row as u16))?;
+
+            stdout.flush()?;
+            needs_redraw = false;
+        }
+
+        // A replayed line is typed into a free input line, then run as if Enter were pressed
+        let mut replay_run = false;
+        let input_free = pager.is_none()
+            && search.is_none()
+            && continuation.is_none()
+            && pending_heredoc.is_none()
+            && pasted_lines.is_empty()
+            && ctx.calculator.is_none()
+            && ctx.confirmation.is_none()
+            && ctx.follow.is_none();
+        let ready = |active: &&mut Replay| input_free && (command_buffer.is_empty() || active.has_typed());
+        if let Some(active) = replay.as_mut().filter(ready) {
+            match active.step(Instant::now()) {
+                ReplayStep::Wait => {}
+                ReplayStep::Type(line) => {
+                    command_buffer = line;
+                    needs_redraw = true;
+                }
+                ReplayStep::Run => replay_run = true,
+                ReplayStep::Finished => {
+                    replay = None;
+                    ctx.push_output(ctx.options.theme.notice("[replay finished]"));
+                    needs_redraw = true;
+                }
+            }
+        }
+
+        // Wait briefly for input so background output can be drawn between keystrokes
+        if !replay_run && !event::poll(TICK_RATE)? {
+            continue;
+        }
+        let event = if replay_run { Event::Key(KeyEvent::from(KeyCode::Enter)) } else { event::read()? };
+
+        // An open search takes keys first; a key it has no use for closes it and is then
+        // handled as usual
+        let event = match (search.as_mut(), event) {
+            (Some(open), Event::Key(key_event)) if pager.is_none() => {
+                needs_redraw = true;
+                match open.handle_key(key_event, &ctx.output_lines) {
+                    SearchOutcome::Searching => {
+                        jump_to_line = open.current();
+                        continue;
+                    }
+                    SearchOutcome::Done => {
+                        search = None;
+                        continue;
+                    }
+                    SearchOutcome::Unhandled => {
+                        search = None;
+                        Event::Key(key_event)
+                    }
+                }
+            }
+            (_, event) => event,
+        };
+
+        // Handle input
+        match event {
+            Event::Key(key_event) if pager.is_some() => {
+                needs_redraw = true;
+                if !pager.as_mut().is_some_and(|pager| pager.handle_key(key_event)) {
+                    pager = None;
+                }
+            }
+            // While `tail -f` follows a file, keys only stop it
+            Event::Key(key_event) if ctx.follow.is_some() => {
+                if key_event.code == KeyCode::Char('q') || key_bindings.action(&key_event) == Some(Action::Cancel) {
+                    ctx.follow = None;
+                    needs_redraw = true;
+                }
+            }
+            // Keys that change nothing, such as Enter on an empty line, leave the screen as is
+            Event::Key(key_event) => {
+                let action = if replay_run { Some(Action::Run) } else { key_bindings.action(&key_event) };
+                match action {
+                    Some(Action::Cancel) => {
+                        needs_redraw |= !command_buffer.is_empty()
+                            || continuation.is_some()
+                            || pending_heredoc.is_some()
+                            || !pasted_lines.is_empty()
+                            || ctx.calculator.is_some()
+                            || ctx.confirmation.is_some();
+                        if replay.take().is_some() {
+                            ctx.push_output(ctx.options.theme.notice("[replay stopped]"));
+                            needs_redraw = true;
+                        }
+                        command_buffer.clear();
+                        continuation = None;
+                        pending_heredoc = None;
+                        pasted_lines.clear();
+                        ctx.calculator = None;
+                        ctx.confirmation = None;
+                    }
+                    Some(Action::DeleteBack) => {
+                        needs_redraw |= command_buffer.pop().is_some();
+                    }
+                    Some(Action::Run) => {
+                        if pending_heredoc.is_some()
+                            || continuation.is_some()
+                            || !command_buffer.trim().is_empty()
+                            || !pasted_lines.is_empty()
+                            || ctx.confirmation.is_some()
+                        {
+                            needs_redraw = true;
+                            let line = std::mem::take(&mut command_buffer);
+                            scroll_offset = 0;
+                            if let Some(next) = pasted_lines.pop_front() {
+                                command_buffer = next;
+                            }
+                            if line.trim().is_empty()
+                                && pending_heredoc.is_none()
+                                && continuation.is_none()
+                                && ctx.confirmation.is_none()
+                            {
+                                continue;
+                            }
+
+                            // The line typed answers the question of a command waiting to be confirmed
+                            if let Some(asked) = ctx.confirmation.as_ref() {
+                                ctx.push_output(format!("{}{}", asked.question, line));
+                                let output = shell::answer_confirmation(&line, ctx);
+                                ctx.push_output(screen_text(&output, &ctx.options));
+                                ctx.last_exit_code = output.exit_code;
+                                continue;
+                            }
+
+                            // Lines typed after `bc` go to the calculator until `quit`
+                            if let Some(mut calculator) = ctx.calculator.take() {
+                                ctx.push_output(format!("{}{}", CALCULATOR_PROMPT, line));
+                                if line.trim() != "quit" {
+                                    let output = calculator.run_line(&line);
+                                    ctx.push_output(screen_text(&output, &ctx.options));
+                                    ctx.last_exit_code = output.exit_code;
+                                    ctx.calculator = Some(calculator);
+                                }
+                                continue;
+                            }
+
+                            let (command_line, heredoc) = match pending_heredoc.take() {
+                                // Lines typed after a `<<` command make up its here-document
+                                Some((command_line, mut heredoc)) => {
+                                    ctx.push_output(format!("{}{}", HEREDOC_PROMPT, line));
+                                    if !heredoc.push_line(&line) {
+                                        pending_heredoc = Some((command_line, heredoc));
+                                        continue;
+                                    }
+                                    (command_line, Some(heredoc.into_body()))
+                                }
+                                None => {
+                                    let prompt = match continuation {
+                                        Some(_) => CONTINUATION_PROMPT.to_string(),
+                                        None => ctx.prompt(),
+                                    };
+                                    ctx.push_output(format!("{}{}", prompt, line));
+
+                                    let line = continuation.take().unwrap_or_default() + &line;
+                                    if let Some(partial) = shell::continue_line(&line) {
+                                        continuation = Some(partial);
+                                        continue;
+                                    }
+                                    if let Some(heredoc) = Heredoc::from_command(&line) {
+                                        pending_heredoc = Some((line, heredoc));
+                                        continue;
+                                    }
+                                    (line, None)
+                                }
+                            };
 
-    match File::create(file_name) {
-        Ok(mut file) => {
-            if !sanitized_content.is_empty() {
-                if let Err(e) = writeln!(file, "{}", sanitized_content) {
-                    return format!("Error writing to file '{}': {}", file_name, e).with(Color::Red).to_string();
+                            // `!!`, `!N`, and `^old^new^` refer to earlier lines, shown expanded before they run
+                            let command_line = match shell::record_history(&command_line, ctx) {
+                                Ok((expanded, true)) => {
+                                    ctx.push_output(expanded.clone());
+                                    expanded
+                                }
+                                Ok((line, false)) => line,
+                                Err(e) => {
+                                    ctx.push_output(ctx.options.theme.error(e));
+                                    ctx.last_exit_code = 1;
+                                    continue;
+                                }
+                            };
+
+                            ctx.terminal_width = Some(terminal::size()?.0 as usize);
+                            // A command that panics reports an error instead of ending the session
+                            let output = panic::catch_unwind(AssertUnwindSafe(|| {
+                                run_command_line(&command_line, heredoc, ctx, &output_tx)
+                            }))
+                            .unwrap_or_else(|panic| {
+                                // The panic hook restored the terminal to show the message
+                                let _ = enter_terminal();
+                                screen.invalidate();
+                                let message = panic_message(&panic);
+                                CommandOutput::error(format!("Error: '{}' crashed: {}", command_line, message))
+                            });
+                            if progress_drawn.swap(false, Ordering::Relaxed) {
+                                screen.invalidate();
+                            }
+                            if let Some(watch) = dir_watch.as_ref().filter(|_| runs_ls(&command_line, ctx)) {
+                                watch.reset();
+                            }
+                            let paged_file = ctx.paged_file.take();
+                            let page_output = std::mem::take(&mut ctx.page_output);
+                            if let Some(file) = paged_file {
+                                // The pager reads the file itself; anything else goes to the scrollback
+                                pager = Some(Pager::open(file, ctx.options.raw, ctx.tab_size()));
+                                ctx.push_output(screen_text(&output, &ctx.options));
+                            } else if page_output && !output.stdout.is_empty() {
+                                // Only errors go to the scrollback; the output is read in the pager
+                                let text =
+                                    if ctx.options.raw { output.stdout.clone() } else { sanitize(&output.stdout) };
+                                pager = Some(Pager::new(&text, ctx.tab_size()));
+                                let errors = CommandOutput { stderr: output.stderr.clone(), ..Default::default() };
+                                ctx.push_output(screen_text(&errors, &ctx.options));
+                            } else {
+                                ctx.push_output(screen_text(&output, &ctx.options));
+                            }
+                            ctx.last_exit_code = output.exit_code;
+                            ctx.last_output = output.stdout;
+                            if ctx.exit_requested {
+                                break;
+                            }
+                        }
+                    }
+                    Some(Action::PageUp) => {
+                        scroll_offset += visible_rows;
+                        needs_redraw = true;
+                    }
+                    Some(Action::PageDown) => {
+                        needs_redraw |= scroll_offset > 0;
+                        scroll_offset = scroll_offset.saturating_sub(visible_rows);
+                    }
+                    // Like Ctrl+D in a shell, quitting only works on an empty line, so a slip
+                    // never throws away a half-typed command
+                    Some(Action::Quit)
+                        if command_buffer.is_empty()
+                            && continuation.is_none()
+                            && pending_heredoc.is_none()
+                            && pasted_lines.is_empty() =>
+                    {
+                        break
+                    }
+                    Some(Action::Quit) => {}
+                    // Only command lines complete, not here-document bodies or answers
+                    Some(Action::Complete)
+                        if pending_heredoc.is_none() && ctx.calculator.is_none() && ctx.confirmation.is_none() =>
+                    {
+                        let completion = complete::complete(&command_buffer, ctx);
+                        if !completion.choices.is_empty() {
+                            ctx.push_output(completion.choices.join("  "));
+                            scroll_offset = 0;
+                        }
+                        needs_redraw |= completion.line != command_buffer || !completion.choices.is_empty();
+                        command_buffer = completion.line;
+                    }
+                    Some(Action::Complete) => {}
+                    // `/` at an empty prompt searches the scrollback rather than typing
+                    None if key_event.code == KeyCode::Char('/')
+                        && command_buffer.is_empty()
+                        && continuation.is_none()
+                        && pending_heredoc.is_none()
+                        && pasted_lines.is_empty() =>
+                    {
+                        search = Some(ScrollbackSearch::new());
+                        needs_redraw = true;
+                    }
+                    // Unbound control keys type nothing, so a stray Ctrl+D is not a `d`
+                    None if key_event.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {}
+                    None => {
+                        if let KeyCode::Char(c) = key_event.code {
+                            command_buffer.push(c);
+                            needs_redraw = true;
+                        }
+                    }
                 }
             }
-            format!("File '{}' created.", file_name).with(Color::Green).to_string()
+            Event::Mouse(mouse_event) if pager.is_some() => {
+                let lines = match mouse_event.kind {
+                    MouseEventKind::ScrollUp => -(SCROLL_STEP as isize),
+                    MouseEventKind::ScrollDown => SCROLL_STEP as isize,
+                    _ => continue,
+                };
+                if let Some(pager) = pager.as_mut() {
+                    pager.scroll_by(lines);
+                }
+                needs_redraw = true;
+            }
+            Event::Mouse(mouse_event) => match mouse_event.kind {
+                MouseEventKind::ScrollUp => {
+                    scroll_offset += SCROLL_STEP;
+                    needs_redraw = true;
+                }
+                MouseEventKind::ScrollDown => {
+                    needs_redraw |= scroll_offset > 0;
+                    scroll_offset = scroll_offset.saturating_sub(SCROLL_STEP);
+                }
+                _ => {}
+            },
+            // Pasted text is never run straight away: its first line joins the input line and
+            // the rest wait their turn, so each line needs its own Enter
+            Event::Paste(text) if pager.is_none() && ctx.follow.is_none() => {
+                let text = text.replace("\r\n", "\n").replace('\r', "\n");
+                let mut lines = text.trim_end_matches('\n').split('\n');
+                command_buffer.push_str(lines.next().unwrap_or_default());
+                pasted_lines.extend(lines.map(str::to_string));
+                needs_redraw = true;
+            }
+            Event::Resize(_, _) => needs_redraw = true,
+            Event::Paste(_) | Event::FocusGained | Event::FocusLost => {}
         }
-        Err(e) => format!("Error creating file '{}': {}", file_name, e).with(Color::Red).to_string(),
     }
+
+    Ok(())
 }
 
-/// Creates a new directory.
-fn create_directory(dir_name: &str) -> String {
-    if dir_name.is_empty() {
-        return "Error: Directory name is required.".with(Color::Red).to_string();
-    }
-    match fs::create_dir(dir_name) {
-        Ok(_) => format!("Directory '{}' created.", dir_name).with(Color::Green).to_string(),
-        Err(e) => format!("Error creating directory '{}': {}", dir_name, e).with(Color::Red).to_string(),
+/// Runs each line of a script file through the pipeline, printing results to the real
+/// stdout and stderr. Blank lines and `#` comments are skipped.
+///
+/// Stops at the first failing command unless `--keep-going` is set and `set -e` is not, and
+/// returns the exit status of the last command run.
+fn run_script(path: &Path, ctx: &mut ShellContext) -> std::io::Result<i32> {
+    let script = fs::read_to_string(path)?;
+    let keep_going = ctx.options.keep_going;
+    let mut last_line = (0, String::new());
+    let tab_size = ctx.terminal_width.map(|_| ctx.tab_size());
+
+    let exit_code = shell::run_script(&script, keep_going, ctx, |number, line, output| {
+        print_output(output, tab_size);
+        last_line = (number, line.to_string());
+    });
+
+    let stopped = !keep_going || ctx.shell_options.errexit;
+    if exit_code != 0 && stopped && !ctx.exit_requested {
+        let (number, line) = last_line;
+        eprintln!("Script stopped at line {}: '{}' exited with status {}.", number, line, exit_code);
     }
+
+    Ok(exit_code)
 }
 
-/// Deletes a file.
-fn delete_file(file_name: &str) -> String {
-    if file_name.is_empty() {
-        return "Error: File name is required.".with(Color::Red).to_string();
+/// Prints a command's result to the real stdout and stderr, for the non-interactive modes.
+/// Tabs become spaces reaching every `tab_size`th column when it is given, for a terminal.
+fn print_output(output: &CommandOutput, tab_size: Option<usize>) {
+    let mut stdout = strip_ansi(&output.stdout);
+    if let Some(tab_size) = tab_size {
+        stdout = tabs_to_spaces(&stdout, tab_size);
     }
-    match fs::remove_file(file_name) {
-        Ok(_) => format!("File '{}' deleted.", file_name).with(Color::Green).to_string(),
-        Err(e) => format!("Error deleting file '{}': {}", file_name, e).with(Color::Red).to_string(),
+    if !stdout.is_empty() {
+        print!("{}", stdout);
+        if !stdout.ends_with('\n') {
+            println!();
+        }
+    }
+    if !output.stderr.is_empty() {
+        eprintln!("{}", output.stderr);
     }
 }
 
-/// Removes an empty directory.
-fn remove_directory(dir_name: &str) -> String {
-    if dir_name.is_empty() {
-        return "Error: Directory name is required.".with(Color::Red).to_string();
-    }
-    match fs::remove_dir(dir_name) {
-        Ok(_) => format!("Directory '{}' removed.", dir_name).with(Color::Green).to_string(),
-        Err(e) => format!("Error removing directory '{}': {}", dir_name, e).with(Color::Red).to_string(),
+/// Runs commands read from standard input, printing each result as a JSON object with
+/// `command`, `exit_code`, `stdout`, and `stderr` fields.
+fn run_json_mode(ctx: &mut ShellContext) -> std::io::Result<()> {
+    let mut stdout = stdout();
+    let input = std::io::stdin().lock().lines().collect::<std::io::Result<Vec<String>>>()?;
+    let mut lines = input.iter();
+
+    while let Some(line) = lines.next() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let heredoc = read_heredoc(line, lines.by_ref().map(String::as_str));
+        let output = shell::execute_with_heredoc(line, heredoc.as_deref(), ctx);
+        let record = json!({
+            "command": line,
+            "exit_code": output.exit_code,
+            "stdout": strip_ansi(&output.stdout),
+            "stderr": output.stderr,
+        });
+        writeln!(stdout, "{}", record)?;
+
+        if ctx.exit_requested {
+            break;
+        }
     }
+
+    Ok(())
 }
 
-/// Changes the current directory.
-fn change_directory(dir_name: &str) -> String {
-    if dir_name.is_empty() {
-        return "Error: Directory name is required.".with(Color::Red).to_string();
-    }
-    match env::set_current_dir(dir_name) {
-        Ok(_) => format!("Changed directory to '{}'.", dir_name).with(Color::Green).to_string(),
-        Err(e) => format!("Error changing directory to '{}': {}", dir_name, e).with(Color::Red).to_string(),
+/// Returns true if a command in `line` is `ls`, or an alias for it, so the current
+/// directory has just been listed.
+fn runs_ls(line: &str, ctx: &ShellContext) -> bool {
+    line.split(['|', ';', '&']).filter_map(|command| command.split_whitespace().next()).any(|name| {
+        let name = ctx.aliases.get(name).and_then(|value| value.split_whitespace().next()).unwrap_or(name);
+        name == "ls"
+    })
+}
+
+/// Returns a command's output as the scrollback shows it: colored, and with other control
+/// characters escaped unless the emulator was started with `--raw`.
+fn screen_text(output: &CommandOutput, options: &Options) -> String {
+    let text = output.display(&options.theme);
+    if options.raw {
+        text
+    } else {
+        sanitize(&text)
     }
 }
 
-/// Handles the `echo` command to display user-provided text.
-fn echo_command(args: Vec<&str>) -> String {
-    args.join(" ") // Join all arguments with a space
+/// Runs a command line, moving it to a background thread when it ends with `&`.
+///
+/// `heredoc` is the body of the line's here-document, if it has one. Background jobs
+/// report their output through `output_tx` once they finish.
+fn run_command_line(
+    line: &str,
+    heredoc: Option<String>,
+    ctx: &mut ShellContext,
+    output_tx: &Sender<String>,
+) -> CommandOutput {
+    match line.trim_end().strip_suffix('&') {
+        Some(job) if !job.trim().is_empty() && !job.ends_with('&') => {
+            let job = job.trim().to_string();
+            let output_tx = output_tx.clone();
+            let theme = ctx.options.theme;
+            let options = ctx.options.clone();
+            let message = theme.notice(format!("[background] {}", job));
+            let mut background_ctx = ctx.for_background();
+
+            thread::spawn(move || {
+                let output = panic::catch_unwind(AssertUnwindSafe(|| {
+                    shell::execute_with_heredoc(&job, heredoc.as_deref(), &mut background_ctx)
+                }))
+                .unwrap_or_else(|panic| CommandOutput::error(format!("Error: '{}' crashed: {}", job, panic_message(&panic))));
+                let _ = output_tx.send(theme.notice(format!("[done] {}", job)));
+                let _ = output_tx.send(screen_text(&output, &options));
+            });
+
+            CommandOutput::success(message)
+        }
+        _ => shell::execute_with_heredoc(line, heredoc.as_deref(), ctx),
+    }
 }
 
-/// Quits the terminal emulator and restores the terminal to its normal state.
-fn quit_terminal(stdout: &mut std::io::Stdout) -> crossterm::Result<()> {
-    execute!(stdout, terminal::LeaveAlternateScreen)?;
-    terminal::disable_raw_mode()?; // Exit raw mode
-    println!("Exiting Unix Emulator. Goodbye!");
-    Ok(())
+/// Returns the message a panic was raised with.
+fn panic_message(panic: &Box<dyn Any + Send>) -> String {
+    match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "unknown panic".to_string(),
+    }
 }
 
 /// Sets the emulator's working directory to the home directory.
@@ -0,0 +1,492 @@
+//! Arithmetic commands: `factor`, `expr`, and the `bc` calculator.
+
+use crate::shell::{CommandOutput, ShellContext};
+use std::collections::BTreeMap;
+
+/// Decimal places `bc` shows until `scale` is set.
+const DEFAULT_SCALE: usize = 10;
+
+/// The most decimal places `bc` shows; a 64-bit float holds no more digits than this.
+const MAX_SCALE: usize = 15;
+
+/// Divisors `prime_factors` tries one by one before splitting what is left with Pollard's rho.
+const TRIAL_DIVISION_LIMIT: u64 = 1000;
+
+/// Returns the prime factors of `n` in increasing order, repeated as often as they divide
+/// it. 0 and 1 have none.
+///
+/// Small factors are found by trial division and larger ones by Pollard's rho, so even a
+/// 64-bit prime takes well under a millisecond.
+pub fn prime_factors(mut n: u64) -> Vec<u64> {
+    let mut factors = Vec::new();
+    if n < 2 {
+        return factors;
+    }
+    let mut divisor = 2;
+    while divisor < TRIAL_DIVISION_LIMIT && divisor <= n / divisor {
+        while n.is_multiple_of(divisor) {
+            factors.push(divisor);
+            n /= divisor;
+        }
+        divisor += if divisor == 2 { 1 } else { 2 };
+    }
+    let mut remaining = vec![n];
+    while let Some(n) = remaining.pop() {
+        if n == 1 {
+            continue;
+        }
+        if is_prime(n) {
+            factors.push(n);
+        } else {
+            let divisor = find_divisor(n);
+            remaining.extend([divisor, n / divisor]);
+        }
+    }
+    factors.sort_unstable();
+    factors
+}
+
+/// Returns `a * b % n` without overflowing.
+fn mul_mod(a: u64, b: u64, n: u64) -> u64 {
+    (a as u128 * b as u128 % n as u128) as u64
+}
+
+/// Returns `base` to the power `exponent`, modulo `n`.
+fn pow_mod(mut base: u64, mut exponent: u64, n: u64) -> u64 {
+    let mut result = 1 % n;
+    base %= n;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_mod(result, base, n);
+        }
+        base = mul_mod(base, base, n);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// Tells whether `n` is prime with the Miller-Rabin test. Checking the first twelve primes
+/// as witnesses makes the answer exact for every 64-bit number.
+fn is_prime(n: u64) -> bool {
+    const WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if n < 2 {
+        return false;
+    }
+    if let Some(&p) = WITNESSES.iter().find(|&&p| n.is_multiple_of(p)) {
+        return n == p;
+    }
+    let shift = (n - 1).trailing_zeros();
+    let odd = (n - 1) >> shift;
+    WITNESSES.iter().all(|&witness| {
+        let mut x = pow_mod(witness, odd, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..shift {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// Returns a divisor of the odd composite `n` other than 1 and `n`, with Pollard's rho.
+fn find_divisor(n: u64) -> u64 {
+    let gcd = |mut a: u64, mut b: u64| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    (1..)
+        .find_map(|increment| {
+            let step = |x: u64| ((x as u128 * x as u128 + increment as u128) % n as u128) as u64;
+            let (mut slow, mut fast) = (2, 2);
+            loop {
+                slow = step(slow);
+                fast = step(step(fast));
+                match gcd(slow.abs_diff(fast), n) {
+                    1 => continue,
+                    divisor if divisor == n => return None,
+                    divisor => return Some(divisor),
+                }
+            }
+        })
+        .unwrap_or(n)
+}
+
+/// Handles `factor`: prints each number given, or each word of piped input, followed by its
+/// prime factors, as in `12: 2 2 3`.
+pub fn factor_command(args: Vec<&str>, stdin: Option<&str>) -> CommandOutput {
+    let words: Vec<&str> = if args.is_empty() { stdin.unwrap_or_default().split_whitespace().collect() } else { args };
+    if words.is_empty() {
+        return CommandOutput::error("Error: Usage: factor <number>...");
+    }
+
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+    for word in words {
+        match word.parse::<u64>() {
+            Ok(n) => {
+                let factors: Vec<String> = prime_factors(n).iter().map(u64::to_string).collect();
+                lines.push(format!("{}: {}", n, factors.join(" ")).trim_end().to_string());
+            }
+            Err(_) => errors.push(format!("Error: factor: '{}' is not a valid positive integer.", word)),
+        }
+    }
+    let exit_code = if errors.is_empty() { 0 } else { 1 };
+    CommandOutput { stdout: lines.join("\n"), stderr: errors.join("\n"), exit_code }
+}
+
+/// A piece of an arithmetic expression, with numbers of type `N`. Negation is the
+/// operator `~`, so it cannot be confused with subtraction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token<N> {
+    Number(N),
+    Operator(char),
+    Open,
+    Close,
+}
+
+/// Returns how tightly an operator binds: negation first, then `^`, then `*`, `/`, and
+/// `%`, and `+` and `-` last.
+fn precedence(operator: char) -> u8 {
+    match operator {
+        '~' => 4,
+        '^' => 3,
+        '*' | '/' | '%' => 2,
+        _ => 1,
+    }
+}
+
+/// Turns `expr` arguments into tokens. Each number, operator, and parenthesis must be a
+/// separate argument, as with the real `expr`.
+fn tokenize(args: &[&str]) -> Result<Vec<Token<i64>>, String> {
+    args.iter()
+        .map(|arg| match *arg {
+            "(" => Ok(Token::Open),
+            ")" => Ok(Token::Close),
+            "+" | "-" | "*" | "/" | "%" => Ok(Token::Operator(arg.chars().next().unwrap_or_default())),
+            _ => arg.parse().map(Token::Number).map_err(|_| format!("Error: expr: non-integer argument '{}'.", arg)),
+        })
+        .collect()
+}
+
+/// Reorders infix tokens into postfix order with the shunting-yard algorithm, so that
+/// operators come after their operands and precedence and parentheses are already applied.
+/// Returns `None` if the tokens do not form an expression.
+fn to_postfix<N: Copy + PartialEq>(tokens: &[Token<N>]) -> Option<Vec<Token<N>>> {
+    let mut output = Vec::new();
+    let mut operators: Vec<Token<N>> = Vec::new();
+    // Whether the next token should be a number, `(`, or negation, rather than an operator or `)`
+    let mut expect_operand = true;
+    for &token in tokens {
+        match token {
+            Token::Number(_) if expect_operand => {
+                output.push(token);
+                expect_operand = false;
+            }
+            Token::Open | Token::Operator('~') if expect_operand => operators.push(token),
+            Token::Operator(operator) if !expect_operand && operator != '~' => {
+                // Operators of the same precedence group from the left, except `^`, which
+                // groups from the right as in `2 ^ 3 ^ 2`
+                while let Some(&Token::Operator(top)) = operators.last() {
+                    if precedence(top) < precedence(operator) || (operator == '^' && top == '^') {
+                        break;
+                    }
+                    output.extend(operators.pop());
+                }
+                operators.push(token);
+                expect_operand = true;
+            }
+            Token::Close if !expect_operand => loop {
+                match operators.pop()? {
+                    Token::Open => break,
+                    operator => output.push(operator),
+                }
+            },
+            _ => return None,
+        }
+    }
+    if expect_operand {
+        return None;
+    }
+    while let Some(operator) = operators.pop() {
+        if operator == Token::Open {
+            return None;
+        }
+        output.push(operator);
+    }
+    Some(output)
+}
+
+/// Evaluates integer arithmetic given as separate arguments, such as `3 + 4 '*' 2`, with
+/// `*`, `/`, and `%` binding tighter than `+` and `-` and parentheses grouping. Division
+/// rounds toward zero.
+pub fn evaluate(args: &[&str]) -> Result<i64, String> {
+    let postfix = to_postfix(&tokenize(args)?).ok_or("Error: expr: syntax error.")?;
+    let mut stack = Vec::new();
+    for token in postfix {
+        let operator = match token {
+            Token::Number(n) => {
+                stack.push(n);
+                continue;
+            }
+            Token::Operator(operator) => operator,
+            // Parentheses never reach the postfix order
+            Token::Open | Token::Close => continue,
+        };
+        // The shunting-yard pass has checked that every operator has its operands
+        let (Some(right), Some(left)) = (stack.pop(), stack.pop()) else {
+            return Err("Error: expr: syntax error.".to_string());
+        };
+        let result = match operator {
+            '/' | '%' if right == 0 => return Err("Error: expr: division by zero.".to_string()),
+            '+' => left.checked_add(right),
+            '-' => left.checked_sub(right),
+            '*' => left.checked_mul(right),
+            '/' => left.checked_div(right),
+            _ => left.checked_rem(right),
+        };
+        stack.push(result.ok_or("Error: expr: result out of range.")?);
+    }
+    stack.pop().ok_or_else(|| "Error: expr: syntax error.".to_string())
+}
+
+/// Handles `expr`: prints the value of an arithmetic expression. A single argument that is
+/// not a number is printed as it is. Like POSIX `expr`, exits with status 1 when the result
+/// is 0 or empty, and 2 when the expression is invalid.
+pub fn expr_command(args: Vec<&str>) -> CommandOutput {
+    let result = match args[..] {
+        [] => return CommandOutput { stderr: "Error: Usage: expr <expression>".to_string(), exit_code: 2, ..Default::default() },
+        [word] if word.parse::<i64>().is_err() && !["(", ")", "+", "-", "*", "/", "%"].contains(&word) => word.to_string(),
+        _ => match evaluate(&args) {
+            Ok(value) => value.to_string(),
+            Err(e) => return CommandOutput { stderr: e, exit_code: 2, ..Default::default() },
+        },
+    };
+    let exit_code = if result.is_empty() || result == "0" { 1 } else { 0 };
+    CommandOutput { stdout: result, exit_code, ..Default::default() }
+}
+
+/// The state of a `bc` session: the variables assigned so far, including `scale`, the
+/// number of decimal places results are shown with.
+#[derive(Default)]
+pub struct Calculator {
+    variables: BTreeMap<String, f64>,
+}
+
+impl Calculator {
+    /// Runs a line of `bc` statements separated by `;`. An assignment such as `x = 2 ^ 8`
+    /// prints nothing, and any other expression prints its value. A statement that fails is
+    /// reported and the rest still run.
+    pub fn run_line(&mut self, line: &str) -> CommandOutput {
+        let mut values = Vec::new();
+        let mut errors = Vec::new();
+        for statement in line.split(';').map(str::trim).filter(|statement| !statement.is_empty()) {
+            let assignment = statement
+                .split_once('=')
+                .filter(|(name, _)| is_variable_name(name.trim()))
+                .map(|(name, expression)| (name.trim(), expression));
+            let expression = assignment.map_or(statement, |(_, expression)| expression);
+            match self.evaluate(expression) {
+                Ok(value) => match assignment {
+                    Some((name, _)) => {
+                        self.variables.insert(name.to_string(), value);
+                    }
+                    None => values.push(self.format(value)),
+                },
+                Err(e) => errors.push(e),
+            }
+        }
+        let exit_code = if errors.is_empty() { 0 } else { 1 };
+        CommandOutput { stdout: values.join("\n"), stderr: errors.join("\n"), exit_code }
+    }
+
+    /// Evaluates a floating-point expression with `+`, `-`, `*`, `/`, `%`, `^`, negation,
+    /// parentheses, and variables, which are 0 until assigned.
+    fn evaluate(&self, expression: &str) -> Result<f64, String> {
+        let tokens = self.tokenize(expression)?;
+        let postfix = to_postfix(&tokens).ok_or("Error: bc: syntax error.")?;
+        let mut stack: Vec<f64> = Vec::new();
+        for token in postfix {
+            let value = match token {
+                Token::Number(n) => n,
+                Token::Operator('~') => -stack.pop().unwrap_or_default(),
+                Token::Operator(operator) => {
+                    let (right, left) = (stack.pop().unwrap_or_default(), stack.pop().unwrap_or_default());
+                    match operator {
+                        '/' | '%' if right == 0.0 => return Err("Error: bc: division by zero.".to_string()),
+                        '+' => left + right,
+                        '-' => left - right,
+                        '*' => left * right,
+                        '/' => left / right,
+                        '%' => left % right,
+                        _ => left.powf(right),
+                    }
+                }
+                Token::Open | Token::Close => continue,
+            };
+            if !value.is_finite() {
+                return Err("Error: bc: result out of range.".to_string());
+            }
+            stack.push(value);
+        }
+        stack.pop().ok_or_else(|| "Error: bc: syntax error.".to_string())
+    }
+
+    /// Splits an expression into tokens, replacing each variable with its value. A `-`
+    /// where an operand belongs is negation.
+    fn tokenize(&self, expression: &str) -> Result<Vec<Token<f64>>, String> {
+        let chars: Vec<char> = expression.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let (start, c) = (i, chars[i]);
+            i += 1;
+            let expects_operand = matches!(tokens.last(), None | Some(Token::Operator(_) | Token::Open));
+            let token = match c {
+                c if c.is_whitespace() => continue,
+                '0'..='9' | '.' => {
+                    while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                        i += 1;
+                    }
+                    let number: String = chars[start..i].iter().collect();
+                    Token::Number(number.parse().map_err(|_| format!("Error: bc: invalid number '{}'.", number))?)
+                }
+                c if c.is_ascii_alphabetic() || c == '_' => {
+                    while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                        i += 1;
+                    }
+                    let name: String = chars[start..i].iter().collect();
+                    Token::Number(self.variable(&name))
+                }
+                '-' if expects_operand => Token::Operator('~'),
+                '+' | '-' | '*' | '/' | '%' | '^' => Token::Operator(c),
+                '(' => Token::Open,
+                ')' => Token::Close,
+                _ => return Err(format!("Error: bc: unexpected character '{}'.", c)),
+            };
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+
+    /// Returns the value of a variable, 0 if it was never assigned. `scale` starts at 10.
+    fn variable(&self, name: &str) -> f64 {
+        match self.variables.get(name) {
+            Some(&value) => value,
+            None if name == "scale" => DEFAULT_SCALE as f64,
+            None => 0.0,
+        }
+    }
+
+    /// Shows a value rounded to `scale` decimal places, without trailing zeros.
+    fn format(&self, value: f64) -> String {
+        let scale = (self.variable("scale").max(0.0) as usize).min(MAX_SCALE);
+        let text = format!("{:.*}", scale, value);
+        let text = if text.contains('.') { text.trim_end_matches('0').trim_end_matches('.') } else { &text };
+        // Rounding can leave a negative zero
+        if text == "-0" {
+            "0".to_string()
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Returns true if `name` can be a `bc` variable: letters, digits, and `_`, not starting
+/// with a digit.
+fn is_variable_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Handles `bc`: evaluates each line of piped input, stopping at `quit`. Without input, the
+/// interactive screen hands the lines typed after it to a calculator session until `quit`.
+pub fn bc_command(args: Vec<&str>, stdin: Option<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    if let Some(arg) = args.first() {
+        return CommandOutput::error(format!("Error: bc: unexpected argument '{}'; pipe expressions in instead.", arg));
+    }
+    let mut calculator = Calculator::default();
+    let Some(input) = stdin else {
+        if !ctx.interactive {
+            return CommandOutput::error("Error: bc: no input; pipe expressions in, e.g. echo 2^10 | bc.");
+        }
+        ctx.calculator = Some(calculator);
+        return CommandOutput::success("bc: type expressions to evaluate, and quit to leave.");
+    };
+
+    let mut outputs = Vec::new();
+    for line in input.lines().take_while(|line| line.trim() != "quit") {
+        outputs.push(calculator.run_line(line));
+    }
+    let mut combined = CommandOutput::default();
+    for output in outputs {
+        if !output.stdout.is_empty() {
+            combined.stdout.push_str(&output.stdout);
+            combined.stdout.push('\n');
+        }
+        if !output.stderr.is_empty() {
+            combined.stderr.push_str(&output.stderr);
+            combined.stderr.push('\n');
+        }
+        combined.exit_code = combined.exit_code.max(output.exit_code);
+    }
+    combined.stderr.truncate(combined.stderr.trim_end().len());
+    combined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_split_into_prime_factors() {
+        assert_eq!(prime_factors(12), [2, 2, 3]);
+        assert_eq!(prime_factors(97), [97]);
+        assert_eq!(prime_factors(1), [] as [u64; 0]);
+        assert_eq!(prime_factors(600_851_475_143), [71, 839, 1471, 6857]);
+        assert_eq!(prime_factors(u64::MAX), [3, 5, 17, 257, 641, 65537, 6_700_417]);
+        assert_eq!(prime_factors(18_446_744_073_709_551_557), [18_446_744_073_709_551_557]);
+        assert_eq!(prime_factors(4_294_967_291 * 4_294_967_279), [4_294_967_279, 4_294_967_291]);
+        assert_eq!(prime_factors(1_000_003 * 1_000_003 * 1009), [1009, 1_000_003, 1_000_003]);
+    }
+
+    #[test]
+    fn expressions_respect_precedence_and_parentheses() {
+        assert_eq!(evaluate(&["3", "+", "4", "*", "2"]), Ok(11));
+        assert_eq!(evaluate(&["(", "3", "+", "4", ")", "*", "2"]), Ok(14));
+        assert_eq!(evaluate(&["10", "-", "4", "-", "3"]), Ok(3));
+        assert_eq!(evaluate(&["-7", "/", "2"]), Ok(-3));
+        assert_eq!(evaluate(&["17", "%", "5", "*", "2"]), Ok(4));
+        assert!(evaluate(&["1", "/", "0"]).unwrap_err().contains("division by zero"));
+        assert!(evaluate(&["1", "+"]).unwrap_err().contains("syntax error"));
+        assert!(evaluate(&["(", "1"]).unwrap_err().contains("syntax error"));
+        assert!(evaluate(&["1", "+", "x"]).unwrap_err().contains("non-integer"));
+    }
+
+    #[test]
+    fn calculator_keeps_variables_and_rounds_to_scale() {
+        let mut calculator = Calculator::default();
+        let mut run = |line: &str| {
+            let output = calculator.run_line(line);
+            if output.stderr.is_empty() {
+                output.stdout
+            } else {
+                output.stderr
+            }
+        };
+        assert_eq!(run("1 / 3"), "0.3333333333");
+        assert_eq!(run("2 ^ 3 ^ 2; -2 ^ 2; 2 * -3"), "512\n4\n-6");
+        assert_eq!(run("r = 2.5; pi = 3.14159; pi * r ^ 2"), "19.6349375");
+        assert_eq!(run("scale = 2; (1 + 2) / 7"), "0.43");
+        assert_eq!(run("0.1 + 0.2"), "0.3");
+        assert_eq!(run("undefined + 1"), "1");
+        assert_eq!(run("1 / (2 - 2)"), "Error: bc: division by zero.");
+        assert_eq!(run("2 +"), "Error: bc: syntax error.");
+        assert_eq!(run("3 $ 4"), "Error: bc: unexpected character '$'.");
+    }
+}
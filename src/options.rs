@@ -0,0 +1,214 @@
+//! Command-line flags accepted when the emulator starts.
+
+use crate::config::{load_config, read_config, profile_path};
+use crate::theme::Theme;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Settings chosen with command-line flags when the emulator starts.
+#[derive(Clone, Default)]
+pub struct Options {
+    /// Directory that every command is confined to (`--jail <dir>`).
+    pub jail_root: Option<PathBuf>,
+    /// Whether commands that modify the filesystem are disabled (`--read-only`).
+    pub read_only: bool,
+    /// Whether to print each command's result as JSON instead of running the TUI (`--json`).
+    pub json: bool,
+    /// File of commands to run non-interactively (`--script <file>`).
+    pub script: Option<PathBuf>,
+    /// Whether a script continues past failing commands (`--keep-going`).
+    pub keep_going: bool,
+    /// Command line to run once before exiting (`-c <command>`).
+    pub command: Option<String>,
+    /// Directory loaded into an in-memory filesystem that commands work on instead (`--virtual <dir>`).
+    pub virtual_root: Option<PathBuf>,
+    /// Whether unknown commands run as programs from the real `PATH` (`--allow-external`).
+    pub allow_external: bool,
+    /// Whether path arguments that are absolute or contain `..` are rejected (`--safe`).
+    pub safe: bool,
+    /// Colors used on screen (`--theme <name>`).
+    pub theme: Theme,
+    /// Whether the interactive screen starts with the output saved by the previous session
+    /// (`--restore`) instead of empty (`--clear`, the default).
+    pub restore: bool,
+    /// Whether `cat` shows CRLF line endings as plain newlines unless given `--crlf`
+    /// (`--normalize-crlf`).
+    pub normalize_crlf: bool,
+    /// Whether command output reaches the screen with its control characters untouched
+    /// (`--raw`) instead of shown as `^[` and the like.
+    pub raw: bool,
+    /// Whether the session's command statistics are printed when the emulator exits
+    /// (`--stats`).
+    pub stats: bool,
+    /// File that each command line and its output are appended to (`--log <file>`).
+    pub log: Option<PathBuf>,
+    /// Transcript written with `--log` whose command lines the interactive screen runs
+    /// again, one at a time (`--replay <file>`).
+    pub replay: Option<PathBuf>,
+    /// How many files patterns may match before `rm` and `rmdir` ask for confirmation, or
+    /// 0 never to ask (`--glob-confirm <count>`). The shell's default applies when unset.
+    pub glob_confirm: Option<usize>,
+    /// How many lines of output a command may show on a terminal before the rest is cut
+    /// off, or 0 for no limit (`--output-limit <lines>`). The shell's default applies when
+    /// unset.
+    pub output_limit: Option<usize>,
+    /// Whether the interactive screen watches the current directory and points out when
+    /// its contents change (`--watch`).
+    pub watch: bool,
+    /// Name of the configuration in `~/.config/rust_emulator/` the settings below come
+    /// from (`--profile <name>`).
+    pub profile: Option<String>,
+    /// The prompt before the input line, with `{cwd}` standing for the current directory.
+    /// The shell's default applies when unset.
+    pub prompt: Option<String>,
+    /// How many lines of output the scrollback keeps. The shell's default applies when unset.
+    pub max_output_lines: Option<usize>,
+    /// Aliases the configuration defines, added before the startup file runs.
+    pub aliases: BTreeMap<String, String>,
+    /// The welcome line at the top of the interactive screen. The default applies when unset.
+    pub banner: Option<String>,
+    /// Distance between the tab stops that tabs in output are shown reaching, and that
+    /// `expand` uses by default (`--tab-size <n>`). The shell's default applies when unset.
+    pub tab_size: Option<usize>,
+    /// Which lines the history leaves out, unless the `HISTCONTROL` variable is set.
+    pub history_control: Option<String>,
+    /// Whether the history is kept only for the session, never read from or written to the
+    /// history file (`--no-history`).
+    pub no_history: bool,
+}
+
+/// Parses the emulator's command-line flags, with the settings of `config.toml` and the
+/// chosen profile in place of flags that were not given.
+///
+/// Color is also turned off when the `NO_COLOR` environment variable is set and not empty.
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let mut theme = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--jail" => {
+                let dir = args.next().ok_or("--jail requires a directory")?;
+                let root = fs::canonicalize(dir).map_err(|e| format!("Invalid jail directory '{}': {}", dir, e))?;
+                if !root.is_dir() {
+                    return Err(format!("Jail '{}' is not a directory", dir));
+                }
+                options.jail_root = Some(root);
+            }
+            "--read-only" => options.read_only = true,
+            "--json" => options.json = true,
+            "--script" => {
+                let path = args.next().ok_or("--script requires a file")?;
+                // Resolve now, before the emulator moves to its starting directory
+                let path = std::path::absolute(path).map_err(|e| format!("Invalid script path '{}': {}", path, e))?;
+                options.script = Some(path);
+            }
+            "--keep-going" => options.keep_going = true,
+            "--allow-external" => options.allow_external = true,
+            "--safe" => options.safe = true,
+            "--no-color" => no_color = true,
+            "--no-history" => options.no_history = true,
+            "--clear" => options.restore = false,
+            "--restore" => options.restore = true,
+            "--normalize-crlf" => options.normalize_crlf = true,
+            "--raw" => options.raw = true,
+            "--stats" => options.stats = true,
+            "--watch" => options.watch = true,
+            "--log" => {
+                let path = args.next().ok_or("--log requires a file")?;
+                let path = std::path::absolute(path).map_err(|e| format!("Invalid log path '{}': {}", path, e))?;
+                options.log = Some(path);
+            }
+            "--glob-confirm" => {
+                let count = args.next().ok_or("--glob-confirm requires a count")?;
+                let count = count.parse().map_err(|_| format!("Invalid --glob-confirm count '{}'", count))?;
+                options.glob_confirm = Some(count);
+            }
+            "--output-limit" => {
+                let lines = args.next().ok_or("--output-limit requires a number of lines")?;
+                let lines = lines.parse().map_err(|_| format!("Invalid --output-limit '{}'", lines))?;
+                options.output_limit = Some(lines);
+            }
+            "--tab-size" => {
+                let size = args.next().ok_or("--tab-size requires a number")?;
+                match size.parse() {
+                    Ok(size) if size > 0 => options.tab_size = Some(size),
+                    _ => return Err(format!("Invalid --tab-size '{}'", size)),
+                }
+            }
+            "--replay" => {
+                let path = args.next().ok_or("--replay requires a file")?;
+                let path = std::path::absolute(path).map_err(|e| format!("Invalid replay path '{}': {}", path, e))?;
+                options.replay = Some(path);
+            }
+            "--theme" => theme = Some(args.next().ok_or("--theme requires a name")?.clone()),
+            "--profile" => options.profile = Some(args.next().ok_or("--profile requires a name")?.clone()),
+            "-c" | "--command" => {
+                let command = args.next().ok_or("-c requires a command")?;
+                options.command = Some(command.clone());
+            }
+            "--virtual" => {
+                let dir = args.next().ok_or("--virtual requires a directory")?;
+                let root = fs::canonicalize(dir).map_err(|e| format!("Invalid virtual directory '{}': {}", dir, e))?;
+                if !root.is_dir() {
+                    return Err(format!("Virtual root '{}' is not a directory", dir));
+                }
+                options.virtual_root = Some(root);
+            }
+            _ => return Err(format!("Unknown option '{}'", arg)),
+        }
+    }
+
+    // Settings from the configuration files apply where no flag chose otherwise
+    let mut config = load_config()?;
+    if let Some(name) = &options.profile {
+        let path = profile_path(name).ok_or("Cannot find the configuration directory")?;
+        if !path.exists() {
+            return Err(format!("Profile '{}' not found at '{}'", name, path.display()));
+        }
+        config = config.merge(read_config(&path)?);
+    }
+    theme = theme.or(config.theme);
+    no_color |= config.color == Some(false);
+    options.read_only |= config.read_only == Some(true);
+    options.prompt = config.prompt;
+    options.max_output_lines = config.max_output_lines;
+    options.banner = config.banner;
+    if config.tab_size == Some(0) {
+        return Err("Invalid configuration: tab_size must be at least 1".to_string());
+    }
+    options.tab_size = options.tab_size.or(config.tab_size);
+    options.output_limit = options.output_limit.or(config.output_limit);
+    options.history_control = config.history_control;
+    options.aliases = config.aliases;
+    if let Some(name) = theme {
+        options.theme = Theme::named(&name).ok_or_else(|| {
+            let names: Vec<&str> = Theme::NAMED.iter().map(|(name, _)| *name).collect();
+            format!("Unknown theme '{}' (available: {})", name, names.join(", "))
+        })?;
+    }
+    if no_color {
+        options.theme.colored = false;
+    }
+
+    if options.jail_root.is_some() && options.virtual_root.is_some() {
+        return Err("--jail and --virtual cannot be used together".to_string());
+    }
+    if options.replay.is_some() && (options.command.is_some() || options.script.is_some() || options.json) {
+        return Err("--replay runs on the interactive screen, so it cannot be combined with -c, --script, or --json".to_string());
+    }
+    if options.watch && options.virtual_root.is_some() {
+        return Err("--watch looks at the real filesystem, so it cannot be combined with --virtual".to_string());
+    }
+    // External programs see the real filesystem, outside any of the emulator's restrictions
+    let restricted = options.jail_root.is_some() || options.virtual_root.is_some() || options.read_only || options.safe;
+    if options.allow_external && restricted {
+        return Err("--allow-external cannot be combined with --jail, --virtual, --read-only, or --safe".to_string());
+    }
+
+    Ok(options)
+}
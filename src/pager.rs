@@ -0,0 +1,348 @@
+//! A full-screen pager for reading long output, opened by `less`.
+//!
+//! A file is read only as far as the view has reached, so opening a huge log is instant.
+
+use crate::render::{highlight_matches, sanitize, tabs_to_spaces, truncate_line};
+use crate::shell::strip_ansi;
+use crate::theme::Theme;
+use crossterm::event::{KeyCode, KeyEvent};
+use crossterm::style::Stylize;
+use std::io::BufRead;
+
+/// Number of lines read ahead at a time while searching forward through a file.
+const SEARCH_CHUNK: usize = 1000;
+
+/// The state of the pager: the text being read, the scroll position, and the last search.
+pub struct Pager {
+    /// The lines read so far.
+    lines: Vec<String>,
+    /// The same lines with colors removed, for searching.
+    plain_lines: Vec<String>,
+    /// The rest of the file being shown, until its end has been read.
+    source: Option<Box<dyn BufRead + Send>>,
+    /// Whether lines read from `source` keep their control characters (`--raw`).
+    raw: bool,
+    /// Distance between the tab stops that tabs in lines read from `source` reach.
+    tab_size: usize,
+    /// Index of the first line on screen.
+    top: usize,
+    /// Number of text rows on screen, updated on each render.
+    page_rows: usize,
+    /// The most recent search term.
+    query: String,
+    /// Indexes of the lines containing `query`.
+    matches: Vec<usize>,
+    /// Position in `matches` of the match being shown.
+    current: Option<usize>,
+    /// The search being typed after `/`, while the input line is open.
+    input: Option<String>,
+    /// A message for the status line, such as a failed search.
+    message: Option<String>,
+    /// Digits typed before a command, such as the `25` of `25g`.
+    count: String,
+}
+
+impl Pager {
+    /// Creates a pager showing `text` from the top, with tabs reaching every `tab_size`th
+    /// column.
+    pub fn new(text: &str, tab_size: usize) -> Self {
+        let lines: Vec<String> = tabs_to_spaces(text, tab_size).lines().map(str::to_string).collect();
+        let plain_lines = lines.iter().map(|line| strip_ansi(line)).collect();
+        Pager {
+            lines,
+            plain_lines,
+            source: None,
+            raw: false,
+            tab_size,
+            top: 0,
+            page_rows: 1,
+            query: String::new(),
+            matches: Vec::new(),
+            current: None,
+            input: None,
+            message: None,
+            count: String::new(),
+        }
+    }
+
+    /// Creates a pager showing a file from the top, reading it only as far as needed.
+    /// Control characters in the file are escaped as they are read unless `raw` is set.
+    pub fn open(source: Box<dyn BufRead + Send>, raw: bool, tab_size: usize) -> Self {
+        let mut pager = Pager::new("", tab_size);
+        pager.source = Some(source);
+        pager.raw = raw;
+        pager
+    }
+
+    /// Reads from the file until `count` lines are loaded or it ends. A read error ends the
+    /// file early, like reaching its end.
+    fn load_to(&mut self, count: usize) {
+        while self.lines.len() < count {
+            let Some(source) = self.source.as_mut() else {
+                return;
+            };
+            let mut bytes = Vec::new();
+            match source.read_until(b'\n', &mut bytes) {
+                Ok(0) | Err(_) => {
+                    self.source = None;
+                    return;
+                }
+                Ok(_) => {}
+            }
+            // Like `str::lines`, drop the line ending, including the `\r` of a CRLF
+            let line = String::from_utf8_lossy(&bytes);
+            let line = line.strip_suffix('\n').unwrap_or(&line);
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let line = if self.raw { line.to_string() } else { sanitize(line) };
+            let line = tabs_to_spaces(&line, self.tab_size);
+            let plain = strip_ansi(&line);
+            if !self.query.is_empty() && plain.contains(&self.query) {
+                self.matches.push(self.lines.len());
+            }
+            self.lines.push(line);
+            self.plain_lines.push(plain);
+        }
+    }
+
+    /// Returns true while a search is being typed after `/`.
+    pub fn is_searching(&self) -> bool {
+        self.input.is_some()
+    }
+
+    /// Handles a key press. Returns false when the pager should close.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if let Some(input) = &mut self.input {
+            match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace if input.is_empty() => self.input = None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Enter => {
+                    let query = self.input.take().unwrap_or_default();
+                    // Like less, an empty search repeats the previous one
+                    if !query.is_empty() {
+                        self.search(&query);
+                    }
+                    self.next_match();
+                }
+                KeyCode::Esc => self.input = None,
+                _ => {}
+            }
+            return true;
+        }
+
+        self.message = None;
+        if let KeyCode::Char(digit @ '0'..='9') = key.code {
+            self.count.push(digit);
+            return true;
+        }
+        let count = std::mem::take(&mut self.count).parse::<usize>().ok();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            // With a count, `g` and `G` go to that line; without one, to the top or bottom
+            KeyCode::Char('g') | KeyCode::Char('G') if count.is_some() => {
+                self.scroll_to(count.unwrap_or_default().saturating_sub(1))
+            }
+            KeyCode::Char('g') | KeyCode::Home => self.scroll_to(0),
+            KeyCode::Char('G') | KeyCode::End => {
+                self.load_to(usize::MAX);
+                self.scroll_to(self.lines.len());
+            }
+            KeyCode::Char('/') => self.input = Some(String::new()),
+            KeyCode::Char('n') => self.next_match(),
+            KeyCode::Char('N') => self.previous_match(),
+            KeyCode::Down | KeyCode::Enter | KeyCode::Char('j') => self.scroll_to(self.top + 1),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_to(self.top.saturating_sub(1)),
+            KeyCode::PageDown | KeyCode::Char(' ') | KeyCode::Char('f') => self.scroll_to(self.top + self.page_rows),
+            KeyCode::PageUp | KeyCode::Char('b') => self.scroll_to(self.top.saturating_sub(self.page_rows)),
+            _ => {}
+        }
+        true
+    }
+
+    /// Scrolls by `lines`, up if negative, as the mouse wheel does.
+    pub fn scroll_by(&mut self, lines: isize) {
+        self.scroll_to(self.top.saturating_add_signed(lines));
+    }
+
+    /// Moves the first line on screen to `line`, stopping once the last line is visible.
+    fn scroll_to(&mut self, line: usize) {
+        self.load_to(line.saturating_add(self.page_rows));
+        self.top = line.min(self.lines.len().saturating_sub(self.page_rows));
+    }
+
+    /// Finds the lines containing `query`, replacing the previous search.
+    fn search(&mut self, query: &str) {
+        self.query = query.to_string();
+        self.matches = self
+            .plain_lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.contains(query))
+            .map(|(index, _)| index)
+            .collect();
+        self.current = None;
+    }
+
+    /// Shows the first match after the current one, or after the top of the screen if no
+    /// match is being shown, reading further into the file until one turns up.
+    fn next_match(&mut self) {
+        let after = match self.current {
+            Some(current) => self.matches[current] + 1,
+            None => self.top,
+        };
+        let mut found = self.matches.iter().position(|&line| line >= after);
+        while found.is_none() && self.source.is_some() && !self.query.is_empty() {
+            self.load_to(self.lines.len() + SEARCH_CHUNK);
+            found = self.matches.iter().position(|&line| line >= after);
+        }
+        self.show_match(found);
+    }
+
+    /// Shows the last match before the current one, or before the top of the screen.
+    fn previous_match(&mut self) {
+        let before = self.current.map_or(self.top, |current| self.matches[current]);
+        let found = self.matches.iter().rposition(|&line| line < before);
+        self.show_match(found);
+    }
+
+    /// Scrolls the match at `index` in `matches` to the top of the screen, or reports that
+    /// there is none.
+    fn show_match(&mut self, index: Option<usize>) {
+        match index {
+            Some(index) => {
+                self.current = Some(index);
+                self.top = self.matches[index];
+                self.load_to(self.top + self.page_rows);
+            }
+            None if self.query.is_empty() => self.message = Some("No previous search".to_string()),
+            None => self.message = Some(format!("Pattern not found: {}", self.query)),
+        }
+    }
+
+    /// Returns how far through the text the bottom of the screen is, as a percentage. Only
+    /// meaningful once the whole file has been read.
+    fn percent(&self) -> usize {
+        let end = (self.top + self.page_rows).min(self.lines.len());
+        (end * 100).checked_div(self.lines.len()).unwrap_or(100)
+    }
+
+    /// Lays out the screen rows for a screen of the given size: the visible lines, then the
+    /// status line on the last row. Lines wider than the screen are cut off rather than
+    /// wrapped, so each takes one row.
+    pub fn render(&mut self, (columns, rows): (usize, usize), theme: &Theme) -> Vec<String> {
+        self.page_rows = rows.saturating_sub(1).max(1);
+        self.scroll_to(self.top);
+
+        let current_line = self.current.map(|current| self.matches[current]);
+        let end = (self.top + self.page_rows).min(self.lines.len());
+        let mut screen_rows: Vec<String> = (self.top..end)
+            .map(|index| {
+                let line = if Some(index) == current_line {
+                    highlight_matches(&self.plain_lines[index], &self.query)
+                } else {
+                    self.lines[index].clone()
+                };
+                truncate_line(&line, columns)
+            })
+            .collect();
+        screen_rows.resize(self.page_rows, String::new());
+
+        let status = match (&self.input, &self.message) {
+            (Some(input), _) => format!("/{}", input),
+            (None, Some(message)) => theme.error(message),
+            (None, None) if !self.count.is_empty() => format!(":{}", self.count),
+            // The size of a file still being read is unknown, so show the lines instead
+            (None, None) if self.source.is_some() => format!("lines {}-{}", self.top + 1, end),
+            (None, None) if end >= self.lines.len() => "(END)".reverse().to_string(),
+            (None, None) => format!("{}%", self.percent()),
+        };
+        screen_rows.push(truncate_line(&status, columns));
+        screen_rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn press(pager: &mut Pager, keys: &str) {
+        for c in keys.chars() {
+            let code = if c == '\n' { KeyCode::Enter } else { KeyCode::Char(c) };
+            pager.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+        }
+    }
+
+    #[test]
+    fn search_moves_between_matches() {
+        let text = (0..20).map(|n| if n % 5 == 0 { format!("error {}", n) } else { format!("ok {}", n) });
+        let mut pager = Pager::new(&text.collect::<Vec<_>>().join("\n"), 8);
+        pager.page_rows = 4;
+
+        press(&mut pager, "/error\n");
+        assert_eq!((pager.top, pager.current), (0, Some(0)));
+        press(&mut pager, "nn");
+        assert_eq!((pager.top, pager.current), (10, Some(2)));
+        press(&mut pager, "N");
+        assert_eq!((pager.top, pager.current), (5, Some(1)));
+
+        press(&mut pager, "nnn");
+        assert_eq!(pager.current, Some(3));
+        assert_eq!(pager.message.as_deref(), Some("Pattern not found: error"));
+    }
+
+    #[test]
+    fn g_jumps_to_lines_and_the_footer_tracks_progress() {
+        let text: Vec<String> = (1..=100).map(|n| n.to_string()).collect();
+        let mut pager = Pager::new(&text.join("\n"), 8);
+        pager.page_rows = 10;
+        assert_eq!(pager.percent(), 10);
+
+        press(&mut pager, "G");
+        assert_eq!((pager.top, pager.percent()), (90, 100));
+        press(&mut pager, "35g");
+        assert_eq!((pager.top, pager.percent()), (34, 44));
+        press(&mut pager, "g");
+        assert_eq!(pager.top, 0);
+        press(&mut pager, "500G");
+        assert_eq!(pager.top, 90);
+    }
+
+    #[test]
+    fn files_are_read_only_as_far_as_the_view_goes() {
+        let text: String = (1..=10_000).map(|n| format!("line {}\r\n", n)).collect();
+        let mut pager = Pager::open(Box::new(std::io::Cursor::new(text.into_bytes())), false, 8);
+        pager.page_rows = 10;
+        pager.scroll_to(0);
+        assert_eq!(pager.lines.len(), 10);
+        assert_eq!(pager.lines[0], "line 1");
+
+        press(&mut pager, " ");
+        assert_eq!((pager.top, pager.lines.len()), (10, 20));
+        press(&mut pager, "/line 2500\n");
+        assert_eq!(pager.top, 2499);
+        assert!(pager.lines.len() < 10_000 && pager.source.is_some());
+
+        press(&mut pager, "G");
+        assert_eq!((pager.top, pager.lines.len(), pager.percent()), (9990, 10_000, 100));
+        assert!(pager.source.is_none());
+    }
+
+    #[test]
+    fn tabs_reach_the_tab_stops() {
+        let mut pager = Pager::open(Box::new(std::io::Cursor::new(b"a\tb\n".to_vec())), false, 4);
+        assert_eq!(pager.render((20, 3), &Theme::default())[0], "a   b");
+        let mut pager = Pager::new("\tc", 2);
+        assert_eq!(pager.render((20, 3), &Theme::default())[0], "  c");
+    }
+
+    #[test]
+    fn missing_pattern_leaves_the_view_in_place() {
+        let mut pager = Pager::new("one\ntwo\nthree", 8);
+        press(&mut pager, "j/four\n");
+        assert_eq!((pager.top, pager.current), (1, None));
+        assert!(pager.message.is_some());
+    }
+}
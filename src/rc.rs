@@ -0,0 +1,72 @@
+//! The startup file `~/.rustemurc`: commands run when the interactive screen opens, where
+//! `alias --save` keeps aliases for later sessions.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::shell::quote_word;
+
+/// Returns the path of the startup file, `.rustemurc` in the user's home directory.
+pub fn rc_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|dir| dir.join(".rustemurc"))
+}
+
+/// Saves the alias `name` with `value` in the startup file at `path`, replacing the line
+/// that defined it before, if any, so each alias is defined once. Other lines are kept.
+pub fn save_alias(path: &Path, name: &str, value: &str) -> io::Result<()> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let definition = format!("alias {}={}", name, quote_word(value));
+
+    let mut lines = Vec::new();
+    let mut saved = false;
+    for line in contents.lines() {
+        if alias_name(line) != Some(name) {
+            lines.push(line);
+        } else if !saved {
+            lines.push(&definition);
+            saved = true;
+        }
+    }
+    if !saved {
+        lines.push(&definition);
+    }
+
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    fs::write(path, contents)
+}
+
+/// Returns the name of the alias a line defines, if it is an `alias` command defining a
+/// single alias.
+fn alias_name(line: &str) -> Option<&str> {
+    let definition = line.trim().strip_prefix("alias ")?.trim_start();
+    let (name, _) = definition.split_once('=')?;
+    (!name.is_empty() && !name.contains(char::is_whitespace)).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_aliases_replace_their_earlier_definition() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(".rustemurc");
+
+        save_alias(&path, "ll", "ls -a").unwrap();
+        fs::write(&path, fs::read_to_string(&path).unwrap() + "cd docs\nalias ll='ls'\n").unwrap();
+        save_alias(&path, "ll", "ls -l").unwrap();
+        save_alias(&path, "g", "grep -n").unwrap();
+        save_alias(&path, "x", "echo it's").unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(contents, "alias ll='ls -l'\ncd docs\nalias g='grep -n'\nalias x='echo it'\\''s'\n");
+        assert_eq!(alias_name("  alias la='ls -A'"), Some("la"));
+        assert_eq!(alias_name("echo alias x=y"), None);
+    }
+}
@@ -0,0 +1,271 @@
+//! Input and output redirection (`<`, `>`, `>>`, `>|`, `2>`, `2>>`, `2>&1`, `>&2`) and
+//! here-documents (`<<`) on pipeline stages.
+
+use crate::shell::split_unquoted;
+use std::env;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// How an output redirection writes to its file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WriteMode {
+    /// `>`: replaces the file's contents, unless `noclobber` is set and the file exists.
+    Replace,
+    /// `>>`: adds to the end of the file.
+    Append,
+    /// `>|`: replaces the file's contents even when `noclobber` is set.
+    Force,
+}
+
+/// Redirections parsed out of one pipeline stage.
+#[derive(Debug, Default, PartialEq)]
+pub struct Redirections {
+    /// File that standard output is written to, and how.
+    pub stdout: Option<(String, WriteMode)>,
+    /// File that error output is written to (`2>`, `2>>`, or `2>|`), and how.
+    pub stderr: Option<(String, WriteMode)>,
+    /// Whether error output goes wherever standard output goes (`2>&1`).
+    pub stderr_to_stdout: bool,
+    /// Whether standard output goes to error output instead (`>&2`).
+    pub stdout_to_stderr: bool,
+    /// File that standard input is read from (`<`).
+    pub stdin: Option<String>,
+    /// Delimiter of a here-document feeding standard input, and whether leading tabs are
+    /// stripped from its lines (`<<-`).
+    pub heredoc: Option<(String, bool)>,
+}
+
+/// Removes the unquoted redirection operators from a stage, returning the remaining
+/// command text and the redirections that were found.
+///
+/// `$VAR` and `${VAR}` in file names are replaced with environment variables, except
+/// inside single quotes. Here-document delimiters are taken literally.
+pub fn parse_redirections(stage: &str) -> Result<(String, Redirections), String> {
+    let mut command = String::new();
+    let mut redirections = Redirections::default();
+    let mut quote: Option<char> = None;
+    // Operators inside `$(...)` belong to the substituted command
+    let mut depth = 0;
+    let mut chars = stage.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') | (None, '`') => {
+                quote = Some(c);
+                command.push(c);
+            }
+            (Some(open), _) if c == open => {
+                quote = None;
+                command.push(c);
+            }
+            (None, '$') if chars.next_if_eq(&'(').is_some() => {
+                depth += 1;
+                command.push_str("$(");
+            }
+            (None, ')') if depth > 0 => {
+                depth -= 1;
+                command.push(c);
+            }
+            (None, _) if depth > 0 => command.push(c),
+            // `2>` only counts at the start of a word, so `echo 12>x` writes `12` to `x`
+            (None, '2')
+                if chars.peek() == Some(&'>') && (command.is_empty() || command.ends_with(char::is_whitespace)) =>
+            {
+                chars.next();
+                if chars.next_if_eq(&'&').is_some() {
+                    chars.next_if_eq(&'1').ok_or("Error: Only '2>&1' is supported after '2>&'.")?;
+                    redirections.stderr_to_stdout = true;
+                } else {
+                    let mode = read_write_mode(&mut chars);
+                    let target = read_word(&mut chars, true).ok_or("Error: Missing file name after '2>'.")?;
+                    redirections.stderr = Some((target, mode));
+                }
+            }
+            (None, '>') if chars.next_if_eq(&'&').is_some() => {
+                chars.next_if_eq(&'2').ok_or("Error: Only '>&2' is supported after '>&'.")?;
+                redirections.stdout_to_stderr = true;
+            }
+            (None, '>') => {
+                let mode = read_write_mode(&mut chars);
+                let target = read_word(&mut chars, true).ok_or("Error: Missing file name after '>'.")?;
+                redirections.stdout = Some((target, mode));
+            }
+            (None, '<') if chars.next_if_eq(&'<').is_some() => {
+                let strip_tabs = chars.next_if_eq(&'-').is_some();
+                let delimiter = read_word(&mut chars, false).ok_or("Error: Missing delimiter after '<<'.")?;
+                redirections.heredoc = Some((delimiter, strip_tabs));
+            }
+            (None, '<') => {
+                let source = read_word(&mut chars, true).ok_or("Error: Missing file name after '<'.")?;
+                redirections.stdin = Some(source);
+            }
+            _ => command.push(c),
+        }
+    }
+
+    Ok((command, redirections))
+}
+
+/// Reads the rest of an output redirection operator after its first `>`: another `>` to
+/// append, or `|` to overwrite regardless of `noclobber`.
+fn read_write_mode(chars: &mut Peekable<Chars>) -> WriteMode {
+    if chars.next_if_eq(&'>').is_some() {
+        WriteMode::Append
+    } else if chars.next_if_eq(&'|').is_some() {
+        WriteMode::Force
+    } else {
+        WriteMode::Replace
+    }
+}
+
+/// Reads the word following a redirection operator, removing any quotes around it and,
+/// when `expand` is set, replacing variables outside single quotes.
+fn read_word(chars: &mut Peekable<Chars>, expand: bool) -> Option<String> {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+    let mut word = String::new();
+    let mut quote: Option<char> = None;
+    while let Some(&c) = chars.peek() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, _) if c.is_whitespace() || c == '<' || c == '>' => break,
+            (None, '$') | (Some('"'), '$') if expand => {
+                chars.next();
+                word.push_str(&read_variable(chars));
+                continue;
+            }
+            _ => word.push(c),
+        }
+        chars.next();
+    }
+
+    (!word.is_empty()).then_some(word)
+}
+
+/// Reads a variable name following `$`, as `NAME` or `{NAME}`, and returns its value from
+/// the environment. Unset variables expand to nothing, and a `$` without a name is kept.
+pub fn read_variable(chars: &mut Peekable<Chars>) -> String {
+    let braced = chars.next_if_eq(&'{').is_some();
+    let mut name = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+        name.push(c);
+    }
+    if braced && chars.next_if_eq(&'}').is_none() {
+        return format!("${{{}", name);
+    }
+    if name.is_empty() {
+        return if braced { "${}".to_string() } else { "$".to_string() };
+    }
+    env::var(&name).unwrap_or_default()
+}
+
+/// A here-document whose body is still being read, one line at a time, after the command
+/// line that started it.
+pub struct Heredoc {
+    delimiter: String,
+    strip_tabs: bool,
+    body: String,
+}
+
+impl Heredoc {
+    /// Returns the here-document started by a command line, if it contains a `<<` operator.
+    pub fn from_command(line: &str) -> Option<Heredoc> {
+        split_unquoted(line, ';')
+            .into_iter()
+            .flat_map(|command| split_unquoted(command, '|'))
+            .find_map(|stage| parse_redirections(stage).ok()?.1.heredoc)
+            .map(|(delimiter, strip_tabs)| Heredoc { delimiter, strip_tabs, body: String::new() })
+    }
+
+    /// Adds a line to the body, returning `true` once the delimiter line ends it.
+    pub fn push_line(&mut self, line: &str) -> bool {
+        let line = if self.strip_tabs { line.trim_start_matches('\t') } else { line };
+        if line == self.delimiter {
+            return true;
+        }
+        self.body.push_str(line);
+        self.body.push('\n');
+        false
+    }
+
+    /// Returns the collected body, one `\n`-terminated line per line read.
+    pub fn into_body(self) -> String {
+        self.body
+    }
+}
+
+/// Reads the body of the here-document started by `command_line`, if any, from the lines
+/// that follow it. A body cut short by the end of input keeps the lines read so far.
+pub fn read_heredoc<'a>(command_line: &str, lines: impl Iterator<Item = &'a str>) -> Option<String> {
+    let mut heredoc = Heredoc::from_command(command_line)?;
+    for line in lines {
+        if heredoc.push_line(line) {
+            break;
+        }
+    }
+    Some(heredoc.into_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirections_are_removed_from_the_command() {
+        let (command, redirections) = parse_redirections("cat << 'EOF' >> \"out file.txt\"").unwrap();
+        assert_eq!(command.trim(), "cat");
+        assert_eq!(redirections.stdout, Some(("out file.txt".to_string(), WriteMode::Append)));
+        assert_eq!(redirections.heredoc, Some(("EOF".to_string(), false)));
+    }
+
+    #[test]
+    fn file_names_expand_variables_outside_single_quotes() {
+        let home = env::var("HOME").unwrap_or_default();
+        let (_, redirections) = parse_redirections("sort < $HOME/in.txt > '$HOME'/out").unwrap();
+        assert_eq!(redirections.stdin, Some(format!("{}/in.txt", home)));
+        assert_eq!(redirections.stdout, Some(("$HOME/out".to_string(), WriteMode::Replace)));
+
+        let (_, redirections) = parse_redirections("cat << $HOME").unwrap();
+        assert_eq!(redirections.heredoc, Some(("$HOME".to_string(), false)));
+    }
+
+    #[test]
+    fn error_output_has_its_own_redirections() {
+        let (command, redirections) = parse_redirections("find . -name x 2>> errors.txt > found.txt").unwrap();
+        assert_eq!(command.trim(), "find . -name x");
+        assert_eq!(redirections.stderr, Some(("errors.txt".to_string(), WriteMode::Append)));
+        assert_eq!(redirections.stdout, Some(("found.txt".to_string(), WriteMode::Replace)));
+
+        let (command, redirections) = parse_redirections("cat a 2>&1").unwrap();
+        assert_eq!(command.trim(), "cat a");
+        assert!(redirections.stderr_to_stdout);
+        let (command, redirections) = parse_redirections("echo 12>x").unwrap();
+        assert_eq!(command.trim(), "echo 12");
+        assert_eq!(redirections.stdout, Some(("x".to_string(), WriteMode::Replace)));
+        assert!(parse_redirections("echo oops >&2").unwrap().1.stdout_to_stderr);
+        let (_, redirections) = parse_redirections("echo hi >| x").unwrap();
+        assert_eq!(redirections.stdout, Some(("x".to_string(), WriteMode::Force)));
+
+        assert!(parse_redirections("cat a 2>").is_err());
+        assert!(parse_redirections("cat a 2>&3").is_err());
+    }
+
+    #[test]
+    fn quoted_operators_are_left_alone() {
+        let (command, redirections) = parse_redirections("echo 'a > b'").unwrap();
+        assert_eq!(command, "echo 'a > b'");
+        assert_eq!(redirections, Redirections::default());
+        assert!(parse_redirections("echo hi >").is_err());
+    }
+
+    #[test]
+    fn heredoc_collects_lines_until_the_delimiter() {
+        let mut heredoc = Heredoc::from_command("cat <<- END | grep a").unwrap();
+        assert!(!heredoc.push_line("\tapple"));
+        assert!(!heredoc.push_line("  banana"));
+        assert!(heredoc.push_line("\t\tEND"));
+        assert_eq!(heredoc.into_body(), "apple\n  banana\n");
+        assert!(Heredoc::from_command("echo '<< EOF'").is_none());
+    }
+}
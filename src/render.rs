@@ -0,0 +1,337 @@
+//! Drawing the interactive screen: measuring and wrapping text by the terminal cells it
+//! covers, the status bar, and updating the terminal between frames.
+//!
+//! Wide characters such as CJK glyphs and most emoji take two cells and combining marks
+//! take none, and ANSI escape sequences (colors) take no space at all.
+
+use crate::theme::Theme;
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{Print, Stylize},
+    terminal::{self, Clear, ClearType},
+};
+use std::io::Write;
+use unicode_width::UnicodeWidthChar;
+
+/// The rows last drawn on the terminal, so that a new frame only rewrites the rows that
+/// changed instead of clearing and repainting the whole screen.
+///
+/// Rows are compared as whole strings, colors included, since that is how the screen is
+/// laid out; typing a character usually changes just the prompt row.
+#[derive(Default)]
+pub struct Screen {
+    drawn: Vec<String>,
+    /// Terminal size, in columns and rows, that `drawn` was laid out for.
+    size: (usize, usize),
+}
+
+impl Screen {
+    /// Draws `rows` from the top of a terminal of `size`, each already cut to fit. Rows
+    /// that match the previous frame are skipped, and rows left over from it are blanked.
+    /// A change of size repaints everything.
+    pub fn draw(&mut self, out: &mut impl Write, rows: &[String], size: (usize, usize)) -> crossterm::Result<()> {
+        if size != self.size {
+            queue!(out, Clear(ClearType::All))?;
+            self.drawn.clear();
+            self.size = size;
+        }
+        for index in 0..rows.len().max(self.drawn.len()) {
+            let row = rows.get(index).map_or("", String::as_str);
+            if self.drawn.get(index).map_or(row.is_empty(), |drawn| drawn == row) {
+                continue;
+            }
+            queue!(out, MoveTo(0, index as u16), Print(row))?;
+            // On a full row the cursor sits on the last character, which clearing would erase
+            if display_width(row) < size.0 {
+                queue!(out, Clear(ClearType::UntilNewLine))?;
+            }
+        }
+        self.drawn = rows.to_vec();
+        Ok(())
+    }
+
+    /// Forgets what is on the terminal, so the next frame repaints everything. Used when
+    /// something else has drawn over the screen.
+    pub fn invalidate(&mut self) {
+        *self = Screen::default();
+    }
+}
+
+/// Splits `text` into its escape sequences and the printable characters between them,
+/// calling `visit` with each piece and the number of cells it covers.
+fn for_each_piece(text: &str, mut visit: impl FnMut(&str, usize)) {
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = if c == '\x1b' { escape_len(rest) } else { c.len_utf8() };
+        let (piece, tail) = rest.split_at(len);
+        let width = if c == '\x1b' { 0 } else { c.width().unwrap_or(0) };
+        visit(piece, width);
+        rest = tail;
+    }
+}
+
+/// Returns the length in bytes of the escape sequence at the start of `text`: a CSI sequence
+/// runs from `ESC [` to a final byte in `@`..=`~`, and any other escape is two characters.
+fn escape_len(text: &str) -> usize {
+    let mut chars = text.char_indices().skip(1);
+    match chars.next() {
+        Some((_, '[')) => chars
+            .find(|&(_, c)| ('@'..='~').contains(&c))
+            .map_or(text.len(), |(i, c)| i + c.len_utf8()),
+        Some((i, c)) => i + c.len_utf8(),
+        None => text.len(),
+    }
+}
+
+/// Makes `text` safe to show on the screen: colors are kept, but other control characters
+/// are shown in caret notation (`^[`, `^M`, `^G`) so they cannot move the cursor, retitle
+/// the window, or ring the bell. Newlines, CRLF line endings, and tabs are left alone.
+pub fn sanitize(text: &str) -> String {
+    let mut safe = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let mut len = c.len_utf8();
+        match c {
+            '\x1b' if is_color_escape(&rest[..escape_len(rest)]) => {
+                len = escape_len(rest);
+                safe.push_str(&rest[..len]);
+            }
+            '\n' | '\t' => safe.push(c),
+            '\r' if rest[1..].starts_with('\n') => safe.push(c),
+            '\x7f' => safe.push_str("^?"),
+            c if c < ' ' => {
+                safe.push('^');
+                safe.push((c as u8 + b'@') as char);
+            }
+            c if c.is_control() => safe.extend(c.escape_unicode()),
+            c => safe.push(c),
+        }
+        rest = &rest[len..];
+    }
+    safe
+}
+
+/// Returns true if `escape` is a complete SGR sequence, which only changes colors and
+/// styles, such as `ESC [ 1 ; 31 m`.
+fn is_color_escape(escape: &str) -> bool {
+    escape
+        .strip_prefix("\x1b[")
+        .and_then(|escape| escape.strip_suffix('m'))
+        .is_some_and(|params| params.chars().all(|c| c.is_ascii_digit() || c == ';' || c == ':'))
+}
+
+/// Builds the status bar shown on the bottom row: the input mode and the exit status of the
+/// last command, in the error color when it failed, cut to `width` cells.
+pub fn status_bar(mode: &str, exit_code: i32, theme: &Theme, width: usize) -> String {
+    let status = format!("exit {}", exit_code);
+    let status = if exit_code == 0 { theme.success(status) } else { theme.error(status) };
+    truncate_line(&format!("-- {} --  {}", mode, status), width)
+}
+
+/// Writes `text` over the bottom row of the terminal, where the status bar is, so a command
+/// can show its progress while the screen waits for it. The next frame has to repaint
+/// that row.
+pub fn draw_progress(out: &mut impl Write, text: &str) -> crossterm::Result<()> {
+    let (columns, rows) = terminal::size()?;
+    let text = truncate_line(text, columns as usize);
+    queue!(out, MoveTo(0, rows.saturating_sub(1)), Clear(ClearType::CurrentLine), Print(text))?;
+    out.flush()
+}
+
+/// Shows each occurrence of `query` in `line`, which should be free of colors, in reverse
+/// video.
+pub fn highlight_matches(line: &str, query: &str) -> String {
+    line.split(query).collect::<Vec<_>>().join(&query.reverse().to_string())
+}
+
+/// Returns the number of terminal cells `text` covers.
+pub fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    for_each_piece(text, |_, cells| width += cells);
+    width
+}
+
+/// Replaces each tab in `text` with the spaces that reach the next multiple of `tab_size`
+/// cells, so tabbed text lines up the same way wherever it is shown. Escape sequences take
+/// no room, and each line starts again at the first column.
+pub fn tabs_to_spaces(text: &str, tab_size: usize) -> String {
+    if !text.contains('\t') {
+        return text.to_string();
+    }
+    let tab_size = tab_size.max(1);
+    let mut expanded = String::with_capacity(text.len());
+    let mut column = 0;
+    for_each_piece(text, |piece, cells| match piece {
+        "\t" => {
+            let spaces = tab_size - column % tab_size;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        }
+        "\n" => {
+            expanded.push('\n');
+            column = 0;
+        }
+        _ => {
+            expanded.push_str(piece);
+            column += cells;
+        }
+    });
+    expanded
+}
+
+/// Lays `items` out as `ls` does on a terminal: in as many columns as fit in `width` cells,
+/// two spaces apart, filled top to bottom and then left to right. Returns the rows.
+pub fn layout_columns(items: &[String], width: usize) -> Vec<String> {
+    let widths: Vec<usize> = items.iter().map(|item| display_width(item)).collect();
+    let column_widths = |rows: usize| -> Vec<usize> {
+        widths.chunks(rows).map(|column| column.iter().copied().max().unwrap_or(0)).collect()
+    };
+    // The fewest rows whose columns fit, or one item per row when even two columns are too wide
+    let rows = (1..items.len())
+        .find(|&rows| {
+            let columns = column_widths(rows);
+            columns.iter().sum::<usize>() + 2 * (columns.len() - 1) <= width
+        })
+        .unwrap_or(items.len());
+    let Some(column_widths) = (rows > 0).then(|| column_widths(rows)) else {
+        return Vec::new();
+    };
+
+    (0..rows)
+        .map(|row| {
+            let mut line = String::new();
+            for (column, column_width) in column_widths.iter().enumerate() {
+                let index = column * rows + row;
+                if index >= items.len() {
+                    break;
+                }
+                line.push_str(&items[index]);
+                if index + rows < items.len() {
+                    line.push_str(&" ".repeat(column_width - widths[index] + 2));
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+/// Breaks `line` into rows of at most `width` cells, never splitting a wide character. An
+/// empty line is one empty row.
+pub fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let width = width.max(2);
+    let mut rows = vec![String::new()];
+    let mut used = 0;
+    for_each_piece(line, |piece, cells| {
+        if used + cells > width {
+            rows.push(String::new());
+            used = 0;
+        }
+        rows.last_mut().expect("rows is never empty").push_str(piece);
+        used += cells;
+    });
+    rows
+}
+
+/// Cuts `line` off after `width` cells, keeping its escape sequences.
+pub fn truncate_line(line: &str, width: usize) -> String {
+    let mut kept = String::new();
+    let mut used = 0;
+    for_each_piece(line, |piece, cells| {
+        if used + cells <= width {
+            kept.push_str(piece);
+            used += cells;
+        } else if cells == 0 {
+            // Escapes past the cut still apply, so a color reset is not lost
+            kept.push_str(piece);
+        } else {
+            used = width + 1;
+        }
+    });
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wide_characters_and_escapes_are_measured_by_cells() {
+        assert_eq!(display_width("abc"), 3);
+        assert_eq!(display_width("日本"), 4);
+        assert_eq!(display_width("\x1b[31mred\x1b[0m"), 3);
+        assert_eq!(display_width("e\u{301}"), 1);
+    }
+
+    #[test]
+    fn tabs_reach_the_next_stop() {
+        assert_eq!(tabs_to_spaces("a\tb\n\tc", 4), "a   b\n    c");
+        assert_eq!(tabs_to_spaces("\x1b[31mab\x1b[0m\tc", 4), "\x1b[31mab\x1b[0m  c");
+        assert_eq!(tabs_to_spaces("日本\tx", 8), "日本    x");
+        assert_eq!(tabs_to_spaces("no tabs", 8), "no tabs");
+    }
+
+    #[test]
+    fn columns_fill_downward_and_fit_the_width() {
+        let items: Vec<String> = ["a", "bb", "ccc", "\x1b[34mdd\x1b[39m", "e"].map(String::from).into();
+        assert_eq!(layout_columns(&items, 80), ["a  bb  ccc  \x1b[34mdd\x1b[39m  e"]);
+        assert_eq!(layout_columns(&items, 12), ["a   ccc  e", "bb  \x1b[34mdd\x1b[39m"]);
+        assert_eq!(layout_columns(&items, 9), ["a    \x1b[34mdd\x1b[39m", "bb   e", "ccc"]);
+        assert_eq!(layout_columns(&items, 3), ["a", "bb", "ccc", "\x1b[34mdd\x1b[39m", "e"]);
+        assert_eq!(layout_columns(&[], 80), Vec::<String>::new());
+    }
+
+    #[test]
+    fn wrapping_never_splits_a_wide_character() {
+        assert_eq!(wrap_line("ab日本", 5), vec!["ab日", "本"]);
+        assert_eq!(wrap_line("\x1b[31mabcd\x1b[0m", 2), vec!["\x1b[31mab", "cd\x1b[0m"]);
+        assert_eq!(wrap_line("", 10), vec![""]);
+    }
+
+    #[test]
+    fn status_bar_shows_mode_and_exit_status() {
+        let theme = Theme { colored: false, ..Theme::DEFAULT };
+        assert_eq!(status_bar("INSERT", 0, &theme, 80), "-- INSERT --  exit 0");
+        assert_eq!(status_bar("PAGER", 127, &theme, 12), "-- PAGER -- ");
+        assert!(status_bar("INSERT", 1, &Theme::DEFAULT, 80).contains(&Theme::DEFAULT.error("exit 1")));
+    }
+
+    #[test]
+    fn frames_only_rewrite_changed_rows() {
+        let rows = |texts: &[&str]| texts.iter().map(|text| text.to_string()).collect::<Vec<_>>();
+        let mut screen = Screen::default();
+        let mut out = Vec::new();
+        screen.draw(&mut out, &rows(&["header", "> ls", "status"]), (20, 3)).unwrap();
+        let first = String::from_utf8(out).unwrap();
+        assert!(first.contains("header") && first.contains("status"));
+
+        let mut out = Vec::new();
+        screen.draw(&mut out, &rows(&["header", "> ls -l", "status"]), (20, 3)).unwrap();
+        let second = String::from_utf8(out).unwrap();
+        assert!(second.contains("> ls -l") && !second.contains("header") && !second.contains("status"));
+
+        let mut out = Vec::new();
+        screen.draw(&mut out, &rows(&["header", "> ls -l", "status"]), (20, 3)).unwrap();
+        assert!(out.is_empty());
+
+        screen.invalidate();
+        let mut out = Vec::new();
+        screen.draw(&mut out, &rows(&["header", "> ls -l", "status"]), (20, 3)).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("header"));
+    }
+
+    #[test]
+    fn sanitizing_keeps_colors_and_shows_other_controls() {
+        assert_eq!(sanitize("\x1b[1;31mred\x1b[0m\tok\r\n"), "\x1b[1;31mred\x1b[0m\tok\r\n");
+        assert_eq!(sanitize("a\x1b[2Jb\x1b]0;title\x07"), "a^[[2Jb^[]0;title^G");
+        assert_eq!(sanitize("50%\r100%\x08\x7f"), "50%^M100%^H^?");
+        assert_eq!(sanitize("\u{9b}31m"), "\\u{9b}31m");
+        assert_eq!(sanitize("\x1b"), "^[");
+    }
+
+    #[test]
+    fn truncating_keeps_trailing_escapes() {
+        assert_eq!(truncate_line("日本語", 5), "日本");
+        assert_eq!(truncate_line("\x1b[31mabcdef\x1b[0m", 3), "\x1b[31mabc\x1b[0m");
+    }
+}
@@ -0,0 +1,108 @@
+//! Replaying the command lines of a `--log` transcript on the interactive screen (`--replay`).
+
+use crate::redirect::Heredoc;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Pause before each replayed line is typed, and again before it runs.
+const REPLAY_DELAY: Duration = Duration::from_millis(1000);
+
+/// What the interactive screen should do next for a replay.
+#[derive(Debug, PartialEq)]
+pub enum ReplayStep {
+    /// Nothing yet: the delay has not passed.
+    Wait,
+    /// Put the line in the input line, where it waits to be run.
+    Type(String),
+    /// Run the line in the input line.
+    Run,
+    /// Every line has been run.
+    Finished,
+}
+
+/// A transcript's command lines being typed and run one at a time, with a pause before each.
+pub struct Replay {
+    lines: VecDeque<String>,
+    /// When the next step is due.
+    next_at: Instant,
+    /// Whether a line has been typed and waits to be run.
+    typed: bool,
+}
+
+impl Replay {
+    /// Starts replaying `lines`, the first one typed after the usual delay from `now`.
+    pub fn new(lines: Vec<String>, now: Instant) -> Self {
+        Replay { lines: lines.into(), next_at: now + REPLAY_DELAY, typed: false }
+    }
+
+    /// Returns the next step once its delay has passed by `now`: typing a line, then
+    /// running it.
+    pub fn step(&mut self, now: Instant) -> ReplayStep {
+        if now < self.next_at {
+            return ReplayStep::Wait;
+        }
+        self.next_at = now + REPLAY_DELAY;
+        if self.typed {
+            self.typed = false;
+            return ReplayStep::Run;
+        }
+        match self.lines.pop_front() {
+            Some(line) => {
+                self.typed = true;
+                ReplayStep::Type(line)
+            }
+            None => ReplayStep::Finished,
+        }
+    }
+
+    /// Returns true if a line has been typed and not yet run.
+    pub fn has_typed(&self) -> bool {
+        self.typed
+    }
+
+    /// Returns the number of lines still to be typed.
+    pub fn remaining(&self) -> usize {
+        self.lines.len()
+    }
+}
+
+/// Returns the command lines recorded in a `--log` transcript, in order.
+///
+/// Commands with a here-document are left out, since the transcript does not hold the
+/// document's body.
+pub fn transcript_commands(transcript: &str) -> Vec<String> {
+    transcript
+        .lines()
+        .filter(|line| line.starts_with('['))
+        .filter_map(|line| line.split_once("] $ "))
+        .map(|(_, command)| command)
+        .filter(|command| Heredoc::from_command(command).is_none())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transcripts_give_their_command_lines() {
+        let transcript = "[2026-10-15 08:30:34 UTC] $ echo hi; cat nope\nhi\n[exit status 1]\n\
+                          [2026-10-15 08:30:40 UTC] $ cat << EOF > notes.txt\n\
+                          [2026-10-15 08:30:41 UTC] $ ls | wc -l\n20\n";
+        assert_eq!(transcript_commands(transcript), ["echo hi; cat nope", "ls | wc -l"]);
+    }
+
+    #[test]
+    fn each_line_is_typed_then_run_after_a_delay() {
+        let start = Instant::now();
+        let mut replay = Replay::new(vec!["pwd".to_string()], start);
+        assert_eq!(replay.step(start), ReplayStep::Wait);
+        assert_eq!(replay.step(start + REPLAY_DELAY), ReplayStep::Type("pwd".to_string()));
+        assert!(replay.has_typed());
+        assert_eq!(replay.remaining(), 0);
+        assert_eq!(replay.step(start + REPLAY_DELAY), ReplayStep::Wait);
+        assert_eq!(replay.step(start + REPLAY_DELAY * 2), ReplayStep::Run);
+        assert_eq!(replay.step(start + REPLAY_DELAY * 3), ReplayStep::Finished);
+    }
+}
@@ -0,0 +1,168 @@
+//! Searching the scrollback of the interactive screen, opened with `/` at an empty prompt.
+
+use crate::shell::strip_ansi;
+use crossterm::event::{KeyCode, KeyEvent};
+use std::collections::VecDeque;
+
+/// What the interactive screen should do after the search has seen a key.
+#[derive(Debug, PartialEq)]
+pub enum SearchOutcome {
+    /// The key was used and the search stays open.
+    Searching,
+    /// The key closed the search.
+    Done,
+    /// The search has no use for the key, so it closes and the key is handled as usual.
+    Unhandled,
+}
+
+/// A search through the scrollback: the term being typed, then the match being shown.
+///
+/// Matches are found afresh on each move, so they stay right as new output arrives and
+/// old lines scroll out of the buffer.
+pub struct ScrollbackSearch {
+    /// The search being typed after `/`, until Enter runs it.
+    input: Option<String>,
+    /// The term searched for.
+    query: String,
+    /// Index in the scrollback of the line with the match being shown.
+    current: Option<usize>,
+}
+
+impl ScrollbackSearch {
+    /// Opens a search with an empty input line.
+    pub fn new() -> Self {
+        ScrollbackSearch { input: Some(String::new()), query: String::new(), current: None }
+    }
+
+    /// Returns the search being typed, while the input line is open.
+    pub fn input(&self) -> Option<&str> {
+        self.input.as_deref()
+    }
+
+    /// Returns the term whose matches are highlighted, once a search has run.
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Returns the index of the line with the match being shown.
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Handles a key. While the term is typed, Enter searches from the newest line up; after
+    /// that, `n` moves to the next older match and `N` to the next newer one, wrapping
+    /// around, and Esc or Enter closes the search.
+    pub fn handle_key(&mut self, key: KeyEvent, lines: &VecDeque<String>) -> SearchOutcome {
+        if let Some(input) = &mut self.input {
+            match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace if input.is_empty() => return SearchOutcome::Done,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Enter => {
+                    let input = self.input.take().unwrap_or_default();
+                    if input.is_empty() {
+                        return SearchOutcome::Done;
+                    }
+                    self.query = input;
+                    self.current = None;
+                    self.move_to(lines, true);
+                }
+                KeyCode::Esc => return SearchOutcome::Done,
+                _ => {}
+            }
+            return SearchOutcome::Searching;
+        }
+
+        match key.code {
+            KeyCode::Char('n') => self.move_to(lines, true),
+            KeyCode::Char('N') => self.move_to(lines, false),
+            KeyCode::Esc | KeyCode::Enter => return SearchOutcome::Done,
+            _ => return SearchOutcome::Unhandled,
+        }
+        SearchOutcome::Searching
+    }
+
+    /// Returns the indexes of the lines containing the query, oldest first.
+    fn matching_lines(&self, lines: &VecDeque<String>) -> Vec<usize> {
+        lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| strip_ansi(line).contains(&self.query))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Moves to the nearest match above the current one (`older`) or below it, wrapping
+    /// around at either end. Without a current match, starts from the newest line.
+    fn move_to(&mut self, lines: &VecDeque<String>, older: bool) {
+        let matches = self.matching_lines(lines);
+        let current = self.current.unwrap_or(lines.len());
+        let next = if older {
+            matches.iter().rev().find(|&&line| line < current).or(matches.last())
+        } else {
+            matches.iter().find(|&&line| line > current).or(matches.first())
+        };
+        self.current = next.copied();
+    }
+
+    /// Returns the status bar mode: `SEARCH`, then the match shown as `SEARCH 2/5`, counted
+    /// from the newest, or `SEARCH no match`.
+    pub fn status(&self, lines: &VecDeque<String>) -> String {
+        if self.input.is_some() {
+            return "SEARCH".to_string();
+        }
+        let matches = self.matching_lines(lines);
+        match self.current.and_then(|current| matches.iter().position(|&line| line == current)) {
+            Some(position) => format!("SEARCH {}/{}", matches.len() - position, matches.len()),
+            None => "SEARCH no match".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn press(search: &mut ScrollbackSearch, keys: &str, lines: &VecDeque<String>) -> SearchOutcome {
+        let mut outcome = SearchOutcome::Searching;
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\x1b' => KeyCode::Esc,
+                c => KeyCode::Char(c),
+            };
+            outcome = search.handle_key(KeyEvent::new(code, KeyModifiers::NONE), lines);
+        }
+        outcome
+    }
+
+    #[test]
+    fn matches_are_visited_from_the_newest_and_wrap_around() {
+        let lines: VecDeque<String> =
+            ["> / ls", "error: one", "ok", "\x1b[31merror: two\x1b[0m", "> / pwd"].map(String::from).into();
+        let mut search = ScrollbackSearch::new();
+
+        assert_eq!(press(&mut search, "error\n", &lines), SearchOutcome::Searching);
+        assert_eq!((search.current(), search.status(&lines).as_str()), (Some(3), "SEARCH 1/2"));
+        press(&mut search, "n", &lines);
+        assert_eq!((search.current(), search.status(&lines).as_str()), (Some(1), "SEARCH 2/2"));
+        press(&mut search, "n", &lines);
+        assert_eq!(search.current(), Some(3));
+        press(&mut search, "N", &lines);
+        assert_eq!(search.current(), Some(1));
+
+        assert_eq!(press(&mut search, "l", &lines), SearchOutcome::Unhandled);
+        assert_eq!(press(&mut search, "\x1b", &lines), SearchOutcome::Done);
+    }
+
+    #[test]
+    fn a_missing_term_is_reported() {
+        let lines: VecDeque<String> = ["one", "two"].map(String::from).into();
+        let mut search = ScrollbackSearch::new();
+        press(&mut search, "three\n", &lines);
+        assert_eq!((search.current(), search.status(&lines).as_str()), (None, "SEARCH no match"));
+    }
+}
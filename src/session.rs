@@ -0,0 +1,155 @@
+//! Saving the scrollback when the interactive screen closes, so `--restore` can show it
+//! again, the command history kept between sessions, and the transcript written with `--log`.
+
+use crate::shell::{strip_ansi, CommandOutput};
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Returns the path of the saved session, `rust_emulator/session.txt` in the user's data
+/// directory (`~/.local/share` on Linux).
+pub fn session_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("rust_emulator").join("session.txt"))
+}
+
+/// Writes the scrollback to `path`, one line per line, creating its directory if needed.
+pub fn save_session(path: &Path, lines: &VecDeque<String>) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut contents = String::new();
+    for line in lines {
+        contents.push_str(line);
+        contents.push('\n');
+    }
+    fs::write(path, contents)
+}
+
+/// Reads back at most the last `max_lines` lines saved at `path`. A missing file is an
+/// empty session.
+pub fn load_session(path: &Path, max_lines: usize) -> io::Result<VecDeque<String>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(VecDeque::new()),
+        Err(e) => return Err(e),
+    };
+    let lines: Vec<&str> = contents.lines().collect();
+    let start = lines.len().saturating_sub(max_lines);
+    Ok(lines[start..].iter().map(|line| line.to_string()).collect())
+}
+
+/// Number of history lines kept in the history file.
+const MAX_HISTORY_LINES: usize = 1000;
+
+/// Returns the path of the history file, `rust_emulator/history` in the user's data
+/// directory (`~/.local/share` on Linux).
+pub fn history_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("rust_emulator").join("history"))
+}
+
+/// Writes the last `MAX_HISTORY_LINES` lines of `history` to `path`, creating its directory
+/// if needed.
+pub fn save_history(path: &Path, history: &[String]) -> io::Result<()> {
+    let start = history.len().saturating_sub(MAX_HISTORY_LINES);
+    save_session(path, &history[start..].iter().cloned().collect())
+}
+
+/// Reads the history saved at `path`, oldest first. A missing file is an empty history.
+pub fn load_history(path: &Path) -> io::Result<Vec<String>> {
+    Ok(load_session(path, MAX_HISTORY_LINES)?.into())
+}
+
+/// A transcript of the session (`--log <file>`): each command line run, with the time it
+/// finished, followed by its output and errors without colors.
+pub struct SessionLog {
+    writer: BufWriter<File>,
+}
+
+impl SessionLog {
+    /// Opens the transcript at `path`, creating it if needed. Entries are added after
+    /// whatever it already holds.
+    pub fn open(path: &Path) -> io::Result<SessionLog> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(SessionLog { writer: BufWriter::new(file) })
+    }
+
+    /// Adds the command line `line` and its result to the transcript. A failing exit status
+    /// is noted after the output.
+    pub fn record(&mut self, line: &str, output: &CommandOutput) -> io::Result<()> {
+        writeln!(self.writer, "[{}] $ {}", timestamp(SystemTime::now()), line)?;
+        for text in [strip_ansi(&output.stdout), strip_ansi(&output.stderr)] {
+            if !text.is_empty() {
+                writeln!(self.writer, "{}", text.strip_suffix('\n').unwrap_or(&text))?;
+            }
+        }
+        if output.exit_code != 0 {
+            writeln!(self.writer, "[exit status {}]", output.exit_code)?;
+        }
+        Ok(())
+    }
+
+    /// Writes out any entries still buffered.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Formats `time` as `YYYY-MM-DD HH:MM:SS UTC`.
+fn timestamp(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn sessions_round_trip_and_keep_the_newest_lines() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("nested/session.txt");
+        let lines: VecDeque<String> = ["> / ls", "\x1b[34mdocs\x1b[39m", "", "notes.txt"].map(String::from).into();
+
+        save_session(&path, &lines).unwrap();
+        assert_eq!(load_session(&path, 10).unwrap(), lines);
+        assert_eq!(load_session(&path, 2).unwrap(), ["", "notes.txt"]);
+        assert!(load_session(&dir.path().join("missing"), 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn history_keeps_the_newest_lines() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("history");
+        let history: Vec<String> = (0..MAX_HISTORY_LINES + 5).map(|n| format!("echo {}", n)).collect();
+
+        save_history(&path, &history).unwrap();
+        let loaded = load_history(&path).unwrap();
+        assert_eq!(loaded.len(), MAX_HISTORY_LINES);
+        assert_eq!(loaded[0], "echo 5");
+        assert!(load_history(&dir.path().join("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn logs_append_commands_with_plain_output_and_errors() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("log.txt");
+        fs::write(&path, "earlier\n").unwrap();
+
+        let mut log = SessionLog::open(&path).unwrap();
+        log.record("ls", &CommandOutput::success("\x1b[34mdocs\x1b[39m\nnotes.txt\n")).unwrap();
+        log.record("cat missing", &CommandOutput::error("Error: cat: 'missing': not found")).unwrap();
+        log.flush().unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "earlier");
+        assert!(lines[1].starts_with('[') && lines[1].ends_with(" UTC] $ ls"), "{}", lines[1]);
+        assert_eq!(lines[2..4], ["docs", "notes.txt"]);
+        assert!(lines[4].ends_with("] $ cat missing"));
+        assert_eq!(lines[5..], ["Error: cat: 'missing': not found", "[exit status 1]"]);
+        assert_eq!(timestamp(UNIX_EPOCH + std::time::Duration::from_secs(86_399)), "1970-01-01 23:59:59 UTC");
+    }
+}
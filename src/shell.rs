@@ -0,0 +1,2141 @@
+//! Command execution: session state, pipelines, and dispatch to the built-in commands.
+//!
+//! Nothing here touches the terminal, so the TUI, the non-interactive modes, and the
+//! tests all run commands the same way.
+
+use crate::calendar::cal_command;
+use crate::commands::*;
+use crate::expand::{expand_alias, expand_braces, expand_history, expand_variables, substitute_commands};
+use crate::filesystem::{lexical_join, FileSystem, OsFileSystem};
+use crate::find::find_command;
+use crate::follow::Follow;
+use crate::glob::expand_glob;
+use crate::grep::grep_command;
+use crate::math::{bc_command, expr_command, factor_command, Calculator};
+use crate::redirect::{parse_redirections, read_heredoc, WriteMode};
+use crate::render::tabs_to_spaces;
+use crate::session::SessionLog;
+use crate::text::*;
+use crate::theme::Theme;
+use crate::trash::trash_command;
+use crate::tree::tree_command;
+use crate::options::Options;
+use arboard::Clipboard;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::env;
+use std::io::{BufRead, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Commands that modify the filesystem, disabled by `--read-only`.
+const MUTATING_COMMANDS: &[&str] = &["touch", "mkdir", "rm", "rmdir", "trash", "rename", "cp"];
+
+/// Commands that change the working directory, which belongs to the whole process, so a
+/// background job running one would move the foreground session too.
+const DIRECTORY_COMMANDS: &[&str] = &["cd", "pushd", "popd"];
+
+/// Commands that ask before going ahead when patterns matched more files than
+/// `--glob-confirm` allows, since an unexpected match is hard to undo.
+const CONFIRMED_COMMANDS: &[&str] = &["rm", "rmdir"];
+
+/// How many files patterns may match before a command in `CONFIRMED_COMMANDS` asks first,
+/// unless `--glob-confirm` says otherwise.
+const DEFAULT_GLOB_CONFIRM: usize = 10;
+
+/// Number of output lines kept in the scrollback, unless the profile says otherwise.
+const DEFAULT_MAX_OUTPUT_LINES: usize = 1000;
+
+/// How many lines of output a command may show on a terminal, unless `--output-limit` or
+/// the configuration says otherwise.
+const DEFAULT_OUTPUT_LIMIT: usize = 10_000;
+
+/// Distance between the tab stops that tabs shown on screen and `expand` reach, unless
+/// `--tab-size` or the configuration says otherwise.
+const DEFAULT_TAB_SIZE: usize = 8;
+
+/// The prompt before the input line, unless the configuration says otherwise.
+const DEFAULT_PROMPT: &str = "> {cwd} ";
+
+/// Characters the shell splits on, expands, or unquotes, which a `\` outside quotes makes
+/// literal.
+const SPECIAL_CHARACTERS: &str = "\"'\\$`&;|<>()*?[]{}#~";
+
+/// Aliases defined when the emulator starts, before the configuration's aliases and the
+/// startup file, either of which may replace or `unalias` them.
+pub const DEFAULT_ALIASES: &[(&str, &str)] = &[("ll", "ls -l"), ("la", "ls -la"), ("lt", "ls -lt")];
+
+/// Every command handled by `handle_command` itself, as reported by `which`.
+pub const BUILTIN_COMMANDS: &[&str] = &[
+    ".", "alias", "awk", "bc", "cal", "cat", "cd", "clear", "comm", "copy", "cp", "cut", "dirs", "echo", "env", "exit",
+    "expand", "export", "expr", "factor", "file", "find", "fold", "grep", "head", "history", "join", "less", "ls",
+    "mkdir", "paste", "popd", "pushd", "pwd", "rename", "rm", "rmdir", "sed", "set", "sleep", "sort", "source", "stats",
+    "tail", "time", "touch", "tr", "trash", "tree", "type", "unalias", "unexpand", "uniq", "unset", "uptime", "wc",
+    "which", "xargs",
+];
+
+/// The flags each built-in command takes, offered by Tab completion after a `-`.
+pub const COMMAND_FLAGS: &[(&str, &[&str])] = &[
+    ("alias", &["--save"]),
+    ("awk", &["-F"]),
+    ("cal", &["-y"]),
+    ("cat", &["-s", "--lf", "--crlf"]),
+    ("comm", &["-1", "-2", "-3"]),
+    ("cp", &["-p", "-v", "--preserve", "--verbose", "--progress"]),
+    ("cut", &["-f", "-d", "-c"]),
+    ("echo", &["-n", "-e", "-E"]),
+    ("expand", &["-t"]),
+    ("find", &["-name", "-type", "-mtime", "-size", "-exec", "--gitignore"]),
+    ("fold", &["-w", "-s"]),
+    ("grep", &["-i", "-v", "-c", "-E", "-r", "--include", "--exclude", "--gitignore"]),
+    ("head", &["-n"]),
+    ("join", &["-1", "-2", "-t", "-a"]),
+    (
+        "ls",
+        &["-a", "-l", "-1", "-t", "-S", "-r", "-Q", "-L", "--group-directories-first", "--quote-names", "--dereference"],
+    ),
+    ("mkdir", &["-v", "--verbose"]),
+    ("paste", &["-s", "-d"]),
+    ("pwd", &["-L", "-P"]),
+    ("rm", &["-r", "-f", "-v", "--recursive", "--force", "--verbose"]),
+    ("rmdir", &["-v", "--verbose"]),
+    ("sed", &["-i"]),
+    ("set", &["-o", "+o", "-e", "+e", "-C", "+C", "-x", "+x"]),
+    ("sort", &["-n", "-r", "-u", "-s", "-k"]),
+    ("tail", &["-n", "-f"]),
+    ("touch", &["-d", "-t"]),
+    ("tr", &["-d"]),
+    ("trash", &["--list", "--restore"]),
+    ("tree", &["--gitignore"]),
+    ("type", &["-a"]),
+    ("unalias", &["-a"]),
+    ("unexpand", &["-t"]),
+    ("uniq", &["-c"]),
+    ("wc", &["-l", "-w", "-c"]),
+    ("xargs", &["-n", "-I"]),
+];
+
+/// State shared by the commands of an emulator session.
+pub struct ShellContext {
+    /// Startup settings such as the jail root.
+    pub options: Options,
+    /// Filesystem that commands read and write, shared with background jobs.
+    pub fs: Arc<dyn FileSystem>,
+    /// Lines shown in the scrollback buffer.
+    pub output_lines: VecDeque<String>,
+    /// Output of the most recent command line, used by `copy`.
+    pub last_output: String,
+    /// Clipboard handle, kept open so copied text stays available on X11.
+    pub clipboard: Option<Clipboard>,
+    /// Set by the `exit` command to end the session.
+    pub exit_requested: bool,
+    /// Number of `source` commands currently running, to stop runaway recursion.
+    pub source_depth: usize,
+    /// Aliases defined with `alias`, by name.
+    pub aliases: BTreeMap<String, String>,
+    /// Directories saved by `pushd`, most recent last.
+    pub dir_stack: Vec<PathBuf>,
+    /// Set by `less` so the interactive screen shows the command line's output in the pager
+    /// rather than the scrollback. Other modes print the output as usual.
+    pub page_output: bool,
+    /// A file `less` left open for the pager to read as it scrolls, instead of output.
+    pub paged_file: Option<Box<dyn BufRead + Send>>,
+    /// Whether commands run in the interactive screen, where `less` can page a file lazily.
+    pub interactive: bool,
+    /// Whether commands run in a background job started with `&`.
+    pub background: bool,
+    /// When the emulator session started, for `uptime` and `stats`.
+    pub started: Instant,
+    /// How many times each command has run this session, for `stats`.
+    pub command_counts: HashMap<String, u32>,
+    /// The transcript that command lines and their output are added to (`--log`).
+    pub log: Option<SessionLog>,
+    /// The startup file that `alias --save` writes to, if the user has a home directory.
+    pub rc_path: Option<PathBuf>,
+    /// The file the history is saved to when the interactive screen closes, which
+    /// `history -c` empties too. Unset with `--no-history`.
+    pub history_path: Option<PathBuf>,
+    /// A command held back until the user answers the question it asks on the interactive
+    /// screen.
+    pub confirmation: Option<Confirmation>,
+    /// The calculator `bc` started without input, which takes the lines typed on the
+    /// interactive screen until `quit`.
+    pub calculator: Option<Calculator>,
+    /// Shows how far a long command such as `cp --progress` has got, in place of the last
+    /// message it showed; an empty message clears it. Set by front ends that can show it.
+    pub progress: Option<ProgressReporter>,
+    /// A file `tail -f` keeps showing as it grows, until the user stops it.
+    pub follow: Option<Follow>,
+    /// Command lines typed on the interactive screen, oldest first, for `history` and `!!`.
+    pub history: Vec<String>,
+    /// Behaviors turned on and off with `set`.
+    pub shell_options: ShellOptions,
+    /// Commands about to run, fully expanded, that `set -x` shows before the output of the
+    /// command line they belong to.
+    pub trace: Vec<String>,
+    /// Exit status of the last command run, for `$?` and the prompt's `{status}`.
+    pub last_exit_code: i32,
+    /// Width in cells of the terminal a command's output goes to, which `ls` fills with
+    /// columns. Set by front ends that show output on a terminal; unset while the output
+    /// is piped, redirected, or substituted.
+    pub terminal_width: Option<usize>,
+    /// The working directory as shown in the prompt. Kept up to date by `cd`, `pushd`, and
+    /// `popd`, so the screen need not ask the filesystem on every redraw.
+    pub current_dir: String,
+}
+
+impl ShellContext {
+    /// Creates a context for a background job that shares this session's settings,
+    /// filesystem, and aliases.
+    pub fn for_background(&self) -> ShellContext {
+        ShellContext {
+            options: self.options.clone(),
+            fs: Arc::clone(&self.fs),
+            aliases: self.aliases.clone(),
+            shell_options: self.shell_options.clone(),
+            started: self.started,
+            background: true,
+            ..Default::default()
+        }
+    }
+
+    /// Reads the working directory into `current_dir` after it may have changed.
+    pub fn refresh_current_dir(&mut self) {
+        self.current_dir = match self.fs.current_dir() {
+            Ok(path) => path.display().to_string(),
+            Err(_) => "Unknown Directory".to_string(),
+        };
+    }
+
+    /// Returns the prompt shown before the input line, with `{cwd}` replaced by the
+    /// working directory and `{status}` by the last command's exit status in the error
+    /// color, followed by a space, when it failed.
+    pub fn prompt(&self) -> String {
+        let status = match self.last_exit_code {
+            0 => String::new(),
+            code => self.options.theme.prompt_error(format!("{} ", code)),
+        };
+        let template = self.options.prompt.as_deref().unwrap_or(DEFAULT_PROMPT);
+        template.replace("{cwd}", &self.current_dir).replace("{status}", &status)
+    }
+
+    /// Returns how many lines of output the scrollback keeps.
+    pub fn max_output_lines(&self) -> usize {
+        self.options.max_output_lines.unwrap_or(DEFAULT_MAX_OUTPUT_LINES)
+    }
+
+    /// Returns the distance between tab stops.
+    pub fn tab_size(&self) -> usize {
+        self.options.tab_size.unwrap_or(DEFAULT_TAB_SIZE)
+    }
+
+    /// Returns how many lines of output the running command may show, or `None` if its
+    /// output does not go to a terminal or there is no limit.
+    pub fn line_limit(&self) -> Option<usize> {
+        let limit = self.options.output_limit.unwrap_or(DEFAULT_OUTPUT_LIMIT);
+        self.terminal_width.and(Some(limit)).filter(|&limit| limit > 0)
+    }
+
+    /// Appends text to the scrollback one line at a time, with tabs turned into spaces,
+    /// dropping the oldest lines beyond `max_output_lines`.
+    pub fn push_output(&mut self, text: String) {
+        let text = tabs_to_spaces(&text, self.tab_size());
+        self.output_lines.extend(text.lines().map(str::to_string));
+        let excess = self.output_lines.len().saturating_sub(self.max_output_lines());
+        self.output_lines.drain(..excess);
+    }
+}
+
+impl Default for ShellContext {
+    fn default() -> Self {
+        ShellContext {
+            options: Options::default(),
+            fs: Arc::new(OsFileSystem),
+            output_lines: VecDeque::new(),
+            last_output: String::new(),
+            clipboard: None,
+            exit_requested: false,
+            source_depth: 0,
+            aliases: BTreeMap::new(),
+            dir_stack: Vec::new(),
+            page_output: false,
+            paged_file: None,
+            interactive: false,
+            background: false,
+            calculator: None,
+            started: Instant::now(),
+            command_counts: HashMap::new(),
+            log: None,
+            rc_path: None,
+            history_path: None,
+            confirmation: None,
+            progress: None,
+            follow: None,
+            history: Vec::new(),
+            shell_options: ShellOptions::default(),
+            trace: Vec::new(),
+            last_exit_code: 0,
+            terminal_width: None,
+            current_dir: String::new(),
+        }
+    }
+}
+
+/// Behaviors turned on with `set -o <name>`, or `set -<letter>`, and off with `+` instead.
+#[derive(Clone, Debug, Default)]
+pub struct ShellOptions {
+    /// `errexit` (`-e`): a script, or any command line outside the interactive screen,
+    /// stops at the first command that fails, even with `--keep-going`.
+    pub errexit: bool,
+    /// `noclobber` (`-C`): `>` refuses to replace a file that exists, unless written `>|`.
+    pub noclobber: bool,
+    /// `xtrace` (`-x`): each command is shown, after `+ `, once its aliases, variables,
+    /// substitutions, braces, and patterns are expanded.
+    pub xtrace: bool,
+}
+
+impl ShellOptions {
+    /// The letter and name of each option, in the order `set` lists them.
+    pub const NAMES: &[(char, &str)] = &[('e', "errexit"), ('C', "noclobber"), ('x', "xtrace")];
+
+    /// Returns whether the option called `name` is on, or `None` if there is no such option.
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "errexit" => Some(self.errexit),
+            "noclobber" => Some(self.noclobber),
+            "xtrace" => Some(self.xtrace),
+            _ => None,
+        }
+    }
+
+    /// Turns the option called `name` on or off, returning false if there is no such option.
+    pub fn set(&mut self, name: &str, on: bool) -> bool {
+        let option = match name {
+            "errexit" => &mut self.errexit,
+            "noclobber" => &mut self.noclobber,
+            "xtrace" => &mut self.xtrace,
+            _ => return false,
+        };
+        *option = on;
+        true
+    }
+}
+
+/// Shows a progress message on whatever screen the session runs on.
+pub type ProgressReporter = Box<dyn Fn(&str) + Send>;
+
+/// A command waiting for the user to confirm it, such as an `rm` whose patterns matched
+/// many files.
+pub struct Confirmation {
+    /// The question shown in the prompt.
+    pub question: String,
+    /// The command's words, already expanded, so it acts on exactly the files it asked about.
+    words: Vec<Vec<String>>,
+}
+
+/// The result of running a command.
+#[derive(Debug, Default)]
+pub struct CommandOutput {
+    /// Normal output, passed on to the next pipeline stage.
+    pub stdout: String,
+    /// Error messages, which are always shown rather than piped.
+    pub stderr: String,
+    /// Exit status: 0 on success, non-zero on failure.
+    pub exit_code: i32,
+}
+
+impl CommandOutput {
+    /// Creates a successful result with the given output.
+    pub fn success(stdout: impl Into<String>) -> Self {
+        CommandOutput { stdout: stdout.into(), ..Default::default() }
+    }
+
+    /// Creates a failed result (exit status 1) with the given error message.
+    pub fn error(message: impl Into<String>) -> Self {
+        CommandOutput { stderr: message.into(), exit_code: 1, ..Default::default() }
+    }
+
+    /// Returns the text shown on screen: the output followed by any errors in the theme's
+    /// error color.
+    pub fn display(&self, theme: &Theme) -> String {
+        let mut lines: Vec<String> = self.stdout.lines().map(str::to_string).collect();
+        lines.extend(self.stderr.lines().map(|line| theme.error(line)));
+        lines.join("\n")
+    }
+}
+
+/// Runs a full command line: each `;`-separated command in turn, with its pipeline.
+///
+/// Output from every command is combined, and the exit status is that of the last one.
+pub fn execute(line: &str, ctx: &mut ShellContext) -> CommandOutput {
+    execute_with_heredoc(line, None, ctx)
+}
+
+/// Runs a full command line like [`execute`], feeding `heredoc` as standard input to the
+/// stage that has a `<<` redirection.
+///
+/// The line and its result are added to the session's transcript, if there is one. Lines
+/// run on its behalf, such as those of a `source`d file, are not added separately.
+pub fn execute_with_heredoc(line: &str, heredoc: Option<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    let Some(mut log) = ctx.log.take() else {
+        return run_line(line, heredoc, ctx);
+    };
+    let mut output = run_line(line, heredoc, ctx);
+    match log.record(line, &output) {
+        Ok(()) => ctx.log = Some(log),
+        Err(e) => {
+            let message = format!("Error: could not write to the log, so logging has stopped: {}", e);
+            output.stderr = join_output(&[std::mem::take(&mut output.stderr), message]);
+        }
+    }
+    output
+}
+
+/// Expands the history references in a line typed on the interactive screen, such as `!!`
+/// or `^old^new^`, and adds the line to the history. Returns the line to run and whether
+/// expansion changed it, in which case the screen shows it before running it, as bash does.
+/// A reference that cannot be expanded is an error, and the line is not added.
+///
+/// As in bash, the `HISTCONTROL` variable, or else the configuration's `history_control`,
+/// can leave lines out: `ignorespace` those starting with a space, `ignoredups` those the
+/// same as the line before, and `ignoreboth` both. Settings are separated by `:`.
+pub fn record_history(line: &str, ctx: &mut ShellContext) -> Result<(String, bool), String> {
+    let (expanded_line, expanded) = match expand_history(line, &ctx.history)? {
+        Some(expanded) => (expanded, true),
+        None => (line.to_string(), false),
+    };
+    let control = env::var("HISTCONTROL").ok().or_else(|| ctx.options.history_control.clone()).unwrap_or_default();
+    let ignores = |setting| control.split(':').any(|value| value == setting || value == "ignoreboth");
+    let skipped = (ignores("ignorespace") && line.starts_with(' '))
+        || (ignores("ignoredups") && ctx.history.last() == Some(&expanded_line));
+    if !skipped {
+        ctx.history.push(expanded_line.clone());
+    }
+    Ok((expanded_line, expanded))
+}
+
+/// Runs each `;`-separated command of a line in turn.
+fn run_line(line: &str, heredoc: Option<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    let mut exit_code = 0;
+
+    for command in split_unquoted(line, ';') {
+        if command.trim().is_empty() {
+            continue;
+        }
+
+        let output = match strip_time_prefix(command) {
+            Some(pipeline) => time_command(pipeline, heredoc, ctx),
+            None => run_pipeline(command, heredoc, ctx),
+        };
+        // Traced commands come before the output, but never go through pipes or into files
+        for traced in ctx.trace.drain(..) {
+            results.push(format!("+ {}\n", traced));
+        }
+        if !output.stdout.is_empty() {
+            results.push(output.stdout);
+        }
+        if !output.stderr.is_empty() {
+            errors.push(output.stderr);
+        }
+        exit_code = output.exit_code;
+        ctx.last_exit_code = exit_code;
+
+        // Commands after one waiting for confirmation are dropped along with it if it is declined
+        if ctx.exit_requested || ctx.confirmation.is_some() {
+            break;
+        }
+        if exit_code != 0 && ctx.shell_options.errexit && !ctx.interactive {
+            break;
+        }
+    }
+
+    CommandOutput { stdout: join_output(&results), stderr: errors.join("\n"), exit_code }
+}
+
+/// Returns the pipeline after a leading `time`, which is a prefix timing the whole pipeline
+/// rather than a command of its own, if `command` starts with one.
+fn strip_time_prefix(command: &str) -> Option<&str> {
+    let pipeline = command.trim_start().strip_prefix("time")?;
+    (pipeline.is_empty() || pipeline.starts_with(char::is_whitespace)).then_some(pipeline)
+}
+
+/// Merges the results of running a command once per argument. The exit status is that of
+/// the last failure, or 0 if every run succeeded.
+pub fn combine_outputs(outputs: Vec<CommandOutput>) -> CommandOutput {
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    let mut exit_code = 0;
+
+    for output in outputs {
+        if !output.stdout.is_empty() {
+            results.push(output.stdout);
+        }
+        if !output.stderr.is_empty() {
+            errors.push(output.stderr);
+        }
+        if output.exit_code != 0 {
+            exit_code = output.exit_code;
+        }
+    }
+
+    CommandOutput { stdout: join_output(&results), stderr: errors.join("\n"), exit_code }
+}
+
+/// Joins the standard output of several commands, starting each on its own line.
+pub fn join_output(chunks: &[String]) -> String {
+    let mut joined = String::new();
+    for chunk in chunks {
+        if !joined.is_empty() && !joined.ends_with('\n') {
+            joined.push('\n');
+        }
+        joined.push_str(chunk);
+    }
+    joined
+}
+
+/// Runs the lines of a script through the pipeline in the given session.
+///
+/// Blank lines and `#` comments are skipped, and a line that starts a here-document takes
+/// its body from the lines after it. `report` receives the line number, text, and result
+/// of each command. Stops after the first failing command unless `keep_going` is set and
+/// `set -e` is not, and returns the exit status of the last command run.
+pub fn run_script(
+    script: &str,
+    keep_going: bool,
+    ctx: &mut ShellContext,
+    mut report: impl FnMut(usize, &str, &CommandOutput),
+) -> i32 {
+    let mut exit_code = 0;
+    let mut lines = script.lines().enumerate();
+
+    while let Some((index, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let heredoc = read_heredoc(line, lines.by_ref().map(|(_, line)| line));
+        let output = execute_with_heredoc(line, heredoc.as_deref(), ctx);
+        report(index + 1, line, &output);
+        exit_code = output.exit_code;
+
+        if ctx.exit_requested || (exit_code != 0 && (!keep_going || ctx.shell_options.errexit)) {
+            break;
+        }
+    }
+
+    exit_code
+}
+
+/// Runs a command line, feeding the output of each `|`-separated stage into the next.
+///
+/// Errors from every stage are collected, and the exit status is that of the last stage.
+pub fn run_pipeline(line: &str, heredoc: Option<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    let mut input: Option<String> = None;
+    let mut errors = Vec::new();
+    let mut output = CommandOutput::default();
+
+    let stages = split_unquoted(line, '|');
+    let last = stages.len() - 1;
+    for (index, stage) in stages.into_iter().enumerate() {
+        let (stage, redirections) = match parse_redirections(stage) {
+            Ok(parsed) => parsed,
+            Err(e) => return CommandOutput::error(e),
+        };
+        if stage.trim().is_empty() {
+            return CommandOutput::error("Error: Missing command in pipeline.");
+        }
+        if ctx.options.read_only && (redirections.stdout.is_some() || redirections.stderr.is_some()) {
+            return CommandOutput::error("Error: Output redirection is disabled in read-only mode.");
+        }
+
+        let file_input = match &redirections.stdin {
+            Some(source) => match read_redirect_input(source, ctx) {
+                Ok(text) => Some(text),
+                Err(e) => return CommandOutput::error(e),
+            },
+            None => None,
+        };
+        let stdin = match (&redirections.heredoc, &file_input) {
+            (Some(_), _) => Some(heredoc.unwrap_or("")),
+            (None, Some(text)) => Some(text.as_str()),
+            (None, None) => input.as_deref(),
+        };
+        // Only the last stage's output reaches the terminal, unless it too is redirected
+        let redirects_stdout = redirections.stdout.is_some() || redirections.stdout_to_stderr;
+        let terminal_width = ctx.terminal_width;
+        if index < last || redirects_stdout {
+            ctx.terminal_width = None;
+        }
+        output = handle_command(&stage, stdin, ctx);
+        ctx.terminal_width = terminal_width;
+        if index < last || redirects_stdout {
+            // The file `less` left for the pager is needed as text by what follows
+            if let Some(mut file) = ctx.paged_file.take() {
+                let mut bytes = Vec::new();
+                match file.read_to_end(&mut bytes) {
+                    Ok(_) => output.stdout = String::from_utf8_lossy(&bytes).into_owned(),
+                    Err(e) => errors.push(format!("Error reading file: {}", e)),
+                }
+            }
+        }
+        // Output for a terminal stops at the limit, which walks such as `find` check as they go
+        if let Some(limit) = ctx.line_limit().filter(|_| index == last && !redirects_stdout) {
+            if truncate_lines(&mut output.stdout, limit) {
+                errors.push(format!("[output truncated at {} lines]", limit));
+            }
+        }
+        if redirections.stdout_to_stderr {
+            // Error output is shown line by line like standard output, so the final newline
+            // ending the last line goes too, rather than showing as a blank line
+            let stdout = std::mem::take(&mut output.stdout);
+            let moved = stdout.strip_suffix('\n').unwrap_or(&stdout);
+            output.stderr = join_streams(std::mem::take(&mut output.stderr), moved);
+        }
+        if redirections.stderr_to_stdout {
+            output.stdout = join_streams(std::mem::take(&mut output.stdout), &output.stderr);
+            output.stderr.clear();
+        }
+        if let Some((target, mode)) = &redirections.stderr {
+            let mut text = std::mem::take(&mut output.stderr);
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            if let Err(e) = redirect_output(target, *mode, &text, ctx) {
+                errors.push(e);
+                output.exit_code = 1;
+            }
+        } else if !output.stderr.is_empty() {
+            errors.push(std::mem::take(&mut output.stderr));
+        }
+        if let Some((target, mode)) = &redirections.stdout {
+            let text = std::mem::take(&mut output.stdout);
+            if let Err(e) = redirect_output(target, *mode, &text, ctx) {
+                errors.push(e);
+                output.exit_code = 1;
+            }
+        }
+        // Colors are only meaningful on screen, so later stages see plain text
+        input = Some(strip_ansi(&output.stdout));
+    }
+
+    output.stderr = errors.join("\n");
+    output
+}
+
+/// Cuts `text` off after its first `limit` lines, returning true if anything was cut.
+fn truncate_lines(text: &mut String, limit: usize) -> bool {
+    match text.match_indices('\n').nth(limit - 1) {
+        Some((index, _)) if index + 1 < text.len() => {
+            text.truncate(index + 1);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Puts the text of `second` after `first`, on a line of its own, for `2>&1` and `>&2`.
+fn join_streams(mut first: String, second: &str) -> String {
+    if !first.is_empty() && !second.is_empty() && !first.ends_with('\n') {
+        first.push('\n');
+    }
+    first.push_str(second);
+    first
+}
+
+/// Reads the file named by a `<` redirection.
+fn read_redirect_input(source: &str, ctx: &ShellContext) -> Result<String, String> {
+    let path = expand_path(ctx, source)?;
+    ctx.fs.read_to_string(&path).map_err(|e| format!("Error reading file '{}': {}", source, e))
+}
+
+/// Writes a stage's standard output or error output to the file named by a `>`, `>>`,
+/// `>|`, `2>`, or `2>>` redirection. With `noclobber` set, `>` and `2>` refuse to replace a
+/// file that exists.
+fn redirect_output(target: &str, mode: WriteMode, text: &str, ctx: &ShellContext) -> Result<(), String> {
+    let path = expand_path(ctx, target)?;
+    if ctx.shell_options.noclobber && mode == WriteMode::Replace && ctx.fs.metadata(&path).is_ok() {
+        return Err(format!("Error: '{}' exists; use '>|' to overwrite it (noclobber is set).", target));
+    }
+    let text = strip_ansi(text);
+    let result = if mode == WriteMode::Append { ctx.fs.append(&path, &text) } else { ctx.fs.write(&path, &text) };
+    result.map_err(|e| format!("Error writing to file '{}': {}", target, e))
+}
+
+/// Splits a command line on `separator` characters that are not inside quotes.
+///
+/// Separators inside `$(...)` or backtick command substitutions, or escaped with a `\`, are
+/// also left alone.
+pub fn split_unquoted(line: &str, separator: char) -> Vec<&str> {
+    split_unquoted_by(line, |c| c == separator)
+}
+
+/// Splits a command into its words on whitespace that is not inside quotes, keeping the
+/// quotes in each word. Braces and patterns are expanded in these words before
+/// `remove_quotes` turns them into the arguments a command gets.
+pub fn split_words(command: &str) -> Vec<&str> {
+    split_unquoted_by(command, char::is_whitespace).into_iter().filter(|word| !word.is_empty()).collect()
+}
+
+/// Removes the quotes from a word as a shell does: text inside single quotes is taken as it
+/// is, and a `\` before whitespace or a special character makes it literal. Other
+/// backslashes are kept, so `echo -e a\nb` still reaches `echo` as typed. Inside double
+/// quotes a `\` only escapes `"`, `\`, `$`, and `` ` ``.
+pub fn remove_quotes(word: &str) -> String {
+    let mut text = String::with_capacity(word.len());
+    let mut quote: Option<char> = None;
+    let mut chars = word.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '\\') => match chars.next_if(|&next| next.is_whitespace() || SPECIAL_CHARACTERS.contains(next)) {
+                Some(escaped) => text.push(escaped),
+                None => text.push(c),
+            },
+            (Some('"'), '\\') => match chars.next_if(|next| "\"\\$`".contains(*next)) {
+                Some(escaped) => text.push(escaped),
+                None => text.push(c),
+            },
+            _ => text.push(c),
+        }
+    }
+
+    text
+}
+
+/// Puts a word in single quotes if the shell would otherwise split, expand, or unquote it,
+/// so that a command line built from words, as by `xargs`, runs them as they are.
+pub fn quote_word(word: &str) -> String {
+    let special = |c: char| c.is_whitespace() || SPECIAL_CHARACTERS.contains(c);
+    if !word.is_empty() && !word.contains(special) {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+/// Splits a command line on the characters matching `is_separator` that are not inside
+/// quotes or command substitutions and are not escaped.
+fn split_unquoted_by(line: &str, is_separator: impl Fn(char) -> bool) -> Vec<&str> {
+    let mut stages = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut depth = 0;
+    let mut start = 0;
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        match (quote, c) {
+            (None, '\\') | (Some('"'), '\\') => {
+                chars.next();
+            }
+            (None, '"') | (None, '\'') | (None, '`') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '$') if chars.next_if(|&(_, next)| next == '(').is_some() => depth += 1,
+            (None, ')') if depth > 0 => depth -= 1,
+            // The `|` of a `>|` redirection does not start a new pipeline stage
+            (None, '>') if chars.next_if(|&(_, next)| next == '|').is_some() => {}
+            (None, _) if is_separator(c) && depth == 0 => {
+                stages.push(&line[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    stages.push(&line[start..]);
+
+    stages
+}
+
+/// Checks whether a command line continues onto the next line because it ends with a `\`
+/// or leaves a quote open. If so, returns the text to prepend to the next line: the line
+/// without its trailing `\`, or the line and a newline inside the open quote.
+pub fn continue_line(line: &str) -> Option<String> {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for c in line.chars() {
+        match (quote, c) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                escaped = !escaped;
+                continue;
+            }
+            _ if escaped => {}
+            (None, '"') | (None, '\'') | (None, '`') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            _ => {}
+        }
+        escaped = false;
+    }
+
+    match quote {
+        Some(_) => Some(format!("{}\n", line)),
+        None if escaped => Some(line[..line.len() - 1].to_string()),
+        None => None,
+    }
+}
+
+/// Handles the execution of commands entered by the user.
+///
+/// `stdin` holds the output of the previous pipeline stage, if any.
+pub fn handle_command(command: &str, stdin: Option<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    if command.contains("$(") || command.contains('`') {
+        return match substitute_commands(command, ctx) {
+            Ok((expanded, mut errors)) => {
+                let mut output = run_command(&expanded, stdin, ctx);
+                if !output.stderr.is_empty() {
+                    errors.push(output.stderr);
+                }
+                output.stderr = errors.join("\n");
+                output
+            }
+            Err(e) => CommandOutput::error(e),
+        };
+    }
+    run_command(command, stdin, ctx)
+}
+
+/// Runs a single command whose substitutions have already been expanded.
+fn run_command(command: &str, stdin: Option<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    let command = expand_variables(&expand_alias(command, &ctx.aliases), ctx.last_exit_code);
+    // Each word as typed, expanded into the words its braces and patterns give
+    let mut patterns = Vec::new();
+    let mut matched = 0;
+    let mut words: Vec<Vec<String>> = Vec::new();
+    for word in split_words(&command) {
+        let expanded = match expand_braces(word) {
+            Ok(expanded) => expanded,
+            Err(e) => return CommandOutput::error(e),
+        };
+        let word = expanded.into_iter().flat_map(|word| {
+            let paths = expand_glob(&word, ctx);
+            if paths == [word.as_str()] {
+                // Quotes are removed last, so quoted braces and patterns stay as typed
+                return vec![remove_quotes(&word)];
+            }
+            matched += paths.len();
+            patterns.push(word);
+            paths
+        });
+        words.push(word.collect());
+    }
+    let cmd = words.first().and_then(|word| word.first()).map_or("", String::as_str);
+    if ctx.shell_options.xtrace {
+        ctx.trace.push(words.iter().flatten().map(String::as_str).collect::<Vec<_>>().join(" "));
+    }
+
+    if ctx.options.read_only && MUTATING_COMMANDS.contains(&cmd) {
+        return CommandOutput::error(format!("Error: '{}' is disabled in read-only mode.", cmd));
+    }
+    if ctx.background && DIRECTORY_COMMANDS.contains(&cmd) {
+        return CommandOutput::error(format!("Error: '{}' cannot change directory in a background job.", cmd));
+    }
+    if !cmd.is_empty() {
+        *ctx.command_counts.entry(cmd.to_string()).or_default() += 1;
+    }
+
+    let limit = ctx.options.glob_confirm.unwrap_or(DEFAULT_GLOB_CONFIRM);
+    if CONFIRMED_COMMANDS.contains(&cmd) && limit > 0 && matched > limit {
+        let matches = format!("{}: {} matched {} files", cmd, patterns.join(" "), matched);
+        if !ctx.interactive {
+            return CommandOutput::error(format!(
+                "Error: {}, more than {}. Run it on the interactive screen to confirm, or raise the limit with --glob-confirm.",
+                matches, limit
+            ));
+        }
+        let question = format!("{}. Go ahead? [y/N] ", matches);
+        ctx.confirmation = Some(Confirmation { question, words });
+        return CommandOutput::default();
+    }
+
+    dispatch(&words, stdin, ctx)
+}
+
+/// Answers the question of the command waiting for confirmation: `y` or `yes` runs it, and
+/// anything else leaves it undone.
+pub fn answer_confirmation(answer: &str, ctx: &mut ShellContext) -> CommandOutput {
+    let Some(confirmation) = ctx.confirmation.take() else {
+        return CommandOutput::default();
+    };
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => dispatch(&confirmation.words, None, ctx),
+        _ => CommandOutput::success("Nothing was done."),
+    }
+}
+
+/// Runs the built-in command, or program, named by the first of `words`.
+fn dispatch(words: &[Vec<String>], stdin: Option<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    let mut parts = words.iter().flatten().map(String::as_str);
+    let cmd = parts.next().unwrap_or("");
+
+    match cmd {
+        "ls" => list_directory(ctx, parts.collect()),
+        "pwd" => current_directory(ctx, parts.collect()),
+        "cat" => {
+            let args: Vec<&str> = parts.collect();
+            cat_command(args, stdin, ctx)
+        }
+        "echo" => {
+            let args: Vec<&str> = parts.collect();
+            CommandOutput::success(echo_command(args))
+        }
+        "touch" => touch_command(ctx, words.get(1..).unwrap_or_default()),
+        "clear" => {
+            // The next redraw repaints the now-empty screen
+            ctx.output_lines.clear();
+            CommandOutput::default()
+        }
+        "grep" => {
+            let args: Vec<&str> = parts.collect();
+            grep_command(args, stdin, ctx)
+        }
+        "file" => file_command(parts.collect(), ctx),
+        "find" => find_command(parts.collect(), ctx),
+        "tree" => tree_command(parts.collect(), ctx),
+        "cal" => cal_command(parts.collect(), ctx),
+        "factor" => factor_command(parts.collect(), stdin),
+        "expr" => expr_command(parts.collect()),
+        "bc" => bc_command(parts.collect(), stdin, ctx),
+        "less" => less_command(parts.collect(), stdin, ctx),
+        "wc" => wc_command(parts.collect(), stdin, ctx),
+        "sort" => sort_command(parts.collect(), stdin, ctx),
+        "uniq" => uniq_command(parts.collect(), stdin, ctx),
+        "comm" => comm_command(parts.collect(), stdin, ctx),
+        "paste" => paste_command(parts.collect(), stdin, ctx),
+        "join" => join_command(parts.collect(), stdin, ctx),
+        "fold" => fold_command(parts.collect(), stdin, ctx),
+        "expand" | "unexpand" => expand_command(cmd, parts.collect(), stdin, ctx),
+        "cut" => cut_command(parts.collect(), stdin, ctx),
+        "tr" => tr_command(parts.collect(), stdin),
+        "sed" => sed_command(parts.collect(), stdin, ctx),
+        "head" | "tail" => head_tail_command(cmd, parts.collect(), stdin, ctx),
+        "xargs" => {
+            let args: Vec<&str> = parts.collect();
+            xargs_command(args, stdin.unwrap_or(""), ctx)
+        }
+        "copy" => {
+            let file_name = parts.next().unwrap_or("");
+            copy_command(file_name, stdin, ctx)
+        }
+        "mkdir" => mkdir_command(parts.collect(), ctx),
+        "rm" => rm_command(parts.collect(), ctx),
+        "rmdir" => rmdir_command(parts.collect(), ctx),
+        "trash" => trash_command(parts.collect(), ctx),
+        "rename" => rename_command(parts.collect(), ctx),
+        "cp" => cp_command(parts.collect(), ctx),
+        "cd" => {
+            let mut dir_name = parts.next().unwrap_or("");
+            if dir_name == "--" {
+                dir_name = parts.next().unwrap_or("");
+            }
+            change_directory(ctx, dir_name)
+        }
+        "pushd" => {
+            let dir_name = parts.next();
+            pushd_command(ctx, dir_name)
+        }
+        "popd" => popd_command(ctx),
+        "dirs" => dirs_command(ctx),
+        "uptime" => uptime_command(ctx),
+        "stats" => stats_command(ctx),
+        "sleep" => {
+            let seconds = parts.next().unwrap_or("");
+            sleep_command(seconds, ctx)
+        }
+        "source" | "." => {
+            let file_name = parts.next().unwrap_or("");
+            source_command(file_name, ctx)
+        }
+        "alias" => alias_command(parts.collect(), ctx),
+        "export" => export_command(parts.collect()),
+        "awk" => awk_command(parts.collect(), stdin, ctx),
+        "history" => history_command(parts.collect(), ctx),
+        "unset" => unset_command(parts.collect()),
+        "set" => set_command(parts.collect(), ctx),
+        "env" => env_command(parts.collect()),
+        "unalias" => {
+            let names: Vec<&str> = parts.collect();
+            unalias_command(names, ctx)
+        }
+        "which" => {
+            let names: Vec<&str> = parts.collect();
+            which_command(names, ctx)
+        }
+        "type" => {
+            let args: Vec<&str> = parts.collect();
+            type_command(args, ctx)
+        }
+        "exit" => {
+            ctx.exit_requested = true;
+            CommandOutput::default()
+        }
+        _ if ctx.options.allow_external && !cmd.is_empty() => external_command(cmd, parts.collect(), stdin, ctx),
+        _ => CommandOutput { exit_code: 127, ..CommandOutput::error(format!("Unknown command: {}", cmd)) },
+    }
+}
+
+/// Removes ANSI escape sequences (such as colors) from `text`.
+pub fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            plain.push(c);
+            continue;
+        }
+        // Skip a CSI sequence: ESC '[' parameters... final byte in '@'..='~'
+        if chars.next() == Some('[') {
+            for next in chars.by_ref() {
+                if ('@'..='~').contains(&next) {
+                    break;
+                }
+            }
+        }
+    }
+
+    plain
+}
+
+/// Expands a leading `~` in a path argument and, in jail mode, rejects paths that
+/// resolve outside the jail root.
+///
+/// Inside a jail, `~` refers to the jail root. The returned path is not canonicalized,
+/// so commands still act on symlinks themselves rather than on their targets.
+pub fn expand_path(ctx: &ShellContext, arg: &str) -> Result<PathBuf, String> {
+    if ctx.options.safe {
+        validate_path(arg)?;
+    }
+    let home = match &ctx.options.jail_root {
+        Some(root) => Some(root.clone()),
+        None => ctx.fs.home_dir(),
+    };
+    let path = match (arg.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            home.join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(arg),
+    };
+    let path = resolve_path(ctx, &path);
+
+    if let Some(root) = &ctx.options.jail_root {
+        let resolved = canonicalize_lenient(ctx.fs.as_ref(), &path)
+            .map_err(|e| format!("Error resolving path '{}': {}", arg, e))?;
+        if !resolved.starts_with(root) {
+            return Err(format!("Error: Permission denied: '{}' is outside the jail.", arg));
+        }
+    }
+
+    Ok(path)
+}
+
+/// Rejects path arguments that could reach outside the working directory: absolute paths,
+/// paths starting with `~`, and paths with `..` components. Used in `--safe` mode.
+pub fn validate_path(arg: &str) -> Result<(), String> {
+    let path = Path::new(arg);
+    if path.has_root() || arg.starts_with('~') || path.components().any(|c| c == Component::ParentDir) {
+        return Err(format!("Error: Path not allowed: '{}'.", arg));
+    }
+    Ok(())
+}
+
+/// Resolves a path against the session's working directory, removing `.` and `..`
+/// components without following symlinks. Absolute paths are only normalized.
+pub fn resolve_path(ctx: &ShellContext, path: &Path) -> PathBuf {
+    match ctx.fs.current_dir() {
+        Ok(cwd) => lexical_join(&cwd, path),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Canonicalizes `path`, allowing its final components not to exist yet (as for a file
+/// about to be created) by resolving the nearest existing ancestor instead.
+fn canonicalize_lenient(fs: &dyn FileSystem, path: &Path) -> std::io::Result<PathBuf> {
+    match fs.canonicalize(path) {
+        Ok(resolved) => Ok(resolved),
+        Err(e) => match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => {
+                let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+                Ok(canonicalize_lenient(fs, parent)?.join(name))
+            }
+            _ => Err(e),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::MemoryFileSystem;
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+
+    /// Runs a command line in a fresh context.
+    fn run(line: &str) -> CommandOutput {
+        execute(line, &mut ShellContext::default())
+    }
+
+    /// Returns a fresh context whose files are kept in memory.
+    fn memory_context() -> ShellContext {
+        ShellContext { fs: Arc::new(MemoryFileSystem::new()), ..Default::default() }
+    }
+
+    /// Returns `name` inside the temporary directory as a command argument.
+    fn arg(dir: &TempDir, name: &str) -> String {
+        dir.path().join(name).display().to_string()
+    }
+
+    #[test]
+    fn echo_joins_arguments() {
+        let output = run("echo hello   world");
+        assert_eq!(output.stdout, "hello world\n");
+        assert_eq!(output.exit_code, 0);
+    }
+
+    #[test]
+    fn echo_flags_control_escapes_and_newline() {
+        assert_eq!(run(r"echo -e a\tb\\c\0101").stdout, "a\tb\\cA\n");
+        assert_eq!(run(r"echo -E a\tb").stdout, "a\\tb\n");
+        assert_eq!(run(r"echo -ne a\nb").stdout, "a\nb");
+        assert_eq!(run("echo -x -n").stdout, "-x -n\n");
+    }
+
+    #[test]
+    fn echo_prints_quoted_arguments_without_their_quotes() {
+        assert_eq!(run(r#"echo -e "a\tb""#).stdout, "a\tb\n");
+        assert_eq!(run(r#"echo "two  spaces" 'single "inner"'"#).stdout, "two  spaces single \"inner\"\n");
+        assert_eq!(run(r#"echo -n "it's""#).stdout, "it's");
+    }
+
+    #[test]
+    fn bc_evaluates_quoted_expressions_piped_to_it() {
+        assert_eq!(run(r#"echo "2^10" | bc"#).stdout, "1024\n");
+        assert_eq!(run("echo 'scale = 2; 1 / 3' | bc").stdout, "0.33\n");
+    }
+
+    #[test]
+    fn ls_lists_entries_sorted() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("b.txt"), "").unwrap();
+        fs::write(dir.path().join("a.txt"), "").unwrap();
+
+        let output = run(&format!("ls {}", dir.path().display()));
+        assert_eq!(output.stdout, "a.txt\nb.txt");
+    }
+
+    #[test]
+    fn ls_fills_columns_only_on_a_terminal() {
+        let mut ctx = memory_context();
+        execute("touch {alpha,beta,gamma,delta}", &mut ctx);
+        ctx.terminal_width = Some(16);
+
+        assert_eq!(execute("ls", &mut ctx).stdout, "alpha  delta\nbeta   gamma");
+        assert_eq!(execute("ls -1", &mut ctx).stdout, "alpha\nbeta\ndelta\ngamma");
+        assert_eq!(execute("ls | cat", &mut ctx).stdout, "alpha\nbeta\ndelta\ngamma");
+        assert_eq!(execute("echo $(ls)", &mut ctx).stdout, "alpha beta delta gamma\n");
+        execute("ls > list", &mut ctx);
+        assert_eq!(execute("cat list", &mut ctx).stdout, "alpha\nbeta\ndelta\ngamma");
+    }
+
+    #[test]
+    fn ls_sorts_by_size_and_reverses() {
+        let mut ctx = memory_context();
+        execute("mkdir docs; touch small.txt a; touch big.txt abcdefgh", &mut ctx);
+
+        assert_eq!(execute("ls -S | cat", &mut ctx).stdout, "big.txt\nsmall.txt\ndocs");
+        assert_eq!(execute("ls -Sr | cat", &mut ctx).stdout, "docs\nsmall.txt\nbig.txt");
+        assert_eq!(execute("ls -x", &mut ctx).stderr, "Error: ls: invalid option '-x'.");
+        assert_eq!(
+            execute("ls -S --group-directories-first | cat", &mut ctx).stdout,
+            "docs\nbig.txt\nsmall.txt"
+        );
+        assert_eq!(execute("ls -r --group-directories-first | cat", &mut ctx).stdout, "docs\nsmall.txt\nbig.txt");
+
+        let long = execute("ls -lS | cat", &mut ctx).stdout;
+        let lines: Vec<&str> = long.lines().collect();
+        assert!(lines[0].starts_with("- 9 ") && lines[0].ends_with(" big.txt"), "{:?}", lines);
+        assert_eq!(lines[2], "d 0 -                docs");
+    }
+
+    #[test]
+    fn ls_quotes_names_with_special_characters() {
+        let mut ctx = memory_context();
+        ctx.fs.write(Path::new("/my notes.txt"), "").unwrap();
+        ctx.fs.write(Path::new("/say \"hi\"\\"), "").unwrap();
+        ctx.fs.write(Path::new("/two\nlines"), "").unwrap();
+        ctx.fs.write(Path::new("/plain.txt"), "").unwrap();
+
+        assert_eq!(
+            execute("ls -Q | cat", &mut ctx).stdout,
+            "\"my notes.txt\"\nplain.txt\n\"say \\\"hi\\\"\\\\\"\n\"two\\nlines\""
+        );
+        assert_eq!(execute("ls --quote-names | cat", &mut ctx).stdout, execute("ls -Q | cat", &mut ctx).stdout);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ls_shows_symlinks_or_follows_them_with_dereference() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("target.txt"), "hello").unwrap();
+        std::os::unix::fs::symlink("target.txt", dir.path().join("link")).unwrap();
+        std::os::unix::fs::symlink("missing.txt", dir.path().join("broken")).unwrap();
+
+        let long = strip_ansi(&run(&format!("ls -l {}", dir.path().display())).stdout);
+        let lines: Vec<&str> = long.lines().collect();
+        assert!(lines[0].starts_with("l ") && lines[0].ends_with(" broken -> missing.txt"), "{:?}", lines);
+        assert!(lines[1].starts_with("l ") && lines[1].ends_with(" link -> target.txt"), "{:?}", lines);
+
+        let followed = strip_ansi(&run(&format!("ls -lL {}", dir.path().display())).stdout);
+        let lines: Vec<&str> = followed.lines().collect();
+        assert!(lines[0].ends_with(" broken -> missing.txt"), "{:?}", lines);
+        assert!(lines[1].starts_with("-  5 ") && lines[1].ends_with(" link"), "{:?}", lines);
+        assert_eq!(run(&format!("ls --dereference {}", dir.path().display())).stdout, "broken\nlink\ntarget.txt");
+    }
+
+    #[test]
+    fn touch_then_cat_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let file = arg(&dir, "notes.txt");
+
+        assert_eq!(run(&format!("touch {} \"hello there\"", file)).exit_code, 0);
+        assert_eq!(run(&format!("cat {}", file)).stdout, "hello there\n");
+    }
+
+    #[test]
+    fn touch_sets_times_from_dates_and_stamps() {
+        let mut ctx = memory_context();
+        execute("touch old.txt \"kept\"", &mut ctx);
+        assert_eq!(execute("touch -d \"2024-01-01 12:00\" old.txt", &mut ctx).exit_code, 0);
+        assert_eq!(execute("touch -t 202401021330.15 new.txt", &mut ctx).exit_code, 0);
+        assert_eq!(execute("touch -d 2023-06-30 oldest.txt", &mut ctx).exit_code, 0);
+        assert_eq!(execute("touch -d 2022-03-04T05:06:07 older.txt", &mut ctx).exit_code, 0);
+
+        assert_eq!(execute("cat old.txt", &mut ctx).stdout, "kept\n");
+        assert_eq!(execute("ls -t | cat", &mut ctx).stdout, "new.txt\nold.txt\noldest.txt\nolder.txt");
+        let long = execute("ls -l | cat", &mut ctx).stdout;
+        assert!(long.contains("2024-01-02 13:30 new.txt"), "{}", long);
+        assert!(long.contains("2024-01-01 12:00 old.txt"), "{}", long);
+        assert!(long.contains("2023-06-30 00:00 oldest.txt"), "{}", long);
+        assert!(long.contains("2022-03-04 05:06 older.txt"), "{}", long);
+
+        let output = execute("touch -d 2024-02-30 bad.txt", &mut ctx);
+        assert_eq!(output.stderr, "Error: touch: invalid date format '2024-02-30'.");
+        assert_eq!(execute("touch -t 24010112 bad.txt", &mut ctx).exit_code, 1);
+        assert_eq!(execute("ls bad.txt", &mut ctx).exit_code, 1);
+    }
+
+    #[test]
+    fn find_walks_the_tree_with_every_test_applied() {
+        let mut ctx = memory_context();
+        execute("mkdir src; mkdir src/old; touch src/a.rs; touch src/old/b.rs; touch notes.txt", &mut ctx);
+        ctx.fs.write(Path::new("/src/big.rs"), &"x".repeat(3000)).unwrap();
+        execute("touch -d 2020-01-01 src/old/b.rs", &mut ctx);
+
+        assert_eq!(execute("find src | cat", &mut ctx).stdout, "src\nsrc/a.rs\nsrc/big.rs\nsrc/old\nsrc/old/b.rs");
+        assert_eq!(execute("find -name '*.rs' -type f | cat", &mut ctx).stdout, "./src/a.rs\n./src/big.rs\n./src/old/b.rs");
+        assert_eq!(execute("find . -type d | cat", &mut ctx).stdout, ".\n./src\n./src/old");
+        assert_eq!(execute("find src -mtime +30 | cat", &mut ctx).stdout, "src/old/b.rs");
+        assert_eq!(execute("find src -type f -size +2k | cat", &mut ctx).stdout, "src/big.rs");
+        assert_eq!(execute("find src -type f -size -1k -mtime -1 | cat", &mut ctx).stdout, "src/a.rs");
+
+        let output = execute("find missing src/a.rs", &mut ctx);
+        assert_eq!((output.stdout.as_str(), output.exit_code), ("src/a.rs", 1));
+        assert_eq!(output.stderr, "Error: find: 'missing': No such file or directory");
+    }
+
+    #[test]
+    fn find_exec_runs_a_command_on_each_match() {
+        let mut ctx = memory_context();
+        execute("mkdir cache; touch cache/a.tmp; touch cache/b.tmp; touch keep.txt", &mut ctx);
+
+        let output = execute("find . -name '*.tmp' -exec rm -v {} \\;", &mut ctx);
+        assert_eq!(output.stdout, "removed './cache/a.tmp'\nremoved './cache/b.tmp'");
+        assert_eq!(execute("find -type f | cat", &mut ctx).stdout, "./keep.txt");
+
+        assert_eq!(execute("find -type f -exec echo found {} +", &mut ctx).stdout, "found ./keep.txt\n");
+        assert_eq!(execute("find -type f -exec echo {};", &mut ctx).stdout, "./keep.txt\n");
+
+        let output = execute("find -type d -exec rmdir {} ';'", &mut ctx);
+        assert_eq!(output.exit_code, 1);
+        assert!(output.stderr.contains("Error: find: 'rmdir .' exited with status 1."), "{}", output.stderr);
+    }
+
+    #[test]
+    fn grep_searches_trees_through_include_and_exclude_filters() {
+        let mut ctx = memory_context();
+        execute("mkdir src; mkdir target; mkdir target/debug; mkdir .git", &mut ctx);
+        for (path, text) in [("/src/main.rs", "// TODO: parse"), ("/notes.md", "TODO list"), ("/target/debug/gen.rs", "TODO"), ("/.git/HEAD", "TODO")] {
+            ctx.fs.write(Path::new(path), text).unwrap();
+        }
+
+        let output = execute("grep -r TODO --include='*.rs' --exclude='target/*' --exclude=.git", &mut ctx);
+        assert_eq!((output.stdout.as_str(), output.exit_code), ("src/main.rs:// TODO: parse", 0));
+        let output = execute("grep -r TODO --exclude target --exclude .git --exclude '*.rs'", &mut ctx);
+        assert_eq!(output.stdout, "notes.md:TODO list");
+        assert_eq!(execute("grep -rc TODO src", &mut ctx).stdout, "src/main.rs:1");
+        assert!(execute("grep -r TODO --include", &mut ctx).stderr.contains("--include requires a pattern"));
+    }
+
+    #[test]
+    fn gitignore_flag_skips_ignored_entries_in_find_grep_and_tree() {
+        let mut ctx = memory_context();
+        ctx.options.theme.colored = false;
+        execute("mkdir .git; mkdir src; mkdir target", &mut ctx);
+        for (path, text) in [("/.gitignore", "target\n*.log"), ("/.git/config", "TODO"), ("/src/main.rs", "TODO"), ("/debug.log", "TODO"), ("/target/gen.rs", "TODO")] {
+            ctx.fs.write(Path::new(path), text).unwrap();
+        }
+
+        assert_eq!(execute("find --gitignore -type f | cat", &mut ctx).stdout, "./.gitignore\n./src/main.rs");
+        assert_eq!(execute("find -type f | cat", &mut ctx).stdout.lines().count(), 5);
+        assert_eq!(execute("grep -r --gitignore TODO", &mut ctx).stdout, "src/main.rs:TODO");
+        assert_eq!(execute("grep -r TODO", &mut ctx).stdout.lines().count(), 4);
+        assert_eq!(execute("tree --gitignore", &mut ctx).stdout, ".\n├── .gitignore\n└── src\n    └── main.rs\n\n1 directory, 2 files");
+    }
+
+    #[test]
+    fn uptime_counts_from_the_start_of_the_session() {
+        let mut ctx = ShellContext { started: Instant::now() - Duration::from_secs(5025), ..Default::default() };
+        let output = execute("uptime", &mut ctx).stdout;
+        assert!(output.ends_with(" up 1:23:45"), "{}", output);
+        assert_eq!(output.len(), " 00:00:00 up 1:23:45".len());
+
+        ctx.started = Instant::now() - Duration::from_secs(2 * 86_400 + 61);
+        assert!(execute("uptime", &mut ctx).stdout.ends_with(" up 2 days, 0:01:01"));
+    }
+
+    #[test]
+    fn stats_count_each_command_run() {
+        let mut ctx = ShellContext::default();
+        execute("echo a | echo b; pwd; echo c", &mut ctx);
+        let stats = execute("stats", &mut ctx).stdout;
+        let lines: Vec<&str> = stats.lines().collect();
+        assert_eq!(lines[0], "Commands run: 5");
+        assert!(lines[1].starts_with("Session time: 0:00:0"));
+        assert_eq!(lines[2..], ["  echo   3", "  pwd    1", "  stats  1"]);
+    }
+
+    #[test]
+    fn log_records_each_line_once_with_its_errors() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("log.txt");
+        let log = SessionLog::open(&path).unwrap();
+        let mut ctx = ShellContext { log: Some(log), ..memory_context() };
+        ctx.fs.write(Path::new("/setup.sh"), "echo sourced\n").unwrap();
+        execute("pwd", &mut ctx);
+        execute("source setup.sh; echo $(pwd) > where.txt; cat missing.txt", &mut ctx);
+        ctx.log.as_mut().unwrap().flush().unwrap();
+
+        let log = fs::read_to_string(&path).unwrap();
+        let commands: Vec<&str> = log.lines().filter_map(|line| line.split_once("] $ ")).map(|(_, line)| line).collect();
+        assert_eq!(commands.len(), 2, "{}", log);
+        assert!(commands[1].starts_with("source setup.sh;"));
+        assert!(log.contains("\nsourced\n") && log.contains("missing.txt") && log.ends_with("[exit status 1]\n"), "{}", log);
+    }
+
+    #[test]
+    fn cat_normalizes_crlf_line_endings_on_request() {
+        let mut ctx = memory_context();
+        ctx.fs.write(Path::new("/dos.txt"), "one\r\ntwo\r\n").unwrap();
+        ctx.fs.write(Path::new("/unix.txt"), "plain\n").unwrap();
+
+        assert_eq!(execute("cat dos.txt", &mut ctx).stdout, "one\r\ntwo\r\n");
+        assert_eq!(execute("cat --lf dos.txt", &mut ctx).stdout, "one\ntwo\n");
+        ctx.options.normalize_crlf = true;
+        assert_eq!(execute("cat dos.txt", &mut ctx).stdout, "one\ntwo\n");
+        assert_eq!(execute("cat --crlf dos.txt", &mut ctx).stdout, "one\r\ntwo\r\n");
+
+        execute("mkdir docs; touch empty.txt", &mut ctx);
+        let output = execute("file dos.txt unix.txt docs empty.txt", &mut ctx);
+        assert_eq!(
+            output.stdout,
+            "dos.txt: ASCII text, with CRLF line terminators\nunix.txt: ASCII text\ndocs: directory\nempty.txt: empty"
+        );
+    }
+
+    #[test]
+    fn less_leaves_a_single_file_for_the_pager_to_read() {
+        let mut ctx = memory_context();
+        ctx.fs.write(Path::new("/log.txt"), "one\ntwo\n").unwrap();
+        assert_eq!(execute("less log.txt", &mut ctx).stdout, "one\ntwo\n");
+        assert!(ctx.paged_file.is_none());
+
+        ctx.interactive = true;
+        let output = execute("less log.txt", &mut ctx);
+        assert_eq!((output.stdout.as_str(), output.exit_code), ("", 0));
+        assert!(ctx.paged_file.take().is_some() && std::mem::take(&mut ctx.page_output));
+
+        assert_eq!(execute("less log.txt | grep two", &mut ctx).stdout, "two");
+        assert!(ctx.paged_file.is_none());
+        assert_eq!(execute("less missing.txt", &mut ctx).stderr, "Error reading file 'missing.txt': No such file or directory");
+    }
+
+    #[test]
+    fn cat_squeezes_blank_lines() {
+        let mut ctx = memory_context();
+        ctx.fs.write(Path::new("/log.txt"), "\n\n\na\n\n\n\nb\n\n\n").unwrap();
+
+        assert_eq!(execute("cat -s log.txt", &mut ctx).stdout, "\na\n\nb\n\n");
+        assert_eq!(execute("cat log.txt log.txt | cat -s", &mut ctx).stdout, "\na\n\nb\n\na\n\nb\n\n");
+    }
+
+    #[test]
+    fn cat_missing_file_reports_error() {
+        let dir = TempDir::new().unwrap();
+        let output = run(&format!("cat {}", arg(&dir, "missing.txt")));
+
+        assert_eq!(output.exit_code, 1);
+        assert!(output.stdout.is_empty());
+        assert!(output.stderr.starts_with("Error reading file"));
+    }
+
+    #[test]
+    fn mkdir_and_rmdir_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let sub = arg(&dir, "sub");
+
+        assert_eq!(run(&format!("mkdir {}", sub)).exit_code, 0);
+        assert!(dir.path().join("sub").is_dir());
+        assert_eq!(run(&format!("rmdir {}", sub)).exit_code, 0);
+        assert!(!dir.path().join("sub").exists());
+    }
+
+    #[test]
+    fn rm_deletes_file() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("gone.txt"), "x").unwrap();
+
+        assert_eq!(run(&format!("rm {}", arg(&dir, "gone.txt"))).exit_code, 0);
+        assert!(!dir.path().join("gone.txt").exists());
+        assert_eq!(run(&format!("rm {}", arg(&dir, "gone.txt"))).exit_code, 1);
+    }
+
+    #[test]
+    fn mkdir_and_rm_report_each_target_when_verbose() {
+        let mut ctx = memory_context();
+
+        assert_eq!(execute("mkdir -v a b | cat", &mut ctx).stdout, "created directory 'a'\ncreated directory 'b'");
+        let output = execute("mkdir c a d", &mut ctx);
+        assert_eq!((strip_ansi(&output.stdout).as_str(), output.exit_code), ("Created 2 directories.", 1));
+        assert_eq!(output.stderr, "Error creating directory 'a': File exists");
+
+        execute("touch x", &mut ctx);
+        assert_eq!(execute("rm -v x | cat", &mut ctx).stdout, "removed 'x'");
+    }
+
+    #[test]
+    fn rm_and_rmdir_take_several_targets() {
+        let mut ctx = memory_context();
+        execute("touch a; touch b; mkdir d; mkdir e; mkdir tree; touch tree/leaf", &mut ctx);
+
+        let output = execute("rm a missing b", &mut ctx);
+        assert_eq!((strip_ansi(&output.stdout).as_str(), output.exit_code), ("Deleted 2 files.", 1));
+        assert_eq!(output.stderr, "Error deleting file 'missing': No such file or directory");
+
+        assert_eq!(execute("rmdir -v d e | cat", &mut ctx).stdout, "removed directory 'd'\nremoved directory 'e'");
+        assert_eq!(execute("rm tree", &mut ctx).exit_code, 1);
+        assert_eq!(execute("cd tree; rm -r .; cd ..", &mut ctx).stderr, "Error: rm: refusing to remove '.'.");
+        assert_eq!(execute("rm -rv tree | cat", &mut ctx).stdout, "removed directory 'tree'");
+        assert_eq!(execute("ls", &mut ctx).stdout, "");
+    }
+
+    #[test]
+    fn rm_force_ignores_missing_files() {
+        let mut ctx = memory_context();
+        execute("touch a", &mut ctx);
+
+        let output = execute("rm -f missing a also-missing", &mut ctx);
+        assert_eq!((strip_ansi(&output.stdout).as_str(), output.stderr.as_str(), output.exit_code), ("Deleted 1 file.", "", 0));
+        let output = execute("rm --force gone", &mut ctx);
+        assert_eq!((output.stdout.as_str(), output.exit_code), ("", 0));
+        assert_eq!(execute("rm -f", &mut ctx).exit_code, 0);
+    }
+
+    #[test]
+    fn pipeline_feeds_stdout_forward() {
+        let output = run("echo alpha beta | xargs -n 1 echo | grep -c a");
+        assert_eq!(output.stdout, "2");
+    }
+
+    #[test]
+    fn xargs_passes_its_input_without_expanding_it_again() {
+        let mut ctx = memory_context();
+        execute("touch a b", &mut ctx);
+
+        assert_eq!(execute("echo '*' | xargs echo", &mut ctx).stdout, "*\n");
+        let output = execute("echo 'a  b $HOME \"q\"' | xargs -I{} echo '[{}]'", &mut ctx);
+        assert_eq!(output.stdout, "[a  b $HOME \"q\"]\n");
+        assert_eq!(execute("echo x y | xargs -n1 echo a | grep -c a", &mut ctx).stdout, "2");
+        assert_eq!(execute("echo x | xargs -nq", &mut ctx).stderr, "Error: Invalid number for -n: 'q'.");
+    }
+
+    #[test]
+    fn heredoc_is_redirected_to_a_file() {
+        let mut ctx = memory_context();
+
+        let output = execute_with_heredoc("cat << EOF > notes.txt; echo end >> notes.txt", Some("a\nb\n"), &mut ctx);
+        assert_eq!(output.stdout, "");
+        assert_eq!(execute("cat notes.txt", &mut ctx).stdout, "a\nb\nend\n");
+    }
+
+    #[test]
+    fn touch_creates_every_file_a_brace_pattern_names() {
+        let mut ctx = memory_context();
+
+        assert_eq!(execute("touch file{1,2,3}.txt", &mut ctx).exit_code, 0);
+        assert_eq!(execute("ls; echo {1..5}", &mut ctx).stdout, "file1.txt\nfile2.txt\nfile3.txt\n1 2 3 4 5\n");
+
+        let output = execute("touch {1..10000000000}", &mut ctx);
+        assert_eq!(output.stderr, "Error: {1..10000000000}: brace expansion gives more than 100000 words.");
+        assert_eq!(execute("ls", &mut ctx).stdout, "file1.txt\nfile2.txt\nfile3.txt");
+    }
+
+    #[test]
+    fn error_output_can_be_redirected_separately() {
+        let mut ctx = memory_context();
+        execute("touch found.txt hello", &mut ctx);
+
+        let output = execute("cat found.txt missing.txt 2> errors.txt", &mut ctx);
+        assert_eq!((output.stdout.as_str(), output.stderr.as_str()), ("hello\n", ""));
+        assert_eq!(output.exit_code, 1);
+        execute("cat nothing.txt 2>> errors.txt", &mut ctx);
+        assert_eq!(execute("wc -l errors.txt", &mut ctx).stdout, "      2 errors.txt\n");
+
+        let output = execute("cat found.txt missing.txt 2>&1 | wc -l", &mut ctx);
+        assert_eq!((output.stdout.as_str(), output.stderr.as_str()), ("      2\n", ""));
+        let output = execute("echo oops >&2", &mut ctx);
+        assert_eq!((output.stdout.as_str(), output.stderr.as_str()), ("", "oops"));
+        assert_eq!(execute("echo hi >&2; echo there >&2", &mut ctx).stderr, "hi\nthere");
+        execute("echo logged >&2 2> log.txt", &mut ctx);
+        assert_eq!(execute("cat log.txt", &mut ctx).stdout, "logged\n");
+    }
+
+    #[test]
+    fn noclobber_keeps_existing_files_unless_forced() {
+        let mut ctx = memory_context();
+        execute("set -o noclobber; echo first > notes.txt", &mut ctx);
+
+        let output = execute("echo second > notes.txt", &mut ctx);
+        assert_eq!(output.exit_code, 1);
+        assert!(output.stderr.contains("'notes.txt' exists"));
+        execute("echo more >> notes.txt; echo 2>| errors.txt", &mut ctx);
+        assert_eq!(execute("cat notes.txt", &mut ctx).stdout, "first\nmore\n");
+
+        assert_eq!(execute("echo forced >| notes.txt; cat notes.txt", &mut ctx).stdout, "forced\n");
+        assert_eq!(execute("set +C; echo last > notes.txt; cat notes.txt", &mut ctx).stdout, "last\n");
+        assert_eq!(execute("set -o nounset", &mut ctx).exit_code, 1);
+    }
+
+    #[test]
+    fn set_toggles_and_lists_shell_options() {
+        let mut ctx = memory_context();
+
+        assert_eq!(execute("set", &mut ctx).stdout, "set +o errexit\nset +o noclobber\nset +o xtrace");
+        assert_eq!(execute("set -eC; set -o", &mut ctx).stdout, "set -o errexit\nset -o noclobber\nset +o xtrace");
+        assert_eq!(execute("set +o errexit -x; echo $(echo hi)", &mut ctx).stdout, "+ echo hi\nhi\n");
+        assert_eq!(execute("set -q", &mut ctx).stderr, "Error: set: invalid option '-q'.");
+        assert_eq!(execute("set +o pipefail", &mut ctx).stderr, "Error: set: unknown option 'pipefail'.");
+    }
+
+    #[test]
+    fn xtrace_shows_commands_as_expanded() {
+        let mut ctx = memory_context();
+        execute("touch {a,b}.txt; alias hi='echo hello'; set -x", &mut ctx);
+
+        assert_eq!(execute("echo *.txt | wc -w > count.txt", &mut ctx).stdout, "+ echo a.txt b.txt\n+ wc -w\n");
+        assert_eq!(execute("cat count.txt", &mut ctx).stdout, "+ cat count.txt\n      2\n");
+        assert_eq!(execute("hi $(echo there) {1..2}", &mut ctx).stdout, "+ echo hello there 1 2\nhello there 1 2\n");
+        assert_eq!(execute("set +x; echo quiet", &mut ctx).stdout, "+ set +x\nquiet\n");
+    }
+
+    #[test]
+    fn errexit_stops_scripts_that_keep_going() {
+        let mut ctx = memory_context();
+        let mut ran = Vec::new();
+
+        let script = "cat missing.txt\necho after\nset -e\ncat missing.txt; echo skipped\necho never";
+        let exit_code = run_script(script, true, &mut ctx, |_, line, output| {
+            ran.push((line.to_string(), output.stdout.clone()));
+        });
+        assert_eq!(exit_code, 1);
+        assert_eq!(ran.len(), 4);
+        assert_eq!(ran[1], ("echo after".to_string(), "after\n".to_string()));
+        assert_eq!(ran[3].1, "");
+    }
+
+    #[test]
+    fn output_for_a_terminal_stops_at_the_limit() {
+        let options = Options { output_limit: Some(3), ..Default::default() };
+        let mut ctx = ShellContext { options, terminal_width: Some(80), ..memory_context() };
+        execute("mkdir d; touch d/{a,b,c,e,f}", &mut ctx);
+
+        let output = execute("find d", &mut ctx);
+        assert_eq!(output.stdout, "d\nd/a\nd/b\n");
+        assert_eq!(output.stderr, "[output truncated at 3 lines]");
+        assert_eq!(execute("find d | wc -l", &mut ctx).stdout, "      6\n");
+        assert_eq!(execute("find d > found.txt; cat found.txt | wc -l", &mut ctx).stdout, "      6\n");
+        assert_eq!(execute("echo $(find d)", &mut ctx).stdout, "d d/a d/b d/c d/e d/f\n");
+
+        ctx.options.output_limit = Some(0);
+        assert_eq!(execute("find d", &mut ctx).stderr, "");
+    }
+
+    #[test]
+    fn double_dash_ends_the_options() {
+        let mut ctx = memory_context();
+
+        assert_eq!(execute("mkdir -- -weird; cd -- -weird; touch -- -f hello", &mut ctx).exit_code, 0);
+        assert_eq!(execute("ls -- .; cat -- -f; wc -l -- -f", &mut ctx).stdout, "-f\nhello\n      1 -f\n");
+        assert_eq!(execute("grep -- -f -f", &mut ctx).exit_code, 1);
+        assert_eq!(execute("grep -c -- hel -f", &mut ctx).stdout, "1");
+        assert_eq!(execute("rm -- -f", &mut ctx).exit_code, 0);
+        assert_eq!(execute("ls", &mut ctx).stdout, "");
+    }
+
+    #[test]
+    fn sleep_rejects_intervals_it_cannot_wait_through() {
+        let mut ctx = memory_context();
+
+        assert_eq!(execute("sleep 1e100", &mut ctx).stderr, "Error: Invalid time interval '1e100'.");
+        assert_eq!(execute("sleep -1", &mut ctx).exit_code, 1);
+        assert_eq!(execute("sleep 0.01", &mut ctx).exit_code, 0);
+        ctx.interactive = true;
+        assert!(execute("sleep 11", &mut ctx).stderr.starts_with("Error: sleep: at most 10 seconds"));
+    }
+
+    #[test]
+    fn background_jobs_cannot_change_directory() {
+        let ctx = memory_context();
+        ctx.fs.create_dir(Path::new("/docs")).unwrap();
+
+        let mut job = ctx.for_background();
+        let output = execute("cd /docs", &mut job);
+        assert_eq!(output.stderr, "Error: 'cd' cannot change directory in a background job.");
+        assert_eq!(ctx.fs.current_dir().unwrap(), Path::new("/"));
+    }
+
+    #[test]
+    fn command_substitution_inserts_output() {
+        assert_eq!(run("echo files: $(echo a; echo b)").stdout, "files: a b\n");
+        assert_eq!(run("echo $(echo $(echo nested)) `echo tick`").stdout, "nested tick\n");
+        assert_eq!(run("echo '$(echo literal)'").stdout, "$(echo literal)\n");
+        assert_eq!(run("echo $(echo open").exit_code, 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn external_programs_run_when_allowed() {
+        let options = Options { allow_external: true, ..Default::default() };
+        let mut ctx = ShellContext { options, ..Default::default() };
+
+        let output = execute("echo hi | tr a-z A-Z; false", &mut ctx);
+        assert_eq!(output.stdout, "HI\n");
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(execute("no-such-program-here", &mut ctx).exit_code, 127);
+        assert_eq!(run("true").exit_code, 127);
+    }
+
+    #[test]
+    fn time_reports_how_long_a_command_took() {
+        let mut ctx = memory_context();
+
+        let output = execute("time echo hi > out.txt; cat out.txt", &mut ctx);
+        assert_eq!(output.stdout, "hi\n");
+        assert!(output.stderr.starts_with("real\t0m"), "{}", output.stderr);
+        assert_eq!(output.stderr.lines().count(), 1);
+        assert_eq!(execute("time", &mut ctx).stderr, "Error: Usage: time <command>");
+        assert_eq!(execute("timeout 1", &mut ctx).exit_code, 127);
+
+        let output = execute("time cat missing.txt", &mut ctx);
+        assert_eq!(output.exit_code, 1);
+        assert!(output.stderr.lines().nth(1).is_some_and(|line| line.starts_with("real\t")));
+
+        // The whole pipeline is timed, once, and not only its first stage
+        let output = execute("time echo hi | sleep 0.2 | cat", &mut ctx);
+        assert_eq!(output.stderr.lines().count(), 1);
+        let seconds = output.stderr.strip_prefix("real\t").and_then(|elapsed| elapsed.strip_suffix('s'));
+        let seconds = seconds.and_then(|elapsed| elapsed.split_once('m')).and_then(|(minutes, seconds)| {
+            Some(minutes.parse::<f64>().ok()? * 60.0 + seconds.parse::<f64>().ok()?)
+        });
+        assert!(seconds.is_some_and(|seconds| seconds >= 0.2), "{}", output.stderr);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn time_reports_cpu_times_for_programs() {
+        let options = Options { allow_external: true, ..Default::default() };
+        let mut ctx = ShellContext { options, ..Default::default() };
+
+        let output = execute("time true", &mut ctx);
+        let labels: Vec<&str> = output.stderr.lines().filter_map(|line| line.split('\t').next()).collect();
+        assert_eq!(labels, ["real", "user", "sys"]);
+    }
+
+    #[test]
+    fn paths_resolve_against_the_working_directory() {
+        let ctx = memory_context();
+        ctx.fs.create_dir(Path::new("/docs")).unwrap();
+        ctx.fs.set_current_dir(Path::new("/docs")).unwrap();
+
+        assert_eq!(resolve_path(&ctx, Path::new("notes.txt")), PathBuf::from("/docs/notes.txt"));
+        assert_eq!(resolve_path(&ctx, Path::new("../a/./b")), PathBuf::from("/a/b"));
+        assert_eq!(resolve_path(&ctx, Path::new("/etc/../tmp")), PathBuf::from("/tmp"));
+        assert_eq!(expand_path(&ctx, "~/x").unwrap(), PathBuf::from("/x"));
+    }
+
+    #[test]
+    fn source_runs_lines_in_the_current_session() {
+        let mut ctx = memory_context();
+        ctx.fs.create_dir(Path::new("/docs")).unwrap();
+        ctx.fs.write(Path::new("/setup.sh"), "# setup\ncd docs\ncat missing.txt\necho done\n").unwrap();
+
+        let output = execute(". setup.sh", &mut ctx);
+        assert_eq!(output.exit_code, 0);
+        assert!(output.stdout.ends_with("done\n"));
+        assert_eq!(output.stderr, "setup.sh: line 3: Error reading file 'missing.txt': No such file or directory");
+        assert_eq!(ctx.fs.current_dir().unwrap(), PathBuf::from("/docs"));
+    }
+
+    #[test]
+    fn aliases_run_and_which_reports_them() {
+        let mut ctx = ShellContext::default();
+
+        assert_eq!(execute("alias greet='echo hello' hi=greet; hi world", &mut ctx).stdout, "hello world\n");
+        assert_eq!(execute("alias", &mut ctx).stdout, "alias greet='echo hello'\nalias hi='greet'");
+
+        let output = execute("which hi ls frobnicate", &mut ctx);
+        assert_eq!(output.stdout, "hi: aliased to 'greet'\nls: shell builtin");
+        assert_eq!(output.stderr, "which: frobnicate not found");
+        assert_eq!(output.exit_code, 1);
+
+        assert_eq!(execute("unalias hi; which hi", &mut ctx).exit_code, 1);
+    }
+
+    #[test]
+    fn exit_status_expands_and_shows_in_the_prompt() {
+        let mut ctx = memory_context();
+        ctx.options.prompt = Some("{status}{cwd}$ ".to_string());
+        ctx.options.theme.colored = false;
+        ctx.current_dir = "/".to_string();
+
+        assert_eq!(execute("cat missing.txt; echo $?; echo $?", &mut ctx).stdout, "1\n0\n");
+        assert_eq!(ctx.prompt(), "/$ ");
+        assert_eq!(execute("frobnicate", &mut ctx).exit_code, 127);
+        assert_eq!(ctx.prompt(), "127 /$ ");
+    }
+
+    #[test]
+    fn default_aliases_run() {
+        let mut ctx = memory_context();
+        ctx.aliases.extend(DEFAULT_ALIASES.iter().map(|(name, value)| (name.to_string(), value.to_string())));
+        ctx.fs.write(Path::new("/notes.txt"), "hello").unwrap();
+        for (name, _) in DEFAULT_ALIASES {
+            let output = execute(name, &mut ctx);
+            assert_eq!((output.exit_code, output.stderr.as_str()), (0, ""), "{}", name);
+            assert!(output.stdout.contains("notes.txt"));
+        }
+    }
+
+    #[test]
+    fn export_sets_variables_that_expand_until_unset() {
+        let mut ctx = ShellContext::default();
+        let output = execute("export SHELL_TEST_GREETING='hello there' SHELL_TEST_EMPTY=; echo $SHELL_TEST_GREETING!", &mut ctx);
+        assert_eq!(output.stdout, "hello there!\n");
+        assert!(execute("env", &mut ctx).stdout.lines().any(|line| line == "SHELL_TEST_EMPTY="));
+
+        let output = execute("unset SHELL_TEST_GREETING SHELL_TEST_NEVER_SET 1BAD; echo [$SHELL_TEST_GREETING]", &mut ctx);
+        assert_eq!(output.stdout, "[]\n");
+        assert_eq!(output.stderr, "unset: '1BAD': not a valid identifier");
+        assert_eq!(execute("export a-b=1", &mut ctx).exit_code, 1);
+        std::env::remove_var("SHELL_TEST_EMPTY");
+    }
+
+    #[test]
+    fn rm_asks_before_deleting_more_files_than_the_glob_limit() {
+        let mut ctx = ShellContext { interactive: true, ..memory_context() };
+        execute("touch keep.md; touch log{1..12}.txt", &mut ctx);
+        assert_eq!(execute("echo *.md l*1.txt", &mut ctx).stdout, "keep.md log1.txt log11.txt\n");
+
+        assert_eq!(execute("rm *.txt; echo after", &mut ctx).stdout, "");
+        let question = &ctx.confirmation.as_ref().unwrap().question;
+        assert_eq!(question, "rm: *.txt matched 12 files. Go ahead? [y/N] ");
+        assert_eq!(answer_confirmation("", &mut ctx).stdout, "Nothing was done.");
+        assert_eq!(ctx.fs.read_dir(Path::new("/")).unwrap().len(), 13);
+
+        execute("rm *.txt", &mut ctx);
+        assert_eq!(answer_confirmation("y", &mut ctx).exit_code, 0);
+        assert_eq!(ctx.fs.read_dir(Path::new("/")).unwrap(), ["keep.md"]);
+
+        // Without the interactive screen there is no one to ask
+        let mut ctx = memory_context();
+        execute("touch log{1..3}.txt", &mut ctx);
+        ctx.options.glob_confirm = Some(2);
+        assert!(execute("rm *", &mut ctx).stderr.starts_with("Error: rm: * matched 3 files, more than 2."));
+        ctx.options.glob_confirm = Some(0);
+        assert_eq!(execute("rm *", &mut ctx).exit_code, 0);
+    }
+
+    #[test]
+    fn trash_moves_files_aside_and_restores_them() {
+        let mut ctx = memory_context();
+        execute("mkdir docs; touch docs/notes.txt \"first\"", &mut ctx);
+        let output = execute("trash docs/notes.txt", &mut ctx);
+        assert!(output.stdout.starts_with("Moved 'docs/notes.txt' to the trash as 'notes.txt."), "{}", output.stdout);
+        execute("touch docs/notes.txt \"second\"; trash docs/notes.txt; trash docs", &mut ctx);
+
+        let listing = execute("trash --list", &mut ctx).stdout;
+        let entries: Vec<&str> = listing.lines().collect();
+        assert_eq!(entries.len(), 3);
+        assert!(entries[1].contains("-2  /docs/notes.txt"), "{}", listing);
+        assert!(entries[2].starts_with("docs.") && entries[2].ends_with("  /docs"));
+
+        assert_eq!(execute("trash --restore notes.txt", &mut ctx).exit_code, 1);
+        execute("trash --restore docs", &mut ctx);
+        assert_eq!(execute("trash --restore notes.txt; cat docs/notes.txt", &mut ctx).stdout.lines().last(), Some("second"));
+        assert_eq!(execute("trash --list", &mut ctx).stdout.lines().count(), 1);
+        assert_eq!(execute("trash missing.txt", &mut ctx).exit_code, 1);
+    }
+
+    #[test]
+    fn cd_climbs_no_higher_than_the_jail_root() {
+        let options = Options { jail_root: Some(PathBuf::from("/home/jail")), ..Default::default() };
+        let mut ctx = ShellContext { options, ..memory_context() };
+        for dir in ["/home", "/home/jail", "/home/jail/docs", "/tmp"] {
+            ctx.fs.create_dir(Path::new(dir)).unwrap();
+        }
+        ctx.fs.set_current_dir(Path::new("/home/jail/docs")).unwrap();
+
+        for dir in ["/tmp", "/home", "/", "/tmp/../home"] {
+            let output = execute(&format!("cd {}", dir), &mut ctx);
+            assert_eq!(output.stderr, format!("Error: Permission denied: '{}' is outside the jail.", dir));
+            assert_eq!(output.exit_code, 1);
+        }
+        assert_eq!(execute("pwd", &mut ctx).stdout, "/home/jail/docs");
+
+        let output = execute("cd ../../..; pwd", &mut ctx);
+        assert_eq!((output.exit_code, output.stdout.lines().last()), (0, Some("/home/jail")));
+        assert_eq!(execute("cd docs; cd ~/..; pwd", &mut ctx).stdout.lines().last(), Some("/home/jail"));
+    }
+
+    #[test]
+    fn trash_restores_only_inside_the_jail() {
+        let options = Options { jail_root: Some(PathBuf::from("/jail")), ..Default::default() };
+        let mut ctx = ShellContext { options, ..memory_context() };
+        ctx.fs.create_dir(Path::new("/jail")).unwrap();
+        ctx.fs.create_dir(Path::new("/outside")).unwrap();
+        ctx.fs.set_current_dir(Path::new("/jail")).unwrap();
+        execute("touch f.txt; trash f.txt", &mut ctx);
+        let trashed = execute("trash --list", &mut ctx).stdout.split_whitespace().next().unwrap().to_string();
+
+        let index = format!("{}\t/outside/escaped\n", trashed);
+        ctx.fs.write(Path::new("/jail/.rust_emulator_trash/.index"), &index).unwrap();
+        let output = execute(&format!("trash --restore {}", trashed), &mut ctx);
+        assert_eq!(output.exit_code, 1);
+        assert!(output.stderr.ends_with("'/outside/escaped' is outside the jail."), "{}", output.stderr);
+        assert!(ctx.fs.metadata(Path::new("/outside/escaped")).is_err());
+
+        ctx.fs.write(Path::new("/jail/.rust_emulator_trash/.index"), "../../outside/f.txt\t/jail/f.txt\n").unwrap();
+        assert_eq!(execute("trash --restore f.txt", &mut ctx).exit_code, 1);
+    }
+
+    #[test]
+    fn rename_changes_matching_names_without_overwriting() {
+        let mut ctx = memory_context();
+        execute("mkdir docs; touch docs/a.txt; touch {b.txt,c.txt,c.md,keep.rs}", &mut ctx);
+
+        let output = execute("rename .txt .md docs/a.txt *.txt keep.rs", &mut ctx);
+        assert_eq!(output.stdout, "renamed 'docs/a.txt' -> 'docs/a.md'\nrenamed 'b.txt' -> 'b.md'");
+        assert_eq!(output.stderr, "Error: rename: not renaming 'c.txt', since 'c.md' already exists.");
+        assert_eq!(execute("ls | cat", &mut ctx).stdout, "b.md\nc.md\nc.txt\ndocs\nkeep.rs");
+
+        let output = execute(r"rename 's/^(.)\.md$/old-$1.md/' b.md c.md", &mut ctx);
+        assert_eq!(output.stdout, "renamed 'b.md' -> 'old-b.md'\nrenamed 'c.md' -> 'old-c.md'");
+        assert_eq!(execute("rename 's/./x/g' docs/a.md", &mut ctx).stdout, "renamed 'docs/a.md' -> 'docs/xxxx'");
+        assert_eq!(execute("rename s/[/x/ keep.rs", &mut ctx).exit_code, 1);
+    }
+
+    #[test]
+    fn cp_copies_files_and_preserves_times_with_p() {
+        let mut ctx = memory_context();
+        execute("mkdir backup; echo notes > a.txt; touch b.txt", &mut ctx);
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        ctx.fs.set_modified(Path::new("a.txt"), old).unwrap();
+
+        assert!(execute("cp a.txt c.txt", &mut ctx).stdout.contains("File 'a.txt' copied to 'c.txt'."));
+        assert_eq!(execute("cat c.txt", &mut ctx).stdout, "notes\n");
+        assert_ne!(ctx.fs.metadata(Path::new("c.txt")).unwrap().modified, Some(old));
+        let output = execute("cp -pv a.txt b.txt backup", &mut ctx);
+        assert_eq!(output.stdout, "'a.txt' -> 'backup/a.txt'\n'b.txt' -> 'backup/b.txt'");
+        assert_eq!(ctx.fs.metadata(Path::new("backup/a.txt")).unwrap().modified, Some(old));
+        assert_eq!(execute("cp a.txt b.txt c.txt", &mut ctx).stderr, "Error: cp: target 'c.txt' is not a directory.");
+        assert_eq!(execute("cp backup d", &mut ctx).stderr, "Error: cp: omitting directory 'backup'.");
+    }
+
+    #[test]
+    fn cp_progress_is_shown_for_large_files_only() {
+        let shown = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let messages = Arc::clone(&shown);
+        let mut ctx = ShellContext {
+            progress: Some(Box::new(move |text| messages.lock().unwrap().push(text.to_string()))),
+            ..memory_context()
+        };
+        ctx.fs.write(Path::new("big.bin"), &"x".repeat(2 * 1024 * 1024)).unwrap();
+        ctx.fs.write(Path::new("small.txt"), "x").unwrap();
+
+        execute("mkdir backup; cp big.bin copy.bin; cp --progress small.txt backup", &mut ctx);
+        assert!(shown.lock().unwrap().is_empty());
+        execute("cp --progress big.bin copy.bin", &mut ctx);
+        assert_eq!(*shown.lock().unwrap(), ["Copying 'big.bin': 100%", ""]);
+    }
+
+    #[test]
+    fn typed_lines_are_kept_in_the_history_for_expansion() {
+        let mut ctx = memory_context();
+        for line in ["echo one", "echo two"] {
+            let (line, _) = record_history(line, &mut ctx).unwrap();
+            execute(&line, &mut ctx);
+        }
+
+        assert_eq!(record_history("^two^three^", &mut ctx), Ok(("echo three".to_string(), true)));
+        assert_eq!(record_history("!1 | wc -w", &mut ctx), Ok(("echo one | wc -w".to_string(), true)));
+        assert!(record_history("!nope", &mut ctx).is_err());
+        assert_eq!(record_history("history 2", &mut ctx), Ok(("history 2".to_string(), false)));
+        assert_eq!(execute("history 2", &mut ctx).stdout, "    4  echo one | wc -w\n    5  history 2");
+    }
+
+    #[test]
+    fn history_c_empties_the_history_and_its_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("history");
+        fs::write(&path, "ls\npwd\n").unwrap();
+        let mut ctx = ShellContext { history_path: Some(path.clone()), ..Default::default() };
+        ctx.history = vec!["ls".to_string(), "pwd".to_string()];
+
+        assert_eq!(execute("history -c", &mut ctx).exit_code, 0);
+        assert!(ctx.history.is_empty());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        assert_eq!(execute("history 1 2", &mut ctx).stderr, "Error: Usage: history [count] | history -c");
+    }
+
+    #[test]
+    fn history_control_leaves_out_repeats_and_lines_starting_with_a_space() {
+        let mut ctx = memory_context();
+        ctx.options.history_control = Some("ignoreboth".to_string());
+        for line in ["ls", "ls", " echo secret", "pwd", "ls", "!!"] {
+            record_history(line, &mut ctx).unwrap();
+        }
+        assert_eq!(ctx.history, ["ls", "pwd", "ls"]);
+
+        ctx.options.history_control = Some("ignorespace".to_string());
+        for line in ["pwd", "pwd", " pwd"] {
+            record_history(line, &mut ctx).unwrap();
+        }
+        assert_eq!(ctx.history, ["ls", "pwd", "ls", "pwd", "pwd"]);
+    }
+
+    #[test]
+    fn saved_aliases_are_written_to_the_startup_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(".rustemurc");
+        let mut ctx = ShellContext { rc_path: Some(path.clone()), ..Default::default() };
+
+        let output = execute("alias --save ll='ls -l' la='ls -A'; alias greet='echo hi'; alias --save greet", &mut ctx);
+        assert_eq!((output.stdout.as_str(), output.exit_code), ("", 0));
+        execute("alias --save ll='ls -la'", &mut ctx);
+        let saved = fs::read_to_string(&path).unwrap();
+        assert_eq!(saved, "alias ll='ls -la'\nalias la='ls -A'\nalias greet='echo hi'\n");
+
+        let mut next = ShellContext::default();
+        source_script(&saved, ".rustemurc", &mut next);
+        assert_eq!(next.aliases, ctx.aliases);
+    }
+
+    #[test]
+    fn type_follows_alias_chains() {
+        let mut ctx = ShellContext::default();
+        execute("alias ll='ls -a' l=ll ls='ls -1'", &mut ctx);
+
+        let output = execute("type l cd", &mut ctx);
+        assert_eq!(
+            output.stdout,
+            "l is aliased to 'll'\nll is aliased to 'ls -a'\nls is aliased to 'ls -1'\nls is a shell builtin\ncd is a shell builtin"
+        );
+        assert_eq!(execute("type -a ls", &mut ctx).stdout, "ls is aliased to 'ls -1'\nls is a shell builtin");
+        assert_eq!(execute("type nothing", &mut ctx).stderr, "type: nothing: not found");
+    }
+
+    #[test]
+    fn safe_mode_rejects_paths_leaving_the_directory() {
+        let options = Options { safe: true, ..Default::default() };
+        let mut ctx = ShellContext { options, ..memory_context() };
+
+        for line in ["cat ../../etc/passwd", "cat /etc/passwd", "rm ~/notes.txt", "echo hi > ../out.txt"] {
+            let output = execute(line, &mut ctx);
+            assert_eq!(output.exit_code, 1, "{}", line);
+            assert!(output.stderr.starts_with("Error: Path not allowed:"), "{}", line);
+        }
+        assert_eq!(execute("mkdir docs; touch docs/./a.txt; cat docs/a.txt", &mut ctx).exit_code, 0);
+    }
+
+    #[test]
+    fn continuation_follows_backslashes_and_open_quotes() {
+        assert_eq!(continue_line("echo one \\"), Some("echo one ".to_string()));
+        assert_eq!(continue_line("echo \"open"), Some("echo \"open\n".to_string()));
+        assert_eq!(continue_line("echo 'it\\'"), None);
+        assert_eq!(continue_line("echo \\\\"), None);
+        assert_eq!(continue_line("echo \"done\""), None);
+    }
+
+    #[test]
+    fn words_keep_quoted_spaces_and_lose_their_quotes() {
+        let words = split_words(r#"cut -d " " -f 2 'my file.txt' a\ b "say \"hi\"""#);
+        assert_eq!(words, ["cut", "-d", "\" \"", "-f", "2", "'my file.txt'", r"a\ b", r#""say \"hi\"""#]);
+        let words: Vec<String> = words.into_iter().map(remove_quotes).collect();
+        assert_eq!(words, ["cut", "-d", " ", "-f", "2", "my file.txt", "a b", "say \"hi\""]);
+        assert_eq!(remove_quotes(r"'a\nb'\n\*"), r"a\nb\n*");
+
+        assert_eq!(quote_word("plain.txt"), "plain.txt");
+        assert_eq!(quote_word("it's here"), r"'it'\''s here'");
+        assert_eq!(remove_quotes(&quote_word("it's here")), "it's here");
+    }
+
+    #[test]
+    fn quoted_arguments_reach_commands_whole() {
+        let mut ctx = memory_context();
+        execute("echo 'a b c' > s.txt; touch \"my file.txt\" 'two words'", &mut ctx);
+
+        assert_eq!(execute("cut -d \" \" -f 2 s.txt", &mut ctx).stdout, "b\n");
+        assert_eq!(execute("cat 'my file.txt'", &mut ctx).stdout, "two words\n");
+        let output = execute("grep 'b c' s.txt; awk '{print $3, \"and\", $1}' s.txt", &mut ctx);
+        assert_eq!(output.stdout, "a b c\nc and a\n");
+        assert_eq!(execute("alias say='echo one  two'; say", &mut ctx).stdout, "one two\n");
+        assert_eq!(execute("echo s.txt | xargs grep 'a b'", &mut ctx).stdout, "a b c");
+        execute("echo 'say \"hi\"' >> s.txt", &mut ctx);
+        assert_eq!(execute("grep '\"' s.txt", &mut ctx).stdout, "say \"hi\"");
+        assert_eq!(execute("find . -name 'my*' -exec wc -l {} ';'", &mut ctx).stdout, "      1 ./my file.txt\n");
+        assert_eq!(execute("echo '*.txt' \"{a,b}\"", &mut ctx).stdout, "*.txt {a,b}\n");
+    }
+
+    #[test]
+    fn text_commands_read_files_or_piped_input() {
+        let mut ctx = memory_context();
+        execute("echo -e b,2\\na,1\\nb,2\\nc,3 > data.csv", &mut ctx);
+
+        assert_eq!(execute("sort data.csv | uniq -c", &mut ctx).stdout, "      1 a,1\n      2 b,2\n      1 c,3\n");
+        assert_eq!(execute("cat data.csv | cut -d , -f 2 | sort -rn | head -n 2", &mut ctx).stdout, "3\n2\n");
+        assert_eq!(execute("tail -n1 data.csv | tr a-z A-Z", &mut ctx).stdout, "C,3\n");
+        assert_eq!(execute("wc -l data.csv", &mut ctx).stdout, "      4 data.csv\n");
+        assert_eq!(execute("echo one two | wc -w", &mut ctx).stdout, "      2\n");
+        assert_eq!(execute("sort", &mut ctx).stderr, "Error: File name is required.");
+    }
+
+    #[test]
+    fn awk_prints_fields_of_each_line() {
+        let mut ctx = memory_context();
+        execute("echo -e ann,31\\nbob,27 > ages.csv", &mut ctx);
+
+        assert_eq!(execute("awk -F, '{print $2, $1}' ages.csv", &mut ctx).stdout, "31 ann\n27 bob\n");
+        assert_eq!(execute("echo a  b c | awk '{print \"<\" $3 \">\"}'", &mut ctx).stdout, "<c>\n");
+        assert_eq!(execute("awk '{print $0}' ages.csv | wc -l", &mut ctx).stdout, "      2\n");
+        assert_eq!(execute("awk '$2 > 30' ages.csv", &mut ctx).exit_code, 1);
+    }
+
+    #[test]
+    fn tail_f_follows_the_file_after_printing_its_end() {
+        let mut ctx = memory_context();
+        execute("echo -e a\\nb\\nc > app.log", &mut ctx);
+
+        assert_eq!(execute("tail -f -n 2 app.log", &mut ctx).stdout, "b\nc\n");
+        ctx.fs.append(Path::new("app.log"), "d\n").unwrap();
+        let follow = ctx.follow.as_mut().unwrap();
+        assert_eq!(follow.poll(ctx.fs.as_ref()).unwrap(), ["d"]);
+
+        ctx.follow = None;
+        assert_eq!(execute("echo x | tail -f", &mut ctx).stderr, "Error: tail: -f follows exactly one file.");
+        assert!(ctx.follow.is_none());
+    }
+
+    #[test]
+    fn sed_prints_substitutions_or_edits_files_in_place() {
+        let mut ctx = memory_context();
+        execute("echo -e one fish\\ntwo fish > fish.txt", &mut ctx);
+
+        assert_eq!(execute("sed s/fish/cat/ fish.txt", &mut ctx).stdout, "one cat\ntwo cat\n");
+        assert_eq!(execute("echo aaa | sed s/a/b/g", &mut ctx).stdout, "bbb\n");
+        let output = execute("sed -i 's/^t/T/' fish.txt", &mut ctx);
+        assert_eq!((output.stdout.as_str(), output.exit_code), ("", 0));
+        assert_eq!(execute("cat fish.txt", &mut ctx).stdout, "one fish\nTwo fish\n");
+        assert_eq!(execute("sed -i s/a/b/", &mut ctx).stderr, "Error: sed: -i needs files to edit.");
+    }
+
+    #[test]
+    fn pushd_and_popd_walk_the_directory_stack() {
+        let mut ctx = memory_context();
+        execute("mkdir a; mkdir b", &mut ctx);
+
+        assert_eq!(execute("pushd a", &mut ctx).stdout, "~/a ~");
+        assert_eq!(ctx.current_dir, "/a");
+        assert_eq!(execute("pushd /b", &mut ctx).stdout, "~/b ~/a ~");
+        assert_eq!(execute("pushd", &mut ctx).stdout, "~/a ~/b ~");
+        assert_eq!(execute("popd; pwd", &mut ctx).stdout, "~/b ~\n/b");
+        assert_eq!(ctx.current_dir, "/b");
+        execute("cd missing", &mut ctx);
+        assert_eq!(ctx.current_dir, "/b");
+        assert_eq!(execute("popd", &mut ctx).stdout, "~");
+        assert_eq!(execute("popd", &mut ctx).stderr, "popd: directory stack empty");
+        assert_eq!(execute("pushd", &mut ctx).stderr, "pushd: no other directory");
+        assert_eq!(execute("dirs", &mut ctx).stdout, "~");
+    }
+
+    #[test]
+    fn ls_colors_directories_with_the_theme() {
+        let options = Options { theme: Theme::HIGH_CONTRAST, ..Default::default() };
+        let mut ctx = ShellContext { options, ..memory_context() };
+
+        let output = execute("mkdir docs; touch notes.txt; ls", &mut ctx);
+        assert!(output.stdout.ends_with(&format!("{}\nnotes.txt", Theme::HIGH_CONTRAST.directory("docs"))));
+        assert_eq!(execute("ls | cat", &mut ctx).stdout, "docs\nnotes.txt");
+    }
+
+    #[test]
+    fn no_color_leaves_output_plain() {
+        let theme = Theme { colored: false, ..Theme::DEFAULT };
+        let options = Options { theme, ..Default::default() };
+        let mut ctx = ShellContext { options, ..memory_context() };
+
+        let output = execute("mkdir docs; ls; echo docs | grep -E o; cat missing", &mut ctx);
+        let shown = output.display(&theme);
+        assert!(!shown.contains('\x1b'), "{:?}", shown);
+        assert!(shown.ends_with("docs\ndocs\nError reading file 'missing': No such file or directory"));
+    }
+
+    #[test]
+    fn unknown_command_exits_127() {
+        let output = run("frobnicate");
+        assert_eq!(output.exit_code, 127);
+        assert_eq!(output.stderr, "Unknown command: frobnicate");
+    }
+
+    #[test]
+    fn exit_stops_a_chain() {
+        let mut ctx = ShellContext::default();
+        let output = execute("echo one; exit; echo two", &mut ctx);
+
+        assert!(ctx.exit_requested);
+        assert_eq!(output.stdout, "one\n");
+    }
+
+    #[test]
+    fn commands_use_the_virtual_filesystem() {
+        let mut ctx = memory_context();
+
+        let output = execute("mkdir docs; cd docs; touch notes.txt \"hi\"; pwd; ls; cat notes.txt", &mut ctx);
+        assert_eq!(output.exit_code, 0);
+        assert!(output.stdout.ends_with("/docs\nnotes.txt\nhi\n"));
+        assert!(!Path::new("/docs").exists());
+    }
+}
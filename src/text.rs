@@ -0,0 +1,1166 @@
+//! Text-processing commands that work on lines: `wc`, `sort`, `uniq`, `comm`, `join`,
+//! `cut`, `paste`, `fold`, `expand`, `unexpand`, `tr`, `sed`, `awk`, `head`, and `tail`. Each reads the files it is
+//! given, or piped input when there are none.
+
+use crate::commands::read_inputs;
+use crate::follow::Follow;
+use crate::shell::{expand_path, CommandOutput, ShellContext};
+use regex::Regex;
+use std::cmp::Ordering;
+use unicode_width::UnicodeWidthChar;
+
+/// Number of lines `head` and `tail` show by default.
+const DEFAULT_LINE_COUNT: usize = 10;
+
+/// Width `fold` wraps lines to by default.
+const DEFAULT_FOLD_WIDTH: usize = 80;
+
+/// Distance between the tab stops `fold` counts with.
+const TAB_STOP: usize = 8;
+
+/// Joins lines into command output, ending each with a newline like the real tools.
+fn lines_output<S: AsRef<str>>(lines: &[S]) -> String {
+    lines.iter().map(|line| format!("{}\n", line.as_ref())).collect()
+}
+
+/// Splits `arg` into a flag letter and its attached value, e.g. `-n5` into `('n', "5")`.
+fn split_flag(arg: &str) -> Option<(char, &str)> {
+    let mut chars = arg.strip_prefix('-')?.chars();
+    let flag = chars.next()?;
+    Some((flag, chars.as_str()))
+}
+
+/// Returns the value of a flag, either attached (`-n5`) or the next argument (`-n 5`).
+fn flag_value<'a>(attached: &'a str, rest: &mut impl Iterator<Item = &'a str>, flag: char, cmd: &str) -> Result<&'a str, String> {
+    if !attached.is_empty() {
+        return Ok(attached);
+    }
+    rest.next().ok_or_else(|| format!("Error: {}: option '-{}' requires a value.", cmd, flag))
+}
+
+/// Handles `wc`: counts lines, words, and bytes. `-l`, `-w`, and `-c` select which counts
+/// to show; with none, all three are shown.
+pub fn wc_command(args: Vec<&str>, stdin: Option<&str>, ctx: &ShellContext) -> CommandOutput {
+    let (mut lines, mut words, mut bytes) = (false, false, false);
+    let mut files = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix('-') {
+            Some("-") => {
+                files.extend(args);
+                break;
+            }
+            Some(flags) if !flags.is_empty() => {
+                for flag in flags.chars() {
+                    match flag {
+                        'l' => lines = true,
+                        'w' => words = true,
+                        'c' => bytes = true,
+                        _ => return CommandOutput::error(format!("Error: wc: invalid option '-{}'.", flag)),
+                    }
+                }
+            }
+            _ => files.push(arg),
+        }
+    }
+    if !(lines || words || bytes) {
+        (lines, words, bytes) = (true, true, true);
+    }
+
+    let inputs = read_inputs(&files, stdin, ctx);
+    let mut rows = Vec::new();
+    let mut total = [0; 3];
+    for (name, content) in &inputs.sources {
+        let counts = [content.lines().count(), content.split_whitespace().count(), content.len()];
+        for (sum, count) in total.iter_mut().zip(counts) {
+            *sum += count;
+        }
+        rows.push(format_counts(counts, [lines, words, bytes], (name != "-").then_some(name.as_str())));
+    }
+    if inputs.sources.len() > 1 {
+        rows.push(format_counts(total, [lines, words, bytes], Some("total")));
+    }
+
+    CommandOutput { stdout: lines_output(&rows), stderr: inputs.errors.join("\n"), exit_code: inputs.exit_code() }
+}
+
+/// Formats the selected counts of one `wc` row, followed by the file name if there is one.
+fn format_counts(counts: [usize; 3], shown: [bool; 3], name: Option<&str>) -> String {
+    let mut fields: Vec<String> = counts
+        .iter()
+        .zip(shown)
+        .filter(|(_, shown)| *shown)
+        .map(|(count, _)| format!("{:>7}", count))
+        .collect();
+    fields.extend(name.map(str::to_string));
+    fields.join(" ")
+}
+
+/// Options for `sort`.
+#[derive(Default)]
+pub struct SortOptions {
+    /// Compare lines by their leading number (`-n`).
+    pub numeric: bool,
+    /// Sort in descending order (`-r`).
+    pub reverse: bool,
+    /// Keep only the first of each run of lines with equal keys (`-u`).
+    pub unique: bool,
+    /// Keep lines with equal keys in their input order (`-s`).
+    pub stable: bool,
+    /// Compare only this whitespace-separated field, counting from 1 (`-k N`).
+    pub key: Option<usize>,
+}
+
+/// Sorts lines by text, or by their leading number with `-n`. Lines without a number sort
+/// as zero. With `-k N` only the Nth field is compared, so `-k 2 -n` orders lines by the
+/// number that starts their second field; a line without that field has an empty key.
+///
+/// Lines with equal keys fall back to comparing the whole line, unless `-s` keeps them in
+/// input order. `-u` then drops every line whose key equals the one before it.
+pub fn sort_lines(lines: &mut Vec<&str>, options: &SortOptions) {
+    lines.sort_by(|a, b| {
+        let mut ordering = compare_keys(a, b, options);
+        if !options.stable {
+            ordering = ordering.then_with(|| a.cmp(b));
+        }
+        if options.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    if options.unique {
+        lines.dedup_by(|a, b| compare_keys(a, b, options) == Ordering::Equal);
+    }
+}
+
+/// Compares the sort keys of two lines: the field chosen by `-k`, or the whole line, as
+/// text or with `-n` by leading number.
+fn compare_keys(a: &str, b: &str, options: &SortOptions) -> Ordering {
+    let key = |line| match options.key {
+        Some(field) => line_field(line, field),
+        None => line,
+    };
+    let (a, b) = (key(a), key(b));
+    if options.numeric {
+        leading_number(a).total_cmp(&leading_number(b))
+    } else {
+        a.cmp(b)
+    }
+}
+
+/// Returns the `field`th whitespace-separated field of `line`, counting from 1, or an
+/// empty string if it has fewer fields.
+fn line_field(line: &str, field: usize) -> &str {
+    line.split_whitespace().nth(field - 1).unwrap_or("")
+}
+
+/// Returns the number at the start of `line`, ignoring leading blanks, or zero if there is none.
+fn leading_number(line: &str) -> f64 {
+    let line = line.trim_start();
+    let end = line
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && c == '-')))
+        .map_or(line.len(), |(i, _)| i);
+    line[..end].parse().unwrap_or(0.0)
+}
+
+/// Handles `sort`: prints the lines of all inputs in order. See [`sort_lines`].
+pub fn sort_command(args: Vec<&str>, stdin: Option<&str>, ctx: &ShellContext) -> CommandOutput {
+    let mut options = SortOptions::default();
+    let mut files = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix('-') {
+            Some("-") => {
+                files.extend(args);
+                break;
+            }
+            Some(flags) if !flags.is_empty() => {
+                for (i, flag) in flags.char_indices() {
+                    match flag {
+                        'n' => options.numeric = true,
+                        'r' => options.reverse = true,
+                        'u' => options.unique = true,
+                        's' => options.stable = true,
+                        'k' => {
+                            // The field may follow directly, as in `-k2`, or be the next word
+                            let rest = &flags[i + 1..];
+                            let value = if rest.is_empty() { args.next().unwrap_or_default() } else { rest };
+                            match value.parse::<usize>() {
+                                Ok(field) if field > 0 => options.key = Some(field),
+                                _ => return CommandOutput::error(format!("Error: sort: invalid field '{}'.", value)),
+                            }
+                            break;
+                        }
+                        _ => return CommandOutput::error(format!("Error: sort: invalid option '-{}'.", flag)),
+                    }
+                }
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    let inputs = read_inputs(&files, stdin, ctx);
+    let mut lines: Vec<&str> = inputs.sources.iter().flat_map(|(_, content)| content.lines()).collect();
+    sort_lines(&mut lines, &options);
+    CommandOutput { stdout: lines_output(&lines), stderr: inputs.errors.join("\n"), exit_code: inputs.exit_code() }
+}
+
+/// Handles `uniq`: collapses runs of identical adjacent lines into one. `-c` prefixes each
+/// line with the length of its run.
+pub fn uniq_command(args: Vec<&str>, stdin: Option<&str>, ctx: &ShellContext) -> CommandOutput {
+    let mut count = false;
+    let mut files = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg {
+            "--" => {
+                files.extend(args);
+                break;
+            }
+            "-c" => count = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return CommandOutput::error(format!("Error: uniq: invalid option '{}'.", arg));
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    let inputs = read_inputs(&files, stdin, ctx);
+    let mut runs: Vec<(&str, usize)> = Vec::new();
+    for line in inputs.sources.iter().flat_map(|(_, content)| content.lines()) {
+        match runs.last_mut() {
+            Some((previous, run)) if *previous == line => *run += 1,
+            _ => runs.push((line, 1)),
+        }
+    }
+
+    let lines: Vec<String> = runs
+        .into_iter()
+        .map(|(line, run)| if count { format!("{:>7} {}", run, line) } else { line.to_string() })
+        .collect();
+    CommandOutput { stdout: lines_output(&lines), stderr: inputs.errors.join("\n"), exit_code: inputs.exit_code() }
+}
+
+/// Merges two sorted lists of lines into the three columns of `comm`: lines only in `a`,
+/// lines only in `b`, and lines in both, each column indented by a tab for every column
+/// shown before it. `suppress[i]` leaves out column `i + 1`.
+pub fn comm_lines(a: &[&str], b: &[&str], suppress: [bool; 3]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut emit = |column: usize, line: &str| {
+        if !suppress[column] {
+            let indent = "\t".repeat((0..column).filter(|&earlier| !suppress[earlier]).count());
+            lines.push(format!("{}{}", indent, line));
+        }
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        match (a.get(i), b.get(j)) {
+            (Some(left), Some(right)) if left == right => {
+                emit(2, left);
+                i += 1;
+                j += 1;
+            }
+            (Some(left), Some(right)) if left < right => {
+                emit(0, left);
+                i += 1;
+            }
+            (Some(left), None) => {
+                emit(0, left);
+                i += 1;
+            }
+            (_, Some(right)) => {
+                emit(1, right);
+                j += 1;
+            }
+            (None, None) => break,
+        }
+    }
+    lines
+}
+
+/// Handles `comm`: compares two sorted files, either of which may be `-` for piped input,
+/// in three columns. `-1`, `-2`, and `-3` (or combined, as in `-12`) hide columns. An
+/// input that is not sorted gives a warning and exit status 1, since lines may then be
+/// paired wrongly.
+pub fn comm_command(args: Vec<&str>, stdin: Option<&str>, ctx: &ShellContext) -> CommandOutput {
+    let mut suppress = [false; 3];
+    let mut files = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix('-') {
+            Some("-") => {
+                files.extend(args);
+                break;
+            }
+            Some(flags) if !flags.is_empty() => {
+                for flag in flags.chars() {
+                    match flag.to_digit(10) {
+                        Some(column @ 1..=3) => suppress[column as usize - 1] = true,
+                        _ => return CommandOutput::error(format!("Error: comm: invalid option '-{}'.", flag)),
+                    }
+                }
+            }
+            _ => files.push(arg),
+        }
+    }
+    if files.len() != 2 {
+        return CommandOutput::error("Error: Usage: comm [-1] [-2] [-3] <file1> <file2>");
+    }
+
+    let inputs = read_inputs(&files, stdin, ctx);
+    if !inputs.errors.is_empty() {
+        return CommandOutput::error(inputs.errors.join("\n"));
+    }
+    let sides: Vec<Vec<&str>> = inputs.sources.iter().map(|(_, content)| content.lines().collect()).collect();
+    let warnings: Vec<String> = sides
+        .iter()
+        .enumerate()
+        .filter(|(_, lines)| !lines.is_sorted())
+        .map(|(index, _)| format!("Warning: comm: file {} is not in sorted order.", index + 1))
+        .collect();
+
+    let lines = comm_lines(&sides[0], &sides[1], suppress);
+    let exit_code = if warnings.is_empty() { 0 } else { 1 };
+    CommandOutput { stdout: lines_output(&lines), stderr: warnings.join("\n"), exit_code }
+}
+
+/// Options for `join`.
+#[derive(Default)]
+pub struct JoinOptions {
+    /// The key field of each file, counting from 1 (`-1 F` and `-2 F`; 1 if not given).
+    pub fields: [usize; 2],
+    /// The character between fields (`-t C`). Without it, fields are separated by runs of
+    /// blanks, leading blanks are ignored, and output fields are joined with a space.
+    pub delimiter: Option<char>,
+    /// Also print the lines of each file that pair with nothing (`-a 1`, `-a 2`).
+    pub unpaired: [bool; 2],
+}
+
+/// Splits a `join` input line into its fields.
+fn join_split(line: &str, delimiter: Option<char>) -> Vec<&str> {
+    match delimiter {
+        Some(delimiter) => line.split(delimiter).collect(),
+        None => line.split_whitespace().collect(),
+    }
+}
+
+/// Joins two lists of lines, each sorted on its key field, into one line per pair with equal
+/// keys: the key, then the other fields of the first file's line, then those of the
+/// second's. A key repeated in both files pairs every line with every line. Lines missing
+/// the key field have an empty key.
+pub fn join_lines(left: &[&str], right: &[&str], options: &JoinOptions) -> Vec<String> {
+    let separator = options.delimiter.map_or(" ".to_string(), String::from);
+    let split = |lines: &[&str], side: usize| -> Vec<(String, Vec<String>)> {
+        lines
+            .iter()
+            .map(|line| {
+                let mut fields: Vec<String> = join_split(line, options.delimiter).into_iter().map(str::to_string).collect();
+                let index = options.fields[side].max(1) - 1;
+                let key = if index < fields.len() { fields.remove(index) } else { String::new() };
+                (key, fields)
+            })
+            .collect()
+    };
+    let (left, right) = (split(left, 0), split(right, 1));
+    let format = |key: &str, parts: &[&Vec<String>]| {
+        let mut fields = vec![key];
+        fields.extend(parts.iter().flat_map(|others| others.iter().map(String::as_str)));
+        fields.join(&separator)
+    };
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() || j < right.len() {
+        let ordering = match (left.get(i), right.get(j)) {
+            (Some((left_key, _)), Some((right_key, _))) => left_key.cmp(right_key),
+            (Some(_), None) => Ordering::Less,
+            _ => Ordering::Greater,
+        };
+        match ordering {
+            Ordering::Less => {
+                if options.unpaired[0] {
+                    lines.push(format(&left[i].0, &[&left[i].1]));
+                }
+                i += 1;
+            }
+            Ordering::Greater => {
+                if options.unpaired[1] {
+                    lines.push(format(&right[j].0, &[&right[j].1]));
+                }
+                j += 1;
+            }
+            Ordering::Equal => {
+                let key = &left[i].0;
+                let left_end = i + left[i..].iter().take_while(|(other, _)| other == key).count();
+                let right_end = j + right[j..].iter().take_while(|(other, _)| other == key).count();
+                for (_, left_fields) in &left[i..left_end] {
+                    for (_, right_fields) in &right[j..right_end] {
+                        lines.push(format(key, &[left_fields, right_fields]));
+                    }
+                }
+                (i, j) = (left_end, right_end);
+            }
+        }
+    }
+    lines
+}
+
+/// Handles `join`: pairs the lines of two files, either of which may be `-` for piped
+/// input, whose key fields are equal. See [`join_lines`].
+pub fn join_command(args: Vec<&str>, stdin: Option<&str>, ctx: &ShellContext) -> CommandOutput {
+    let mut options = JoinOptions { fields: [1, 1], ..Default::default() };
+    let mut files = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, value) = match split_flag(arg) {
+            Some(('-', "")) => {
+                files.extend(args);
+                break;
+            }
+            Some((flag @ ('1' | '2' | 't' | 'a'), attached)) => match flag_value(attached, &mut args, flag, "join") {
+                Ok(value) => (flag, value),
+                Err(e) => return CommandOutput::error(e),
+            },
+            Some(_) if arg != "-" => return CommandOutput::error(format!("Error: join: invalid option '{}'.", arg)),
+            _ => {
+                files.push(arg);
+                continue;
+            }
+        };
+        match (flag, value.parse::<usize>()) {
+            ('t', _) => match value.chars().collect::<Vec<_>>()[..] {
+                [delimiter] => options.delimiter = Some(delimiter),
+                _ => return CommandOutput::error("Error: join: the delimiter must be a single character."),
+            },
+            ('a', Ok(side @ (1 | 2))) => options.unpaired[side - 1] = true,
+            ('a', _) => return CommandOutput::error(format!("Error: join: invalid file number '{}'.", value)),
+            (_, Ok(field)) if field > 0 => options.fields[flag as usize - '1' as usize] = field,
+            _ => return CommandOutput::error(format!("Error: join: invalid field number '{}'.", value)),
+        }
+    }
+    if files.len() != 2 {
+        return CommandOutput::error("Error: Usage: join [-1 <field>] [-2 <field>] [-t <char>] [-a 1|2] <file1> <file2>");
+    }
+
+    let inputs = read_inputs(&files, stdin, ctx);
+    if !inputs.errors.is_empty() {
+        return CommandOutput::error(inputs.errors.join("\n"));
+    }
+    let sides: Vec<Vec<&str>> = inputs.sources.iter().map(|(_, content)| content.lines().collect()).collect();
+    CommandOutput::success(lines_output(&join_lines(&sides[0], &sides[1], &options)))
+}
+
+/// Parses a `cut` list such as `1,3-5,7-` into inclusive, 1-based ranges.
+fn parse_ranges(list: &str) -> Option<Vec<(usize, usize)>> {
+    list.split(',')
+        .map(|part| {
+            let (start, end) = match part.split_once('-') {
+                Some((start, end)) => (
+                    if start.is_empty() { 1 } else { start.parse().ok()? },
+                    if end.is_empty() { usize::MAX } else { end.parse().ok()? },
+                ),
+                None => {
+                    let n = part.parse().ok()?;
+                    (n, n)
+                }
+            };
+            (start >= 1 && start <= end).then_some((start, end))
+        })
+        .collect()
+}
+
+/// Returns whether the 1-based position `n` falls in any of the ranges.
+fn in_ranges(ranges: &[(usize, usize)], n: usize) -> bool {
+    ranges.iter().any(|&(start, end)| (start..=end).contains(&n))
+}
+
+/// Handles `cut`: prints selected fields (`-f`, split on `-d`, a tab by default) or
+/// characters (`-c`) of each line.
+pub fn cut_command(args: Vec<&str>, stdin: Option<&str>, ctx: &ShellContext) -> CommandOutput {
+    let mut fields = None;
+    let mut characters = None;
+    let mut delimiter = "\t";
+    let mut files = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let value = match split_flag(arg) {
+            Some(('-', "")) => {
+                files.extend(args);
+                break;
+            }
+            Some((flag @ ('f' | 'c' | 'd'), attached)) => match flag_value(attached, &mut args, flag, "cut") {
+                Ok(value) => (flag, value),
+                Err(e) => return CommandOutput::error(e),
+            },
+            Some(_) if arg != "-" => return CommandOutput::error(format!("Error: cut: invalid option '{}'.", arg)),
+            _ => {
+                files.push(arg);
+                continue;
+            }
+        };
+        match value {
+            ('d', value) if value.chars().count() == 1 => delimiter = value,
+            ('d', _) => return CommandOutput::error("Error: cut: the delimiter must be a single character."),
+            (flag, list) => match parse_ranges(list) {
+                Some(ranges) if flag == 'f' => fields = Some(ranges),
+                Some(ranges) => characters = Some(ranges),
+                None => return CommandOutput::error(format!("Error: cut: invalid list '{}'.", list)),
+            },
+        }
+    }
+
+    let inputs = read_inputs(&files, stdin, ctx);
+    let lines = inputs.sources.iter().flat_map(|(_, content)| content.lines());
+    let selected: Vec<String> = match (fields, characters) {
+        (Some(ranges), None) => lines
+            .map(|line| {
+                // Like GNU cut, lines without the delimiter are printed whole
+                if !line.contains(delimiter) {
+                    return line.to_string();
+                }
+                let kept: Vec<&str> = line
+                    .split(delimiter)
+                    .enumerate()
+                    .filter(|(i, _)| in_ranges(&ranges, i + 1))
+                    .map(|(_, field)| field)
+                    .collect();
+                kept.join(delimiter)
+            })
+            .collect(),
+        (None, Some(ranges)) => lines
+            .map(|line| line.chars().enumerate().filter(|(i, _)| in_ranges(&ranges, i + 1)).map(|(_, c)| c).collect())
+            .collect(),
+        _ => return CommandOutput::error("Error: Usage: cut -f <list> [-d <delimiter>] | -c <list> [file]..."),
+    };
+    CommandOutput { stdout: lines_output(&selected), stderr: inputs.errors.join("\n"), exit_code: inputs.exit_code() }
+}
+
+/// Joins `fields` with the delimiters in turn, starting over after the last one, so
+/// `-d ',;'` puts a comma after the first field, a semicolon after the second, and so on.
+fn join_fields(fields: &[&str], delimiters: &[char]) -> String {
+    let mut joined = String::new();
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            joined.push(delimiters[(i - 1) % delimiters.len()]);
+        }
+        joined.push_str(field);
+    }
+    joined
+}
+
+/// Merges the lines of each file side by side: the first lines of all files make the first
+/// output line, and so on. Files that run out of lines give empty fields.
+pub fn paste_files(files: &[Vec<&str>], delimiters: &[char]) -> Vec<String> {
+    let rows = files.iter().map(Vec::len).max().unwrap_or(0);
+    (0..rows)
+        .map(|row| {
+            let fields: Vec<&str> = files.iter().map(|lines| lines.get(row).copied().unwrap_or("")).collect();
+            join_fields(&fields, delimiters)
+        })
+        .collect()
+}
+
+/// Handles `paste`: merges corresponding lines of the files, separated by tabs or the
+/// characters given with `-d`. With `-s`, each file's lines are joined onto one line
+/// instead. A file named `-` reads piped input.
+pub fn paste_command(args: Vec<&str>, stdin: Option<&str>, ctx: &ShellContext) -> CommandOutput {
+    let mut delimiters = vec!['\t'];
+    let mut serial = false;
+    let mut files = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match split_flag(arg) {
+            Some(('-', "")) => {
+                files.extend(args);
+                break;
+            }
+            Some(('s', "")) => serial = true,
+            Some(('d', attached)) => match flag_value(attached, &mut args, 'd', "paste") {
+                Ok(value) if !value.is_empty() => delimiters = value.chars().collect(),
+                Ok(_) => return CommandOutput::error("Error: paste: the delimiter list is empty."),
+                Err(e) => return CommandOutput::error(e),
+            },
+            Some(_) if arg != "-" => return CommandOutput::error(format!("Error: paste: invalid option '{}'.", arg)),
+            _ => files.push(arg),
+        }
+    }
+
+    let inputs = read_inputs(&files, stdin, ctx);
+    let contents: Vec<Vec<&str>> = inputs.sources.iter().map(|(_, content)| content.lines().collect()).collect();
+    let lines = if serial {
+        contents.iter().map(|lines| join_fields(lines, &delimiters)).collect()
+    } else {
+        paste_files(&contents, &delimiters)
+    };
+    CommandOutput { stdout: lines_output(&lines), stderr: inputs.errors.join("\n"), exit_code: inputs.exit_code() }
+}
+
+/// Returns the column after `c` when it is written at `column`: a tab moves to the next
+/// multiple of `tab_stop`, and other characters cover as many terminal cells as they take up.
+fn advance(column: usize, c: char, tab_stop: usize) -> usize {
+    match c {
+        '\t' => (column / tab_stop + 1) * tab_stop,
+        c => column + c.width().unwrap_or(0),
+    }
+}
+
+/// Wraps each line of `content` into rows of at most `width` columns, counting wide
+/// characters as two. With `at_spaces`, a row is broken after its last blank when it has
+/// one, so words stay whole; a word longer than a row is still split.
+pub fn fold_lines(content: &str, width: usize, at_spaces: bool) -> Vec<String> {
+    let mut folded = Vec::new();
+    for line in content.lines() {
+        let mut row = String::new();
+        let mut column = 0;
+        for c in line.chars() {
+            if !row.is_empty() && advance(column, c, TAB_STOP) > width {
+                let rest = match row.rfind([' ', '\t']).filter(|_| at_spaces) {
+                    Some(blank) => row.split_off(blank + 1),
+                    None => String::new(),
+                };
+                folded.push(std::mem::replace(&mut row, rest));
+                column = row.chars().fold(0, |column, c| advance(column, c, TAB_STOP));
+            }
+            row.push(c);
+            column = advance(column, c, TAB_STOP);
+        }
+        folded.push(row);
+    }
+    folded
+}
+
+/// Handles `fold`: rewrites the input with long lines wrapped at 80 columns, or the width
+/// given with `-w`. `-s` breaks lines at blanks rather than in the middle of words.
+pub fn fold_command(args: Vec<&str>, stdin: Option<&str>, ctx: &ShellContext) -> CommandOutput {
+    let mut width = DEFAULT_FOLD_WIDTH;
+    let mut at_spaces = false;
+    let mut files = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match split_flag(arg) {
+            Some(('-', "")) => {
+                files.extend(args);
+                break;
+            }
+            Some(('s', "")) => at_spaces = true,
+            Some(('w', attached)) => match flag_value(attached, &mut args, 'w', "fold").map(str::parse) {
+                Ok(Ok(n)) if n > 0 => width = n,
+                Ok(_) => return CommandOutput::error("Error: fold: invalid width."),
+                Err(e) => return CommandOutput::error(e),
+            },
+            Some(_) if arg != "-" => return CommandOutput::error(format!("Error: fold: invalid option '{}'.", arg)),
+            _ => files.push(arg),
+        }
+    }
+
+    let inputs = read_inputs(&files, stdin, ctx);
+    let lines: Vec<String> =
+        inputs.sources.iter().flat_map(|(_, content)| fold_lines(content, width, at_spaces)).collect();
+    CommandOutput { stdout: lines_output(&lines), stderr: inputs.errors.join("\n"), exit_code: inputs.exit_code() }
+}
+
+/// Replaces each tab in `line` with the spaces that reach the same column.
+pub fn expand_tabs(line: &str, tab_stop: usize) -> String {
+    let mut expanded = String::new();
+    let mut column = 0;
+    for c in line.chars() {
+        let next = advance(column, c, tab_stop);
+        if c == '\t' {
+            expanded.extend(std::iter::repeat_n(' ', next - column));
+        } else {
+            expanded.push(c);
+        }
+        column = next;
+    }
+    expanded
+}
+
+/// Rewrites the blanks at the start of `line` as tabs, with spaces only for what is left
+/// over after the last tab stop. Blanks after the first other character are kept as they
+/// are.
+pub fn unexpand_tabs(line: &str, tab_stop: usize) -> String {
+    let indent_len = line.len() - line.trim_start_matches([' ', '\t']).len();
+    let (indent, rest) = line.split_at(indent_len);
+    let columns = indent.chars().fold(0, |column, c| advance(column, c, tab_stop));
+    format!("{}{}{}", "\t".repeat(columns / tab_stop), " ".repeat(columns % tab_stop), rest)
+}
+
+/// Handles `expand` and `unexpand`: converts tabs to spaces, or leading spaces to tabs, with
+/// tab stops as far apart as `-t` gives, or the session's tab size (8 unless set).
+pub fn expand_command(cmd: &str, args: Vec<&str>, stdin: Option<&str>, ctx: &ShellContext) -> CommandOutput {
+    let mut tab_stop = ctx.tab_size();
+    let mut files = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match split_flag(arg) {
+            Some(('-', "")) => {
+                files.extend(args);
+                break;
+            }
+            Some(('t', attached)) => match flag_value(attached, &mut args, 't', cmd).map(str::parse) {
+                Ok(Ok(n)) if n > 0 => tab_stop = n,
+                Ok(_) => return CommandOutput::error(format!("Error: {}: invalid tab size.", cmd)),
+                Err(e) => return CommandOutput::error(e),
+            },
+            Some(_) if arg != "-" => return CommandOutput::error(format!("Error: {}: invalid option '{}'.", cmd, arg)),
+            _ => files.push(arg),
+        }
+    }
+
+    let convert = if cmd == "expand" { expand_tabs } else { unexpand_tabs };
+    let inputs = read_inputs(&files, stdin, ctx);
+    let lines: Vec<String> =
+        inputs.sources.iter().flat_map(|(_, content)| content.lines()).map(|line| convert(line, tab_stop)).collect();
+    CommandOutput { stdout: lines_output(&lines), stderr: inputs.errors.join("\n"), exit_code: inputs.exit_code() }
+}
+
+/// Expands a `tr` set such as `a-z` into its characters.
+fn expand_set(set: &str) -> Vec<char> {
+    let chars: Vec<char> = set.chars().collect();
+    let mut expanded = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if i + 2 < chars.len() && chars[i + 1] == '-' && chars[i] <= chars[i + 2] {
+            expanded.extend(chars[i]..=chars[i + 2]);
+            i += 3;
+        } else {
+            expanded.push(chars[i]);
+            i += 1;
+        }
+    }
+    expanded
+}
+
+/// Handles `tr`: replaces each character of the first set in piped input with the
+/// character at the same position in the second, or deletes them with `-d`. If the second
+/// set is shorter, its last character is repeated.
+pub fn tr_command(args: Vec<&str>, stdin: Option<&str>) -> CommandOutput {
+    let delete = args.first() == Some(&"-d");
+    let sets: Vec<Vec<char>> = args.iter().skip(usize::from(delete)).map(|set| expand_set(set)).collect();
+    let Some(input) = stdin else {
+        return CommandOutput::error("Error: tr only reads piped input.");
+    };
+
+    let output: String = match (delete, sets.as_slice()) {
+        (true, [from]) => input.chars().filter(|c| !from.contains(c)).collect(),
+        (false, [from, to]) if !to.is_empty() => input
+            .chars()
+            .map(|c| match from.iter().position(|&f| f == c) {
+                Some(i) => to[i.min(to.len() - 1)],
+                None => c,
+            })
+            .collect(),
+        _ => return CommandOutput::error("Error: Usage: tr <set1> <set2> | tr -d <set>"),
+    };
+    CommandOutput::success(output)
+}
+
+/// Parses a sed substitution such as `s/old/new/` or `s|a|b|g` into its pattern, its
+/// replacement in the regex crate's syntax, and whether the `g` flag replaces every match.
+fn parse_substitution(expression: &str) -> Result<(Regex, String, bool), String> {
+    let mut chars = expression.chars();
+    let Some(delimiter) = chars.next().filter(|&c| c == 's').and(chars.next()).filter(|c| !c.is_alphanumeric()) else {
+        return Err(format!("Error: sed: unknown command '{}'; only s/<regex>/<replacement>/[g] is supported.", expression));
+    };
+    let parts: Vec<&str> = chars.as_str().split(delimiter).collect();
+    let [pattern, replacement, flags] = parts[..] else {
+        return Err(format!("Error: sed: '{}' is not a complete s{1}regex{1}replacement{1} expression.", expression, delimiter));
+    };
+    let global = match flags {
+        "" => false,
+        "g" => true,
+        _ => return Err(format!("Error: sed: unknown flags '{}' in '{}'.", flags, expression)),
+    };
+    match Regex::new(pattern) {
+        Ok(regex) => Ok((regex, sed_replacement(replacement), global)),
+        Err(e) => Err(format!("Error: sed: invalid pattern '{}': {}", pattern, e)),
+    }
+}
+
+/// Rewrites a sed replacement for the regex crate: `&` becomes the whole match, `\1` to
+/// `\9` the numbered groups, and a backslash before any other character keeps it as is.
+fn sed_replacement(replacement: &str) -> String {
+    let mut converted = String::new();
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => converted.push_str("${0}"),
+            '$' => converted.push_str("$$"),
+            '\\' => match chars.next() {
+                Some(digit) if digit.is_ascii_digit() => converted.push_str(&format!("${{{}}}", digit)),
+                Some('n') => converted.push('\n'),
+                Some('$') => converted.push_str("$$"),
+                Some(other) => converted.push(other),
+                None => converted.push('\\'),
+            },
+            _ => converted.push(c),
+        }
+    }
+    converted
+}
+
+/// Replaces the first match of `pattern` on each line of `content` with `replacement`, or
+/// every match when `global` is set. Line endings are kept as they were, so `$` in the
+/// pattern matches at the end of each line.
+pub fn sed_substitute(content: &str, pattern: &Regex, replacement: &str, global: bool) -> String {
+    content
+        .split_inclusive('\n')
+        .map(|line| {
+            let body = line.trim_end_matches(['\n', '\r']);
+            let ending = &line[body.len()..];
+            let replaced = if global { pattern.replace_all(body, replacement) } else { pattern.replace(body, replacement) };
+            format!("{}{}", replaced, ending)
+        })
+        .collect()
+}
+
+/// Handles `sed`: applies an `s/<regex>/<replacement>/[g]` substitution to every line and
+/// prints the result, or with `-i` writes it back to each file instead.
+pub fn sed_command(args: Vec<&str>, stdin: Option<&str>, ctx: &ShellContext) -> CommandOutput {
+    let mut in_place = false;
+    let mut expression = None;
+    let mut files = Vec::new();
+    for arg in args {
+        match arg {
+            "-i" => in_place = true,
+            _ if arg.starts_with('-') && arg != "-" => {
+                return CommandOutput::error(format!("Error: sed: invalid option '{}'.", arg))
+            }
+            _ if expression.is_none() => expression = Some(arg),
+            _ => files.push(arg),
+        }
+    }
+    let Some(expression) = expression else {
+        return CommandOutput::error("Error: Usage: sed [-i] s/<regex>/<replacement>/[g] [filename]...");
+    };
+    let (pattern, replacement, global) = match parse_substitution(expression) {
+        Ok(substitution) => substitution,
+        Err(e) => return CommandOutput::error(e),
+    };
+
+    if !in_place {
+        let inputs = read_inputs(&files, stdin, ctx);
+        let stdout: String =
+            inputs.sources.iter().map(|(_, content)| sed_substitute(content, &pattern, &replacement, global)).collect();
+        return CommandOutput { stdout, stderr: inputs.errors.join("\n"), exit_code: inputs.exit_code() };
+    }
+    if ctx.options.read_only {
+        return CommandOutput::error("Error: 'sed -i' is disabled in read-only mode.");
+    }
+    if files.is_empty() || files.contains(&"-") {
+        return CommandOutput::error("Error: sed: -i needs files to edit.");
+    }
+    let inputs = read_inputs(&files, None, ctx);
+    let mut errors = inputs.errors.clone();
+    for (name, content) in &inputs.sources {
+        let written = expand_path(ctx, name).and_then(|path| {
+            ctx.fs.write(&path, &sed_substitute(content, &pattern, &replacement, global)).map_err(|e| e.to_string())
+        });
+        if let Err(e) = written {
+            errors.push(format!("Error: sed: could not write '{}': {}", name, e));
+        }
+    }
+    let exit_code = if errors.is_empty() { 0 } else { 1 };
+    CommandOutput { stdout: String::new(), stderr: errors.join("\n"), exit_code }
+}
+
+/// One part of an `awk` `print` action.
+#[derive(Debug, PartialEq)]
+pub enum PrintItem {
+    /// A field of the line, `$1` onwards, or the whole line for `$0`.
+    Field(usize),
+    /// A string literal, or the space a comma between items prints.
+    Text(String),
+}
+
+/// Parses an `awk` program of the form `{print ...}` into what it prints. A bare `print`
+/// prints the whole line.
+fn parse_print(program: &str) -> Result<Vec<PrintItem>, String> {
+    let unsupported = || format!("Error: awk: unsupported program '{}'; only {{print ...}} is supported.", program);
+    let action = program.trim().strip_prefix('{').and_then(|rest| rest.strip_suffix('}')).ok_or_else(unsupported)?;
+    let Some(mut rest) = action.trim().strip_prefix("print") else {
+        return Err(unsupported());
+    };
+    if rest.trim().is_empty() {
+        return Ok(vec![PrintItem::Field(0)]);
+    }
+    if !rest.starts_with(char::is_whitespace) && !rest.starts_with(['$', '"']) {
+        return Err(unsupported());
+    }
+
+    let mut items = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix(',') {
+            items.push(PrintItem::Text(" ".to_string()));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('$') {
+            let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let Ok(field) = after[..digits].parse() else {
+                return Err(format!("Error: awk: expected a field number after '$' in '{}'.", program));
+            };
+            items.push(PrintItem::Field(field));
+            rest = &after[digits..];
+        } else if let Some(after) = rest.strip_prefix('"') {
+            let mut text = String::new();
+            let mut chars = after.char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((index, '"')) => break index,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, 't')) => text.push('\t'),
+                        Some((_, 'n')) => text.push('\n'),
+                        Some((_, c)) => text.push(c),
+                        None => return Err(format!("Error: awk: unterminated string in '{}'.", program)),
+                    },
+                    Some((_, c)) => text.push(c),
+                    None => return Err(format!("Error: awk: unterminated string in '{}'.", program)),
+                }
+            };
+            items.push(PrintItem::Text(text));
+            rest = &after[end + 1..];
+        } else if rest.is_empty() {
+            return Ok(items);
+        } else {
+            return Err(unsupported());
+        }
+    }
+}
+
+/// Prints `fields` for each line of `content`. Fields are split on runs of whitespace, or
+/// on each occurrence of `separator` when one is given; a field past the end is empty.
+pub fn awk_print(content: &str, fields: &[PrintItem], separator: Option<&str>) -> Vec<String> {
+    content
+        .lines()
+        .map(|line| {
+            let split: Vec<&str> = match separator {
+                Some(separator) => line.split(separator).collect(),
+                None => line.split_whitespace().collect(),
+            };
+            fields
+                .iter()
+                .map(|item| match item {
+                    PrintItem::Field(0) => line,
+                    PrintItem::Field(n) => split.get(n - 1).copied().unwrap_or(""),
+                    PrintItem::Text(text) => text.as_str(),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Handles `awk`, for the `{print ...}` form only: prints fields (`$1`, `$2`, ..., or
+/// `$0` for the whole line) and string literals for each line. `-F` sets the field
+/// separator, which is otherwise any run of whitespace.
+pub fn awk_command(args: Vec<&str>, stdin: Option<&str>, ctx: &ShellContext) -> CommandOutput {
+    let usage = "Error: Usage: awk [-F <separator>] '{print $1, $2}' [filename]...";
+    let mut words = args.into_iter();
+    let mut separator = None;
+    let mut program = None;
+    let mut files = Vec::new();
+    while let Some(word) = words.next() {
+        match word.strip_prefix("-F") {
+            Some(attached) if program.is_none() => {
+                let value = if attached.is_empty() { words.next() } else { Some(attached) };
+                match value {
+                    Some("\\t") => separator = Some("\t"),
+                    Some(value) if !value.is_empty() => separator = Some(value),
+                    _ => return CommandOutput::error("Error: awk: -F needs a separator."),
+                }
+            }
+            _ if program.is_none() && word.starts_with('-') => {
+                return CommandOutput::error(format!("Error: awk: invalid option '{}'.", word))
+            }
+            _ if program.is_none() => program = Some(word),
+            _ => files.push(word),
+        }
+    }
+    let Some(program) = program else {
+        return CommandOutput::error(usage);
+    };
+    let fields = match parse_print(program) {
+        Ok(fields) => fields,
+        Err(e) => return CommandOutput::error(e),
+    };
+
+    let inputs = read_inputs(&files, stdin, ctx);
+    let lines: Vec<String> =
+        inputs.sources.iter().flat_map(|(_, content)| awk_print(content, &fields, separator)).collect();
+    CommandOutput { stdout: lines_output(&lines), stderr: inputs.errors.join("\n"), exit_code: inputs.exit_code() }
+}
+
+/// Handles `head` and `tail`: prints the first or last lines of each input (10 unless `-n`
+/// says otherwise), with a `==> name <==` header before each file when there are several.
+///
+/// `tail -f` then keeps following its one file, showing lines as they are added, until the
+/// user stops it on the interactive screen.
+pub fn head_tail_command(cmd: &str, args: Vec<&str>, stdin: Option<&str>, ctx: &mut ShellContext) -> CommandOutput {
+    let mut count = DEFAULT_LINE_COUNT;
+    let mut follow = false;
+    let mut files = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match split_flag(arg) {
+            Some(('-', "")) => {
+                files.extend(args);
+                break;
+            }
+            Some(('f', "")) if cmd == "tail" => follow = true,
+            Some(('n', attached)) => match flag_value(attached, &mut args, 'n', cmd).map(str::parse) {
+                Ok(Ok(n)) => count = n,
+                Ok(Err(_)) => return CommandOutput::error(format!("Error: {}: invalid number of lines.", cmd)),
+                Err(e) => return CommandOutput::error(e),
+            },
+            Some(_) if arg != "-" => return CommandOutput::error(format!("Error: {}: invalid option '{}'.", cmd, arg)),
+            _ => files.push(arg),
+        }
+    }
+
+    let inputs = read_inputs(&files, stdin, ctx);
+    let mut shown = Vec::new();
+    for (name, content) in &inputs.sources {
+        if inputs.sources.len() > 1 {
+            if !shown.is_empty() {
+                shown.push(String::new());
+            }
+            shown.push(format!("==> {} <==", name));
+        }
+        let lines: Vec<&str> = content.lines().collect();
+        let range = if cmd == "head" { 0..count.min(lines.len()) } else { lines.len().saturating_sub(count)..lines.len() };
+        shown.extend(lines[range].iter().map(|line| line.to_string()));
+    }
+
+    if follow && inputs.errors.is_empty() {
+        let (name, content) = match (files.as_slice(), inputs.sources.as_slice()) {
+            ([name], [(_, content)]) if *name != "-" => (*name, content),
+            _ => return CommandOutput::error("Error: tail: -f follows exactly one file."),
+        };
+        match expand_path(ctx, name) {
+            Ok(path) => ctx.follow = Some(Follow::new(name, path, content.len() as u64)),
+            Err(e) => return CommandOutput::error(e),
+        }
+    }
+    CommandOutput { stdout: lines_output(&shown), stderr: inputs.errors.join("\n"), exit_code: inputs.exit_code() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_sort_compares_leading_numbers() {
+        let mut lines = vec!["10 apples", "9 pears", "x", "-1 debt", "9 figs"];
+        sort_lines(&mut lines, &SortOptions { numeric: true, ..Default::default() });
+        assert_eq!(lines, vec!["-1 debt", "x", "9 figs", "9 pears", "10 apples"]);
+
+        sort_lines(&mut lines, &SortOptions { reverse: true, ..Default::default() });
+        assert_eq!(lines, vec!["x", "9 pears", "9 figs", "10 apples", "-1 debt"]);
+    }
+
+    #[test]
+    fn fields_stable_order_and_unique_keys() {
+        let mut lines = vec!["carol 30", "alice 25", "bob 30", "dave 4"];
+        sort_lines(&mut lines, &SortOptions { key: Some(2), numeric: true, ..Default::default() });
+        assert_eq!(lines, vec!["dave 4", "alice 25", "bob 30", "carol 30"]);
+
+        let mut lines = vec!["carol 30", "alice 25", "bob 30", "dave 4"];
+        sort_lines(&mut lines, &SortOptions { key: Some(2), numeric: true, stable: true, reverse: true, ..Default::default() });
+        assert_eq!(lines, vec!["carol 30", "bob 30", "alice 25", "dave 4"]);
+
+        sort_lines(&mut lines, &SortOptions { key: Some(2), unique: true, ..Default::default() });
+        assert_eq!(lines, vec!["alice 25", "bob 30", "dave 4"]);
+
+        let mut lines = vec!["b", "a", "b", "a"];
+        sort_lines(&mut lines, &SortOptions { unique: true, ..Default::default() });
+        assert_eq!(lines, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn comm_merges_sorted_inputs_into_columns() {
+        let (a, b) = (["apple", "banana", "cherry"], ["banana", "date"]);
+        assert_eq!(comm_lines(&a, &b, [false; 3]), ["apple", "\t\tbanana", "cherry", "\tdate"]);
+        assert_eq!(comm_lines(&a, &b, [true, false, false]), ["\tbanana", "date"]);
+        assert_eq!(comm_lines(&a, &b, [true, true, false]), ["banana"]);
+        assert_eq!(comm_lines(&a, &[], [false, false, true]), ["apple", "banana", "cherry"]);
+    }
+
+    #[test]
+    fn join_pairs_lines_on_their_key_fields() {
+        let names = ["1 alice", "2 bob", "2 bobby", "4 dave"];
+        let ages = ["30 1", "25 2", "40 3"];
+        let options = JoinOptions { fields: [1, 2], ..Default::default() };
+        assert_eq!(join_lines(&names, &ages, &options), ["1 alice 30", "2 bob 25", "2 bobby 25"]);
+
+        let options = JoinOptions { fields: [1, 2], unpaired: [true, true], ..Default::default() };
+        assert_eq!(join_lines(&names, &ages, &options), ["1 alice 30", "2 bob 25", "2 bobby 25", "3 40", "4 dave"]);
+
+        let options = JoinOptions { fields: [2, 1], delimiter: Some(','), ..Default::default() };
+        assert_eq!(join_lines(&["x,a b,1"], &["a b,y"], &options), ["a b,x,1,y"]);
+    }
+
+    #[test]
+    fn fold_counts_columns_and_can_keep_words_whole() {
+        assert_eq!(fold_lines("abcdefgh\n\nxy", 3, false), ["abc", "def", "gh", "", "xy"]);
+        assert_eq!(fold_lines("the quick brown fox", 10, true), ["the quick ", "brown fox"]);
+        assert_eq!(fold_lines("a verylongword", 5, true), ["a ", "veryl", "ongwo", "rd"]);
+        assert_eq!(fold_lines("日本語です", 5, false), ["日本", "語で", "す"]);
+        assert_eq!(fold_lines("\tab", 9, false), ["\ta", "b"]);
+    }
+
+    #[test]
+    fn tabs_convert_to_spaces_by_column_and_back() {
+        assert_eq!(expand_tabs("a\tb\tc", 4), "a   b   c");
+        assert_eq!(expand_tabs("日本\tx", 8), "日本    x");
+        assert_eq!(expand_tabs("abcd\te", 4), "abcd    e");
+        assert_eq!(unexpand_tabs("          x  y", 4), "\t\t  x  y");
+        assert_eq!(unexpand_tabs("  \t x", 8), "\t x");
+        assert_eq!(unexpand_tabs(&expand_tabs("\t\tcode", 8), 8), "\t\tcode");
+    }
+
+    #[test]
+    fn paste_pads_short_files_and_cycles_delimiters() {
+        let files = [vec!["1", "2", "3"], vec!["a"], vec!["x", "y"]];
+        assert_eq!(paste_files(&files, &['\t']), ["1\ta\tx", "2\t\ty", "3\t\t"]);
+        assert_eq!(paste_files(&files, &[',', ';']), ["1,a;x", "2,;y", "3,;"]);
+        assert_eq!(join_fields(&["1", "2", "3", "4"], &[',', ';']), "1,2;3,4");
+    }
+
+    #[test]
+    fn cut_lists_accept_single_positions_and_ranges() {
+        assert_eq!(parse_ranges("1,3-4,6-"), Some(vec![(1, 1), (3, 4), (6, usize::MAX)]));
+        assert_eq!(parse_ranges("-2"), Some(vec![(1, 2)]));
+        assert_eq!(parse_ranges("0"), None);
+        assert_eq!(parse_ranges("3-1"), None);
+    }
+
+    #[test]
+    fn sed_substitutes_on_each_line() {
+        let (pattern, replacement, global) = parse_substitution(r"s|(\w+)@(\w+)|\2 at \1 (&)|").unwrap();
+        assert!(!global);
+        assert_eq!(sed_substitute("a@b c@d\r\nx\n", &pattern, &replacement, global), "b at a (a@b) c@d\r\nx\n");
+        let (pattern, replacement, global) = parse_substitution("s/o$/0/g").unwrap();
+        assert_eq!(sed_substitute("foo\nboo", &pattern, &replacement, global), "fo0\nbo0");
+        assert!(parse_substitution("s/a/b").is_err());
+        assert!(parse_substitution("y/a/b/").is_err());
+    }
+
+    #[test]
+    fn awk_prints_fields_and_literals() {
+        let fields = parse_print("{print $2, \"is\" $1}").unwrap();
+        let space = PrintItem::Text(" ".to_string());
+        assert_eq!(fields, [PrintItem::Field(2), space, PrintItem::Text("is".to_string()), PrintItem::Field(1)]);
+        assert_eq!(awk_print("  a   b \nc\n", &fields, None), ["b isa", " isc"]);
+        assert_eq!(awk_print("x,,z", &parse_print("{ print $3 $0 }").unwrap(), Some(",")), ["zx,,z"]);
+        assert_eq!(parse_print("{print}").unwrap(), [PrintItem::Field(0)]);
+        assert!(parse_print("{printf $1}").is_err());
+        assert!(parse_print("$1 > 2 {print}").is_err());
+    }
+
+    #[test]
+    fn tr_sets_expand_ranges() {
+        assert_eq!(expand_set("a-d_"), vec!['a', 'b', 'c', 'd', '_']);
+        assert_eq!(expand_set("a-"), vec!['a', '-']);
+    }
+}
@@ -0,0 +1,120 @@
+//! Colors used for each kind of text the emulator shows.
+
+use crossterm::style::{Color, SetForegroundColor, Stylize};
+use std::fmt::Display;
+
+/// The color for each role of text on screen, chosen with `--theme <name>`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+    /// The command prompt.
+    pub prompt: Color,
+    /// Error messages.
+    pub error: Color,
+    /// Confirmations such as "File created."
+    pub success: Color,
+    /// Directory names in `ls` output.
+    pub directory: Color,
+    /// The welcome banner.
+    pub header: Color,
+    /// Pattern matches in `grep` output.
+    pub highlight: Color,
+    /// Background job notices.
+    pub notice: Color,
+    /// Whether any color is used; off with `--no-color` or the `NO_COLOR` variable.
+    pub colored: bool,
+}
+
+impl Theme {
+    /// Colors suited to most dark terminal backgrounds.
+    pub const DEFAULT: Theme = Theme {
+        prompt: Color::Cyan,
+        error: Color::Red,
+        success: Color::Green,
+        directory: Color::Blue,
+        header: Color::Green,
+        highlight: Color::Magenta,
+        notice: Color::Yellow,
+        colored: true,
+    };
+
+    /// Brighter, more distinct colors for low-contrast displays or light backgrounds.
+    pub const HIGH_CONTRAST: Theme = Theme {
+        prompt: Color::White,
+        error: Color::Red,
+        success: Color::Green,
+        directory: Color::Yellow,
+        header: Color::White,
+        highlight: Color::Cyan,
+        notice: Color::Magenta,
+        colored: true,
+    };
+
+    /// Names accepted by `--theme`, with the theme each selects.
+    pub const NAMED: &'static [(&'static str, Theme)] =
+        &[("default", Theme::DEFAULT), ("high-contrast", Theme::HIGH_CONTRAST)];
+
+    /// Returns the theme called `name`, if there is one.
+    pub fn named(name: &str) -> Option<Theme> {
+        Theme::NAMED.iter().find(|(theme_name, _)| *theme_name == name).map(|(_, theme)| *theme)
+    }
+
+    /// Colors text as the prompt.
+    pub fn prompt(&self, text: impl Display) -> String {
+        self.paint(text, self.prompt)
+    }
+
+    /// Colors text as an error message.
+    pub fn error(&self, text: impl Display) -> String {
+        self.paint(text, self.error)
+    }
+
+    /// Colors text as an error within the prompt, going back to the prompt's color after it
+    /// rather than to no color, so the rest of the prompt keeps its color.
+    pub fn prompt_error(&self, text: impl Display) -> String {
+        if self.colored {
+            format!("{}{}{}", SetForegroundColor(self.error), text, SetForegroundColor(self.prompt))
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Colors text as a confirmation.
+    pub fn success(&self, text: impl Display) -> String {
+        self.paint(text, self.success)
+    }
+
+    /// Colors text as a directory name.
+    pub fn directory(&self, text: impl Display) -> String {
+        self.paint(text, self.directory)
+    }
+
+    /// Colors text as the welcome banner.
+    pub fn header(&self, text: impl Display) -> String {
+        self.paint(text, self.header)
+    }
+
+    /// Colors text as a pattern match.
+    pub fn highlight(&self, text: impl Display) -> String {
+        self.paint(text, self.highlight)
+    }
+
+    /// Colors text as a background job notice.
+    pub fn notice(&self, text: impl Display) -> String {
+        self.paint(text, self.notice)
+    }
+
+    /// Wraps `text` in the escape codes for `color`, or leaves it plain without color.
+    fn paint(&self, text: impl Display, color: Color) -> String {
+        if self.colored {
+            text.to_string().with(color).to_string()
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::DEFAULT
+    }
+}
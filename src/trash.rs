@@ -0,0 +1,156 @@
+//! The `trash` command: a recoverable `rm` that moves files into a trash directory.
+
+use crate::shell::{combine_outputs, expand_path, resolve_path, CommandOutput, ShellContext};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The trash directory, in the home directory (the jail root inside a jail).
+const TRASH_DIR: &str = "~/.rust_emulator_trash";
+
+/// File in the trash directory recording where each entry came from, one
+/// `name<TAB>original path` line per entry, oldest first.
+const INDEX_FILE: &str = ".index";
+
+/// Handles `trash`: moves each file or directory named into the trash under a timestamped
+/// name, `--list` shows what the trash holds, and `--restore <name>...` moves entries back
+/// to where they came from.
+pub fn trash_command(args: Vec<&str>, ctx: &ShellContext) -> CommandOutput {
+    let dir = match expand_path(ctx, TRASH_DIR) {
+        Ok(dir) => dir,
+        Err(e) => return CommandOutput::error(e),
+    };
+    match args.split_first() {
+        Some((&"--list", [])) => list_trash(&dir, ctx),
+        Some((&"--restore", names)) if !names.is_empty() => {
+            combine_outputs(names.iter().map(|name| restore(&dir, name, ctx)).collect())
+        }
+        Some((flag, _)) if flag.starts_with('-') => {
+            CommandOutput::error("Error: Usage: trash <filename>... | trash --list | trash --restore <name>...")
+        }
+        Some(_) => combine_outputs(args.iter().map(|target| move_to_trash(&dir, target, ctx)).collect()),
+        None => CommandOutput::error("Error: File name is required."),
+    }
+}
+
+/// Moves `target` into the trash directory `dir`, creating the directory if needed.
+fn move_to_trash(dir: &Path, target: &str, ctx: &ShellContext) -> CommandOutput {
+    let path = match expand_path(ctx, target) {
+        Ok(path) => path,
+        Err(e) => return CommandOutput::error(e),
+    };
+    let original = resolve_path(ctx, &path);
+    let Some(name) = original.file_name().map(|name| name.to_string_lossy().to_string()) else {
+        return CommandOutput::error(format!("Error: trash: refusing to remove '{}'.", target));
+    };
+    if let Err(e) = ctx.fs.symlink_metadata(&path) {
+        return CommandOutput::error(format!("Error: trash: '{}': {}", target, e));
+    }
+    if ctx.fs.canonicalize(dir).is_ok_and(|trash| ctx.fs.canonicalize(&path).is_ok_and(|path| trash.starts_with(path))) {
+        return CommandOutput::error(format!("Error: trash: refusing to put '{}' in the trash it holds.", target));
+    }
+
+    if ctx.fs.metadata(dir).is_err() {
+        if let Err(e) = ctx.fs.create_dir(dir) {
+            return CommandOutput::error(format!("Error: trash: could not create '{}': {}", TRASH_DIR, e));
+        }
+    }
+    let stamped = format!("{}.{}", name, timestamp(SystemTime::now()));
+    let mut trashed = stamped.clone();
+    let mut copy = 1;
+    while ctx.fs.symlink_metadata(&dir.join(&trashed)).is_ok() {
+        copy += 1;
+        trashed = format!("{}-{}", stamped, copy);
+    }
+
+    if let Err(e) = ctx.fs.rename(&path, &dir.join(&trashed)) {
+        return CommandOutput::error(format!("Error: trash: '{}': {}", target, e));
+    }
+    let mut index = read_index(dir, ctx);
+    index.push((trashed.clone(), original));
+    if let Err(e) = write_index(dir, &index, ctx) {
+        return CommandOutput::error(format!("Error: trash: moved '{}' as '{}' but could not record it: {}", target, trashed, e));
+    }
+    CommandOutput::success(format!("Moved '{}' to the trash as '{}'.", target, trashed))
+}
+
+/// Lists the entries in the trash, oldest first, with the path each came from.
+fn list_trash(dir: &Path, ctx: &ShellContext) -> CommandOutput {
+    let index = read_index(dir, ctx);
+    let width = index.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    let lines: Vec<String> =
+        index.iter().map(|(name, original)| format!("{:<width$}  {}", name, original.display())).collect();
+    CommandOutput::success(lines.join("\n"))
+}
+
+/// Moves the entry `name` out of the trash back to where it came from. `name` is either the
+/// entry's name in the trash or the name it had before, which picks the newest such entry.
+fn restore(dir: &Path, name: &str, ctx: &ShellContext) -> CommandOutput {
+    let mut index = read_index(dir, ctx);
+    let found = index.iter().position(|(trashed, _)| trashed == name).or_else(|| {
+        index.iter().rposition(|(_, original)| original.file_name().is_some_and(|original| original == name))
+    });
+    let Some(position) = found else {
+        return CommandOutput::error(format!("Error: trash: '{}' is not in the trash.", name));
+    };
+
+    let (trashed, original) = &index[position];
+    // The index is a file like any other, so where it says to restore to is checked as a
+    // typed path is, which keeps the entry inside a jail
+    let original = match expand_path(ctx, &original.to_string_lossy()) {
+        Ok(original) => original,
+        Err(e) => return CommandOutput::error(format!("Error: trash: cannot restore '{}': {}", trashed, e)),
+    };
+    if ctx.fs.symlink_metadata(&original).is_ok() {
+        return CommandOutput::error(format!(
+            "Error: trash: '{}' already exists; move it away before restoring '{}'.",
+            original.display(),
+            trashed
+        ));
+    }
+    if let Err(e) = ctx.fs.rename(&dir.join(trashed), &original) {
+        return CommandOutput::error(format!("Error: trash: could not restore '{}': {}", trashed, e));
+    }
+    let message = format!("Restored '{}' to '{}'.", trashed, original.display());
+    index.remove(position);
+    match write_index(dir, &index, ctx) {
+        Ok(()) => CommandOutput::success(message),
+        Err(e) => CommandOutput::error(format!("{} Error: trash: could not update the index: {}", message, e)),
+    }
+}
+
+/// Reads the trash's index, leaving out entries no longer in the trash directory and names
+/// that are not plain file names in it. A missing index is an empty trash.
+fn read_index(dir: &Path, ctx: &ShellContext) -> Vec<(String, PathBuf)> {
+    let contents = ctx.fs.read_to_string(&dir.join(INDEX_FILE)).unwrap_or_default();
+    contents
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .filter(|(name, _)| Path::new(name).file_name().is_some_and(|file_name| file_name == *name))
+        .filter(|(name, _)| ctx.fs.symlink_metadata(&dir.join(name)).is_ok())
+        .map(|(name, original)| (name.to_string(), PathBuf::from(original)))
+        .collect()
+}
+
+/// Replaces the trash's index with `index`.
+fn write_index(dir: &Path, index: &[(String, PathBuf)], ctx: &ShellContext) -> std::io::Result<()> {
+    let contents: String = index.iter().map(|(name, original)| format!("{}\t{}\n", name, original.display())).collect();
+    ctx.fs.write(&dir.join(INDEX_FILE), &contents)
+}
+
+/// Formats `time` as `YYYYMMDD-HHMMSS` in UTC, for names in the trash.
+fn timestamp(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).format("%Y%m%d-%H%M%S").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn timestamps_are_compact_utc_times() {
+        assert_eq!(timestamp(UNIX_EPOCH), "19700101-000000");
+        assert_eq!(timestamp(UNIX_EPOCH + std::time::Duration::from_secs(1_760_517_012)), "20251015-083012");
+    }
+}
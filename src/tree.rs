@@ -0,0 +1,125 @@
+//! The `tree` command: a directory and everything below it, drawn as an indented tree.
+
+use crate::shell::{expand_path, CommandOutput, ShellContext};
+use crate::theme::Theme;
+use crate::walk::Walker;
+
+/// An entry below the directory being drawn: its depth, starting from 1, its name, and
+/// whether it is a directory.
+type TreeEntry = (usize, String, bool);
+
+/// Handles the `tree` command: shows each directory given, or the current one, with the
+/// entries inside it drawn as branches, then a count of the directories and files shown.
+///
+/// `--gitignore` leaves out what `.gitignore` files ignore, and `.git` directories.
+pub fn tree_command(args: Vec<&str>, ctx: &ShellContext) -> CommandOutput {
+    let mut gitignore = false;
+    let mut roots = Vec::new();
+    for arg in args {
+        match arg {
+            "--gitignore" => gitignore = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return CommandOutput::error(format!("Error: tree: unrecognized option '{}'.", arg))
+            }
+            _ => roots.push(arg),
+        }
+    }
+    if roots.is_empty() {
+        roots.push(".");
+    }
+
+    let theme = &ctx.options.theme;
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+    let (mut directories, mut files) = (0, 0);
+    // Past the lines the screen shows, the rest of the walk is not worth doing
+    let limit = ctx.line_limit();
+    let mut walker = Walker::new(ctx, "tree", gitignore);
+    for root in roots {
+        let path = match expand_path(ctx, root) {
+            Ok(path) => path,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        let info = match ctx.fs.metadata(&path) {
+            Ok(info) => info,
+            Err(e) => {
+                errors.push(format!("Error: tree: '{}': {}", root, e));
+                continue;
+            }
+        };
+
+        let mut entries: Vec<TreeEntry> = Vec::new();
+        walker.walk(&path, root, &info, &mut |entry| {
+            if limit.is_some_and(|limit| lines.len() + entries.len() > limit) {
+                return false;
+            }
+            if entry.depth > 0 {
+                entries.push((entry.depth, entry.name.to_string(), entry.info.is_dir));
+            }
+            true
+        });
+        errors.append(&mut walker.errors);
+
+        lines.push(if info.is_dir { theme.directory(root) } else { root.to_string() });
+        lines.extend(tree_lines(&entries, theme));
+        let found_directories = entries.iter().filter(|(_, _, is_dir)| *is_dir).count();
+        directories += found_directories;
+        files += entries.len() - found_directories;
+    }
+
+    lines.push(String::new());
+    lines.push(format!(
+        "{} director{}, {} file{}",
+        directories,
+        if directories == 1 { "y" } else { "ies" },
+        files,
+        if files == 1 { "" } else { "s" }
+    ));
+    let exit_code = if errors.is_empty() { 0 } else { 1 };
+    CommandOutput { stdout: lines.join("\n"), stderr: errors.join("\n"), exit_code }
+}
+
+/// Draws `entries`, listed depth-first, one per line: each name follows a branch that
+/// shows whether more entries come after it in its directory, and the lines of the
+/// directories above it that still have entries to come.
+fn tree_lines(entries: &[TreeEntry], theme: &Theme) -> Vec<String> {
+    // For each depth above the entry being drawn, whether that directory has more to come
+    let mut continuing: Vec<bool> = Vec::new();
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, (depth, name, is_dir))| {
+            let last = entries[index + 1..]
+                .iter()
+                .find(|(later, _, _)| later <= depth)
+                .is_none_or(|(later, _, _)| later < depth);
+            continuing.truncate(depth - 1);
+            let mut line: String = continuing.iter().map(|&more| if more { "│   " } else { "    " }).collect();
+            line.push_str(if last { "└── " } else { "├── " });
+            line.push_str(&if *is_dir { theme.directory(name) } else { name.clone() });
+            continuing.push(!last);
+            line
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn branches_continue_past_directories_with_more_entries() {
+        let theme = Theme { colored: false, ..Theme::DEFAULT };
+        let entries: Vec<TreeEntry> = [(1, "docs", true), (2, "guide.md", false), (2, "old", true), (3, "v1.md", false), (1, "main.rs", false)]
+            .into_iter()
+            .map(|(depth, name, is_dir)| (depth, name.to_string(), is_dir))
+            .collect();
+        assert_eq!(
+            tree_lines(&entries, &theme),
+            ["├── docs", "│   ├── guide.md", "│   └── old", "│       └── v1.md", "└── main.rs"]
+        );
+    }
+}
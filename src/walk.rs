@@ -0,0 +1,179 @@
+//! Walking directory trees for `find`, `grep -r`, and `tree`, optionally skipping what
+//! `.gitignore` files rule out.
+
+use crate::filesystem::FileInfo;
+use crate::shell::ShellContext;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::Path;
+
+/// An entry reached by a [`Walker`].
+pub struct Entry<'a> {
+    /// The entry's path on the filesystem.
+    pub path: &'a Path,
+    /// The entry's path as the command shows it, starting from the path it was given.
+    pub shown: &'a str,
+    /// The last part of the path.
+    pub name: &'a str,
+    /// The entry's type, size, and modification time.
+    pub info: &'a FileInfo,
+    /// How many directories below the starting point the entry is; the starting point is 0.
+    pub depth: usize,
+}
+
+/// A depth-first walk through a directory tree, each directory's entries in name order.
+///
+/// With `gitignore` set, each directory's `.gitignore` applies to everything below it, with
+/// the rules of deeper files taking precedence, and `.git` directories are skipped.
+pub struct Walker<'a> {
+    ctx: &'a ShellContext,
+    /// The command walking, for error messages.
+    command: &'a str,
+    gitignore: bool,
+    /// The `.gitignore` rules of the directories being walked through, outermost first.
+    rules: Vec<Gitignore>,
+    /// Messages for the directories that could not be read and entries that could not be
+    /// examined; the walk goes on past them.
+    pub errors: Vec<String>,
+}
+
+impl<'a> Walker<'a> {
+    /// Creates a walk for `command`, honoring `.gitignore` files if `gitignore` is set.
+    pub fn new(ctx: &'a ShellContext, command: &'a str, gitignore: bool) -> Self {
+        Walker { ctx, command, gitignore, rules: Vec::new(), errors: Vec::new() }
+    }
+
+    /// Calls `visit` with the entry at `path`, shown as `shown`, then, if it is a directory
+    /// and `visit` returned true, walks each entry inside it the same way.
+    pub fn walk(&mut self, path: &Path, shown: &str, info: &FileInfo, visit: &mut impl FnMut(&Entry) -> bool) {
+        let name = Path::new(shown).file_name().map_or(shown.to_string(), |name| name.to_string_lossy().to_string());
+        self.visit(path, shown, &name, info, 0, visit);
+    }
+
+    fn visit(
+        &mut self,
+        path: &Path,
+        shown: &str,
+        name: &str,
+        info: &FileInfo,
+        depth: usize,
+        visit: &mut impl FnMut(&Entry) -> bool,
+    ) {
+        let entry = Entry { path, shown, name, info, depth };
+        if !visit(&entry) || !info.is_dir {
+            return;
+        }
+
+        let mut names = match self.ctx.fs.read_dir(path) {
+            Ok(names) => names,
+            Err(e) => {
+                self.errors.push(format!("Error: {}: '{}': {}", self.command, display_name(shown), e));
+                return;
+            }
+        };
+        names.sort();
+        let has_rules = self.gitignore && self.load_rules(path);
+        for child in names {
+            let child_path = path.join(&child);
+            let child_shown = match shown {
+                "" => child.clone(),
+                _ if shown.ends_with('/') => format!("{}{}", shown, child),
+                _ => format!("{}/{}", shown, child),
+            };
+            match self.ctx.fs.metadata(&child_path) {
+                Ok(child_info) if self.is_ignored(&child_path, &child, &child_info) => {}
+                Ok(child_info) => self.visit(&child_path, &child_shown, &child, &child_info, depth + 1, visit),
+                Err(e) => self.errors.push(format!("Error: {}: '{}': {}", self.command, child_shown, e)),
+            }
+        }
+        if has_rules {
+            self.rules.pop();
+        }
+    }
+
+    /// Adds the rules of the `.gitignore` in the directory at `dir`, if it has one. Returns
+    /// true if rules were added. Lines that are not valid patterns are left out.
+    fn load_rules(&mut self, dir: &Path) -> bool {
+        let Ok(contents) = self.ctx.fs.read_to_string(&dir.join(".gitignore")) else {
+            return false;
+        };
+        let mut builder = GitignoreBuilder::new(dir);
+        for line in contents.lines() {
+            let _ = builder.add_line(None, line);
+        }
+        match builder.build() {
+            Ok(rules) => {
+                self.rules.push(rules);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Returns true if the entry is left out of a `.gitignore`-aware walk: it is a `.git`
+    /// directory, or the nearest `.gitignore` with a rule for it ignores it.
+    fn is_ignored(&self, path: &Path, name: &str, info: &FileInfo) -> bool {
+        if !self.gitignore {
+            return false;
+        }
+        if info.is_dir && name == ".git" {
+            return true;
+        }
+        self.rules
+            .iter()
+            .rev()
+            .map(|rules| rules.matched(path, info.is_dir))
+            .find(|found| !found.is_none())
+            .is_some_and(|found| found.is_ignore())
+    }
+}
+
+/// Returns how a path shown as `shown` appears in messages, where the current directory,
+/// shown as nothing, is `.`.
+fn display_name(shown: &str) -> &str {
+    if shown.is_empty() {
+        "."
+    } else {
+        shown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{FileSystem, MemoryFileSystem};
+    use std::sync::Arc;
+
+    fn walked(ctx: &ShellContext, gitignore: bool) -> Vec<String> {
+        let root = Path::new("/");
+        let info = ctx.fs.metadata(root).unwrap();
+        let mut shown = Vec::new();
+        Walker::new(ctx, "test", gitignore).walk(root, "", &info, &mut |entry| {
+            shown.push(entry.shown.to_string());
+            true
+        });
+        shown
+    }
+
+    #[test]
+    fn gitignore_rules_skip_entries_and_deeper_files_take_precedence() {
+        let fs = MemoryFileSystem::new();
+        for dir in ["/.git", "/src", "/target"] {
+            fs.create_dir(Path::new(dir)).unwrap();
+        }
+        for (path, text) in [
+            ("/.gitignore", "target/\n*.log\n"),
+            ("/.git/HEAD", ""),
+            ("/app.log", ""),
+            ("/src/.gitignore", "!keep.log\n"),
+            ("/src/keep.log", ""),
+            ("/src/main.rs", ""),
+            ("/target/out", ""),
+        ] {
+            fs.write(Path::new(path), text).unwrap();
+        }
+        let ctx = ShellContext { fs: Arc::new(fs), ..Default::default() };
+
+        assert_eq!(walked(&ctx, true), ["", ".gitignore", "src", "src/.gitignore", "src/keep.log", "src/main.rs"]);
+        assert_eq!(walked(&ctx, false).len(), 11);
+    }
+}
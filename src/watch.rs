@@ -0,0 +1,68 @@
+//! Watching the current directory for changes made outside the emulator, for `--watch`.
+
+use notify::{recommended_watcher, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A watch on one directory that notes when files in it are created, removed, renamed,
+/// or changed, so the screen can suggest running `ls` again.
+pub struct DirWatch {
+    dir: PathBuf,
+    changed: Arc<AtomicBool>,
+    // Kept so the watch lasts as long as this does
+    _watcher: RecommendedWatcher,
+}
+
+impl DirWatch {
+    /// Starts watching `dir`, not including its subdirectories' contents.
+    pub fn new(dir: &Path) -> notify::Result<Self> {
+        let changed = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&changed);
+        let mut watcher = recommended_watcher(move |event: notify::Result<notify::Event>| {
+            // Reading a file, as `ls` and `cat` do, changes nothing worth listing again
+            if event.is_ok_and(|event| !matches!(event.kind, EventKind::Access(_))) {
+                flag.store(true, Ordering::Relaxed);
+            }
+        })?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(DirWatch { dir: dir.to_path_buf(), changed, _watcher: watcher })
+    }
+
+    /// Returns the directory being watched.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns true if the directory has changed since the watch started or was last reset.
+    pub fn changed(&self) -> bool {
+        self.changed.load(Ordering::Relaxed)
+    }
+
+    /// Forgets the changes seen so far, once the directory has been listed again.
+    pub fn reset(&self) {
+        self.changed.store(false, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn new_files_mark_the_directory_changed() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let watch = DirWatch::new(dir.path()).unwrap();
+        assert!(!watch.changed());
+
+        std::fs::write(dir.path().join("new.txt"), "hello").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !watch.changed() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(watch.changed());
+        watch.reset();
+        assert!(!watch.changed());
+    }
+}